@@ -24,6 +24,7 @@ extern crate structopt;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
+use navitia_model::ntfs::{GeneratedStopAreas, Version, WriteOptions};
 use navitia_model::Result;
 
 #[derive(Debug, StructOpt)]
@@ -44,6 +45,20 @@ struct Opt {
     /// prefix
     #[structopt(short = "p", long = "prefix")]
     prefix: Option<String>,
+
+    /// NTFS version of the output
+    #[structopt(long = "ntfs-version", default_value = "0.7")]
+    ntfs_version: Version,
+
+    /// write the stop areas generated for the stop points without
+    /// parent station
+    #[structopt(long = "materialize-generated-stop-areas")]
+    materialize_generated_stop_areas: bool,
+
+    /// merge the calendars running on the same days and minimize
+    /// their exceptions
+    #[structopt(long = "compress-calendars")]
+    compress_calendars: bool,
 }
 
 fn run() -> Result<()> {
@@ -51,9 +66,20 @@ fn run() -> Result<()> {
 
     let opt = Opt::from_args();
 
-    let objects = navitia_model::gtfs::read(opt.input, opt.config_path, opt.prefix)?;
+    let mut objects = navitia_model::gtfs::read(opt.input, opt.config_path, opt.prefix)?;
+    if opt.compress_calendars {
+        objects = objects.edit(|collections| collections.compress_calendars())?;
+    }
 
-    navitia_model::ntfs::write(opt.output, &objects)?;
+    let write_options = WriteOptions {
+        version: opt.ntfs_version,
+        generated_stop_areas: if opt.materialize_generated_stop_areas {
+            GeneratedStopAreas::Materialize
+        } else {
+            GeneratedStopAreas::Drop
+        },
+    };
+    navitia_model::ntfs::write_with_options(opt.output, &objects, &write_options)?;
     Ok(())
 }
 
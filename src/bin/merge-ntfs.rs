@@ -0,0 +1,82 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate env_logger;
+#[macro_use]
+extern crate log;
+extern crate navitia_model;
+#[macro_use]
+extern crate structopt;
+
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+use navitia_model::merge::ConflictPolicy;
+use navitia_model::ntfs::Version;
+use navitia_model::{PtObjects, Result};
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "merge-ntfs", about = "Merge several NTFS into one.")]
+struct Opt {
+    /// input directories, merged in the given order
+    #[structopt(short = "i", long = "input", parse(from_os_str), raw(required = "true"))]
+    inputs: Vec<PathBuf>,
+
+    /// output directory
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: PathBuf,
+
+    /// what to do with conflicting ids: error, keep-first or prefix
+    #[structopt(long = "on-conflict", default_value = "error")]
+    on_conflict: ConflictPolicy,
+
+    /// NTFS version of the output
+    #[structopt(long = "ntfs-version", default_value = "0.7")]
+    ntfs_version: Version,
+
+    /// remove the objects not used by any vehicle journey
+    #[structopt(long = "sanitize")]
+    sanitize: bool,
+}
+
+fn run() -> Result<()> {
+    info!("Launching merge-ntfs...");
+
+    let opt = Opt::from_args();
+
+    let models = opt.inputs
+        .iter()
+        .map(|input| navitia_model::ntfs::read(input).map(PtObjects::into_collections))
+        .collect::<navitia_model::error::Result<_>>()?;
+    let mut merged = navitia_model::merge::merge(models, opt.on_conflict)?;
+    if opt.sanitize {
+        merged.sanitize();
+    }
+    let objects = PtObjects::new(merged)?;
+
+    navitia_model::ntfs::write(opt.output, &objects, opt.ntfs_version)?;
+    Ok(())
+}
+
+fn main() {
+    env_logger::init();
+    if let Err(err) = run() {
+        for cause in err.causes() {
+            eprintln!("{}", cause);
+        }
+        std::process::exit(1);
+    }
+}
@@ -14,17 +14,24 @@
 // along with this program.  If not, see
 // <http://www.gnu.org/licenses/>.
 
+extern crate chrono;
 extern crate env_logger;
 #[macro_use]
 extern crate log;
 extern crate navitia_model;
+extern crate serde_json;
 #[macro_use]
 extern crate structopt;
 
+use std::fs::File;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
-use navitia_model::Result;
+use navitia_model::ntfs::{GeneratedStopAreas, StopTimesWriter, Version, WriteOptions};
+use navitia_model::objects::{Date, Timezone};
+use navitia_model::read_report::{ReadReport, Strictness};
+use navitia_model::restrict::{BoundingBox, Selection};
+use navitia_model::{PtObjects, Result};
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "ntfs2ntfs", about = "Convert an NTFS to an NTFS.")]
@@ -36,6 +43,102 @@ struct Opt {
     /// output directory
     #[structopt(short = "o", long = "output", parse(from_os_str))]
     output: Option<PathBuf>,
+
+    /// binary snapshot of the output, with its relations, much faster
+    /// to load than an NTFS
+    #[structopt(long = "snapshot", parse(from_os_str))]
+    snapshot: Option<PathBuf>,
+
+    /// keep the unknown files and columns of the input in the output
+    #[structopt(short = "k", long = "keep-extra-data")]
+    keep_extra_data: bool,
+
+    /// interpolate the stop times without time instead of failing
+    #[structopt(long = "interpolate-missing-times")]
+    interpolate_missing_times: bool,
+
+    /// skip the rows that cannot be read instead of failing
+    #[structopt(long = "lenient")]
+    lenient: bool,
+
+    /// number of threads reading the input and building its
+    /// relations, one per CPU by default
+    #[structopt(long = "threads")]
+    threads: Option<usize>,
+
+    /// JSON report of the rows and values ignored when reading
+    #[structopt(long = "read-report", parse(from_os_str))]
+    read_report: Option<PathBuf>,
+
+    /// NTFS version of the output
+    #[structopt(long = "ntfs-version", default_value = "0.7")]
+    ntfs_version: Version,
+
+    /// write the stop areas generated for the stop points without
+    /// parent station
+    #[structopt(long = "materialize-generated-stop-areas")]
+    materialize_generated_stop_areas: bool,
+
+    /// remove the objects not used by any vehicle journey
+    #[structopt(long = "sanitize")]
+    sanitize: bool,
+
+    /// merge the calendars running on the same days and minimize
+    /// their exceptions
+    #[structopt(long = "compress-calendars")]
+    compress_calendars: bool,
+
+    /// express all the times in this timezone, splitting the vehicle
+    /// journeys when the offset changes (as "Europe/Paris")
+    #[structopt(long = "normalize-timezone")]
+    normalize_timezone: Option<Timezone>,
+
+    /// restrict the output to the days from this date (YYYYMMDD)
+    #[structopt(long = "start-date", parse(try_from_str = "parse_date"),
+                raw(requires = r#""end_date""#))]
+    start_date: Option<Date>,
+
+    /// restrict the output to the days until this date (YYYYMMDD)
+    #[structopt(long = "end-date", parse(try_from_str = "parse_date"),
+                raw(requires = r#""start_date""#))]
+    end_date: Option<Date>,
+
+    /// keep only the vehicle journeys of these networks
+    #[structopt(long = "network")]
+    networks: Vec<String>,
+
+    /// keep only the vehicle journeys of these lines
+    #[structopt(long = "line")]
+    lines: Vec<String>,
+
+    /// keep only the vehicle journeys of these physical modes
+    #[structopt(long = "physical-mode")]
+    physical_modes: Vec<String>,
+
+    /// keep only the vehicle journeys of these contributors
+    #[structopt(long = "contributor")]
+    contributors: Vec<String>,
+
+    /// keep only the vehicle journeys stopping in this bounding box
+    /// (min_lon,min_lat,max_lon,max_lat)
+    #[structopt(long = "bbox")]
+    bounding_box: Option<BoundingBox>,
+}
+
+fn parse_date(date: &str) -> std::result::Result<Date, chrono::ParseError> {
+    Date::parse_from_str(date, "%Y%m%d")
+}
+
+fn write_read_report(report: &ReadReport, path: Option<PathBuf>) -> Result<()> {
+    info!(
+        "{} rows skipped and {} values ignored",
+        report.skipped_rows.len(),
+        report.ignored_values.len()
+    );
+    if let Some(path) = path {
+        serde_json::to_writer_pretty(File::create(path)?, report)?;
+    }
+    Ok(())
 }
 
 fn run() -> Result<()> {
@@ -43,10 +146,76 @@ fn run() -> Result<()> {
 
     let opt = Opt::from_args();
 
-    let objects = navitia_model::ntfs::read(opt.input)?;
+    let options = navitia_model::ntfs::ReadOptions {
+        keep_extra_data: opt.keep_extra_data,
+        interpolate_missing_times: opt.interpolate_missing_times,
+        strictness: if opt.lenient {
+            Strictness::Lenient
+        } else {
+            Strictness::Strict
+        },
+        threads: opt.threads,
+        ..navitia_model::ntfs::ReadOptions::default()
+    };
+    let write_options = WriteOptions {
+        version: opt.ntfs_version,
+        generated_stop_areas: if opt.materialize_generated_stop_areas {
+            GeneratedStopAreas::Materialize
+        } else {
+            GeneratedStopAreas::Drop
+        },
+    };
+    let selection = Selection {
+        networks: opt.networks,
+        lines: opt.lines,
+        physical_modes: opt.physical_modes,
+        contributors: opt.contributors,
+        bounding_box: opt.bounding_box,
+    };
+    let transformed = !selection.is_empty()
+        || opt.start_date.is_some()
+        || opt.sanitize
+        || opt.normalize_timezone.is_some()
+        || opt.compress_calendars;
+    if let (false, None, Some(output)) = (transformed, &opt.snapshot, &opt.output) {
+        // the stop times are written as they are read, so that they are
+        // never all in memory
+        let mut stop_times = StopTimesWriter::new(output)?;
+        let (collections, report) =
+            navitia_model::ntfs::visit_vehicle_journeys(opt.input, &options, |c, vj| {
+                Ok(stop_times.write(c, vj)?)
+            })?;
+        write_read_report(&report, opt.read_report)?;
+        let objects = PtObjects::new(collections)?;
+        navitia_model::ntfs::write_with_stop_times(output, &objects, &write_options, stop_times)?;
+        return Ok(());
+    }
+
+    let (mut objects, report) = navitia_model::ntfs::read_with_report(opt.input, &options)?;
+    write_read_report(&report, opt.read_report)?;
+    objects = objects.extract(&selection)?;
+    if let (Some(start_date), Some(end_date)) = (opt.start_date, opt.end_date) {
+        objects = objects.edit(|collections| {
+            collections.restrict_validity_period(start_date, end_date)?;
+            Ok(())
+        })?;
+    }
+    if opt.sanitize {
+        objects = objects.sanitize()?.0;
+    }
+    if let Some(timezone) = opt.normalize_timezone {
+        objects = objects.normalize_timezones(timezone)?;
+    }
+    if opt.compress_calendars {
+        objects = objects.edit(|collections| collections.compress_calendars())?;
+    }
 
     if let Some(output) = opt.output {
-        navitia_model::ntfs::write(output, &objects)?;
+        navitia_model::ntfs::write_with_options(output, &objects, &write_options)?;
+    }
+    if let Some(snapshot) = opt.snapshot {
+        let options = navitia_model::snapshot::WriteOptions { relations: true };
+        navitia_model::snapshot::write(&objects, snapshot, &options)?;
     }
     Ok(())
 }
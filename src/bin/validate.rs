@@ -0,0 +1,90 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate env_logger;
+#[macro_use]
+extern crate log;
+extern crate navitia_model;
+extern crate serde_json;
+#[macro_use]
+extern crate structopt;
+
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+use navitia_model::ntfs::ReadOptions;
+use navitia_model::read_report::Strictness;
+use navitia_model::validate::Severity;
+use navitia_model::Result;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "validate",
+            about = "Validate an NTFS, exiting with an error if it is not valid.")]
+struct Opt {
+    /// input directory.
+    #[structopt(short = "i", long = "input", parse(from_os_str), default_value = ".")]
+    input: PathBuf,
+
+    /// JSON report, written on the standard output if not given
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    /// add to the issues the rows of their objects
+    #[structopt(short = "p", long = "provenance")]
+    provenance: bool,
+}
+
+fn run() -> Result<bool> {
+    info!("Launching validate...");
+
+    let opt = Opt::from_args();
+
+    // the rows that cannot be read are reported with the other issues
+    let options = ReadOptions {
+        strictness: Strictness::Lenient,
+        track_provenance: opt.provenance,
+        ..ReadOptions::default()
+    };
+    let (collections, read_report) = navitia_model::ntfs::read_collections(opt.input, &options)?;
+    let mut report = collections.validate();
+    report.add_read_report(read_report);
+    info!(
+        "{} errors and {} warnings",
+        report.count(Severity::Error),
+        report.count(Severity::Warning)
+    );
+    match opt.output {
+        Some(output) => serde_json::to_writer_pretty(File::create(output)?, &report)?,
+        None => serde_json::to_writer_pretty(io::stdout(), &report)?,
+    }
+    Ok(report.is_valid())
+}
+
+fn main() {
+    env_logger::init();
+    match run() {
+        Ok(true) => (),
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            for cause in err.causes() {
+                eprintln!("{}", cause);
+            }
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! Operations on the calendars of a model.
+
+use std::collections::HashMap;
+use collection::CollectionWithId;
+use objects::*;
+use {Collections, Result};
+
+impl Collections {
+    /// Rewrites each calendar with the weekdays and period giving the
+    /// fewest exceptions, and merges the calendars running on the same
+    /// days into the first of them, updating the vehicle journeys.
+    pub fn compress_calendars(&mut self) -> Result<()> {
+        let mut calendars: Vec<Calendar> = vec![];
+        let mut idx_by_dates = HashMap::new();
+        let mut service_ids = HashMap::new();
+        for calendar in self.calendars.take() {
+            let vp = calendar.validity_pattern();
+            match idx_by_dates.get(&vp.dates()).cloned() {
+                Some(i) => {
+                    let kept: &mut Calendar = &mut calendars[i];
+                    service_ids.insert(calendar.id, kept.id.clone());
+                    kept.codes.extend(calendar.codes);
+                    kept.object_properties.extend(calendar.object_properties);
+                    for link in calendar.comment_links {
+                        if !kept.comment_links.contains(&link) {
+                            kept.comment_links.push(link);
+                        }
+                    }
+                }
+                None => {
+                    idx_by_dates.insert(vp.dates(), calendars.len());
+                    calendars.push(Calendar {
+                        codes: calendar.codes,
+                        object_properties: calendar.object_properties,
+                        comment_links: calendar.comment_links,
+                        ..Calendar::from_validity_pattern(calendar.id, &vp)
+                    });
+                }
+            }
+        }
+        info!(
+            "Compressing calendars: {} merged into others",
+            service_ids.len()
+        );
+        self.calendars = CollectionWithId::new(calendars)?;
+
+        let idxs: Vec<_> = self.vehicle_journeys.iter().map(|(idx, _)| idx).collect();
+        for idx in idxs {
+            let new_id = service_ids.get(&self.vehicle_journeys[idx].service_id).cloned();
+            if let Some(new_id) = new_id {
+                self.vehicle_journeys.index_mut(idx).service_id = new_id;
+            }
+        }
+        Ok(())
+    }
+}
@@ -14,18 +14,18 @@
 // along with this program.  If not, see
 // <http://www.gnu.org/licenses/>.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
 use std::iter;
 use std::slice;
 use std::ops;
 use std::cmp::Ordering;
-use {AddPrefix, Result, StdResult};
-use failure::ResultExt;
+use {AddPrefix, Error, Result, StdResult};
+use error::{type_name, ErrorKind};
 use std::path;
-use csv;
 use serde;
 use std::borrow::Borrow;
+use read_report::Reader;
 
 pub trait Id<T> {
     fn id(&self) -> &str;
@@ -40,7 +40,7 @@ impl<T> Idx<T> {
     fn new(idx: usize) -> Self {
         Idx(idx as u32, PhantomData)
     }
-    fn get(&self) -> usize {
+    pub(crate) fn get(&self) -> usize {
         self.0 as usize
     }
 }
@@ -55,6 +55,44 @@ impl<T> PartialOrd for Idx<T> {
     }
 }
 
+// An `Idx` is only meaningful with its collection, thus it is only
+// (de)serialized with it, as in the snapshots.
+impl<T> serde::Serialize for Idx<T> {
+    fn serialize<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u32(self.0)
+    }
+}
+impl<'de, T> serde::Deserialize<'de> for Idx<T> {
+    fn deserialize<D>(deserializer: D) -> StdResult<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        u32::deserialize(deserializer).map(|idx| Idx(idx, PhantomData))
+    }
+}
+
+/// The new indexes of the objects of a collection after its
+/// modification, to fix up the `Idx` kept elsewhere.
+#[derive(Debug)]
+pub struct IdxMapping<T> {
+    new_idxs: Vec<Option<Idx<T>>>,
+}
+
+impl<T> IdxMapping<T> {
+    /// The new index of the object at `idx`, `None` if it was removed.
+    pub fn get(&self, idx: Idx<T>) -> Option<Idx<T>> {
+        self.new_idxs.get(idx.get()).and_then(|idx| *idx)
+    }
+
+    /// Updates `idxs`, dropping the indexes of the removed objects.
+    pub fn apply(&self, idxs: &mut Vec<Idx<T>>) {
+        *idxs = idxs.iter().filter_map(|&idx| self.get(idx)).collect();
+    }
+}
+
 #[derive(Debug)]
 pub struct Collection<T> {
     objects: Vec<T>,
@@ -84,6 +122,16 @@ impl<T> Collection<T> {
     }
 }
 
+impl<T> Collection<T> {
+    pub fn into_vec(self) -> Vec<T> {
+        self.objects
+    }
+
+    pub fn take(&mut self) -> Vec<T> {
+        ::std::mem::take(&mut self.objects)
+    }
+}
+
 impl<T> Default for Collection<T> {
     fn default() -> Self {
         Collection::new(Vec::default())
@@ -160,17 +208,23 @@ where
 pub type Iter<'a, T> =
     iter::Map<iter::Enumerate<slice::Iter<'a, T>>, fn((usize, &T)) -> (Idx<T>, &T)>;
 
+fn duplicate_id<T>(id: &str) -> Error {
+    ErrorKind::DuplicateId {
+        collection: type_name::<T>(),
+        id: id.to_string(),
+    }.into()
+}
+
 impl<T: Id<T>> CollectionWithId<T> {
     pub fn new(v: Vec<T>) -> Result<Self> {
         let mut id_to_idx = HashMap::default();
         for (i, obj) in v.iter().enumerate() {
-            ensure!(
-                id_to_idx
-                    .insert(obj.id().to_string(), Idx::new(i))
-                    .is_none(),
-                "{} already found",
-                obj.id()
-            );
+            if id_to_idx
+                .insert(obj.id().to_string(), Idx::new(i))
+                .is_some()
+            {
+                return Err(duplicate_id::<T>(obj.id()));
+            }
         }
         Ok(CollectionWithId {
             collection: Collection::new(v),
@@ -184,6 +238,115 @@ impl<T: Id<T>> CollectionWithId<T> {
             collection: self,
         }
     }
+
+    /// Gives each object, with its index, to `f`, stopping at its
+    /// first error.  Unlike `index_mut`, the ids are not copied, so `f`
+    /// must not change them: it fails if it does, the collection still
+    /// indexing the object by its old id.
+    pub fn try_for_each_mut<F>(&mut self, mut f: F) -> Result<()>
+    where
+        F: FnMut(Idx<T>, &mut T) -> Result<()>,
+    {
+        for (i, obj) in self.collection.objects.iter_mut().enumerate() {
+            let idx = Idx::new(i);
+            f(idx, obj)?;
+            if self.id_to_idx.get(obj.id()) != Some(&idx) {
+                return Err(ErrorKind::InvalidValue {
+                    field: "id",
+                    value: obj.id().to_string(),
+                    message: format!(
+                        "changing the id of a {} to {} is not allowed here",
+                        type_name::<T>(),
+                        obj.id()
+                    ),
+                }.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends `obj`, failing if its id is already used.  The indexes
+    /// of the objects already in the collection are unchanged.
+    pub fn push(&mut self, obj: T) -> Result<Idx<T>> {
+        let idx = Idx::new(self.objects.len());
+        if self.id_to_idx.contains_key(obj.id()) {
+            return Err(duplicate_id::<T>(obj.id()));
+        }
+        self.id_to_idx.insert(obj.id().to_string(), idx);
+        self.collection.objects.push(obj);
+        Ok(idx)
+    }
+
+    /// Appends all the objects of `iter`, failing without modifying
+    /// the collection if one of their ids is already used.  The
+    /// indexes of the objects already in the collection are unchanged.
+    /// Returns the new indexes of the objects of `iter`, the index of
+    /// the nth one being the new index of `Idx` n.
+    pub fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) -> Result<IdxMapping<T>> {
+        let objects: Vec<T> = iter.into_iter().collect();
+        let mut new_ids = HashSet::new();
+        for obj in &objects {
+            if self.id_to_idx.contains_key(obj.id()) || !new_ids.insert(obj.id()) {
+                return Err(duplicate_id::<T>(obj.id()));
+            }
+        }
+        let mut new_idxs = Vec::with_capacity(objects.len());
+        for obj in objects {
+            let idx = Idx::new(self.objects.len());
+            self.id_to_idx.insert(obj.id().to_string(), idx);
+            self.collection.objects.push(obj);
+            new_idxs.push(Some(idx));
+        }
+        Ok(IdxMapping { new_idxs })
+    }
+
+    /// Appends all the objects of `other`, failing without modifying
+    /// the collection if one of their ids is already used.  Returns
+    /// the new indexes of the objects of `other`.
+    pub fn merge(&mut self, other: CollectionWithId<T>) -> Result<IdxMapping<T>> {
+        self.extend(other.into_vec())
+    }
+
+    /// Keeps only the objects satisfying `f`, in the same order.
+    /// Returns the new indexes of the objects of the collection.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) -> IdxMapping<T> {
+        let mut new_idxs = Vec::with_capacity(self.objects.len());
+        let mut objects = Vec::with_capacity(self.objects.len());
+        for obj in self.take() {
+            if f(&obj) {
+                let idx = Idx::new(objects.len());
+                self.id_to_idx.insert(obj.id().to_string(), idx);
+                objects.push(obj);
+                new_idxs.push(Some(idx));
+            } else {
+                new_idxs.push(None);
+            }
+        }
+        self.collection.objects = objects;
+        IdxMapping { new_idxs }
+    }
+
+    /// Removes the object with the given id, if any.  Returns it with
+    /// the new indexes of the objects of the collection.  This is O(n),
+    /// as the indexes of the following objects change: `retain` removes
+    /// several objects at once.
+    pub fn remove(&mut self, id: &str) -> Option<(T, IdxMapping<T>)> {
+        let removed_idx = self.id_to_idx.remove(id)?;
+        let obj = self.collection.objects.remove(removed_idx.get());
+        for idx in self.id_to_idx.values_mut() {
+            if *idx > removed_idx {
+                *idx = Idx::new(idx.get() - 1);
+            }
+        }
+        let new_idxs = (0..self.objects.len() + 1)
+            .map(|i| match i.cmp(&removed_idx.get()) {
+                Ordering::Less => Some(Idx::new(i)),
+                Ordering::Equal => None,
+                Ordering::Greater => Some(Idx::new(i - 1)),
+            })
+            .collect();
+        Some((obj, IdxMapping { new_idxs }))
+    }
 }
 pub struct RefMut<'a, T: 'a + Id<T>> {
     idx: Idx<T>,
@@ -269,12 +432,7 @@ where
     T: Id<T>,
     for<'de> T: serde::Deserialize<'de>,
 {
-    if !path.join(file).exists() {
-        info!("Skipping {}", file);
-        Ok(CollectionWithId::default())
-    } else {
-        make_collection_with_id(path, file)
-    }
+    Reader::default().opt_collection_with_id(path, file)
 }
 
 pub fn make_collection_with_id<T>(path: &path::Path, file: &str) -> Result<CollectionWithId<T>>
@@ -282,38 +440,21 @@ where
     T: Id<T>,
     for<'de> T: serde::Deserialize<'de>,
 {
-    info!("Reading {}", file);
-    let path = path.join(file);
-    let mut rdr = csv::Reader::from_path(&path).with_context(ctx_from_path!(path))?;
-    let vec = rdr.deserialize()
-        .collect::<StdResult<_, _>>()
-        .with_context(ctx_from_path!(path))?;
-    CollectionWithId::new(vec)
+    Reader::default().collection_with_id(path, file)
 }
 
 pub fn make_opt_collection<T>(path: &path::Path, file: &str) -> Result<Collection<T>>
 where
     for<'de> T: serde::Deserialize<'de>,
 {
-    if !path.join(file).exists() {
-        info!("Skipping {}", file);
-        Ok(Collection::default())
-    } else {
-        make_collection(path, file)
-    }
+    Reader::default().opt_collection(path, file)
 }
 
 pub fn make_collection<T>(path: &path::Path, file: &str) -> Result<Collection<T>>
 where
     for<'de> T: serde::Deserialize<'de>,
 {
-    info!("Reading {}", file);
-    let path = path.join(file);
-    let mut rdr = csv::Reader::from_path(&path).with_context(ctx_from_path!(path))?;
-    let vec = rdr.deserialize()
-        .collect::<StdResult<_, _>>()
-        .with_context(ctx_from_path!(path))?;
-    Ok(Collection::new(vec))
+    Reader::default().collection(path, file)
 }
 
 pub fn add_prefix<T>(collection: &mut CollectionWithId<T>, prefix: &str) -> Result<()>
@@ -0,0 +1,224 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! The errors of the readers and writers, classified by kind.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use csv;
+use failure::{self, AsFail, Backtrace, Context, Fail};
+use provenance::Source;
+
+/// What went wrong.  The crate functions raise these kinds in their
+/// chain of causes, and the readers and writers give back the first
+/// one found as `Error::kind`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ErrorKind {
+    MissingFile { path: PathBuf },
+    Io { path: PathBuf, message: String },
+    /// A row that cannot be read.  The lines start at 1 with the
+    /// header, and the columns at 1.
+    Csv {
+        path: PathBuf,
+        line: Option<u64>,
+        column: Option<u64>,
+        message: String,
+    },
+    DuplicateId { collection: &'static str, id: String },
+    UnknownReference {
+        from_type: &'static str,
+        from_id: String,
+        to_type: &'static str,
+        to_id: String,
+    },
+    InvalidValue {
+        field: &'static str,
+        value: String,
+        message: String,
+    },
+    /// An error of none of the above kinds.
+    Other,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorKind::MissingFile { ref path } => write!(f, "file {:?} not found", path),
+            ErrorKind::Io { ref message, .. }
+            | ErrorKind::Csv { ref message, .. }
+            | ErrorKind::InvalidValue { ref message, .. } => f.write_str(message),
+            ErrorKind::DuplicateId { ref id, .. } => write!(f, "{} already found", id),
+            ErrorKind::UnknownReference {
+                from_type,
+                ref from_id,
+                to_type,
+                ref to_id,
+            } => write!(
+                f,
+                "{} {:?} not found, referenced by {} {:?}",
+                to_type, to_id, from_type, from_id
+            ),
+            ErrorKind::Other => f.write_str("unclassified error"),
+        }
+    }
+}
+
+impl Fail for ErrorKind {}
+
+/// The short name of the type `T`, as "StopPoint".
+pub(crate) fn type_name<T: ?Sized>() -> &'static str {
+    let name = ::std::any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+impl ErrorKind {
+    fn from_fail(path: &Path, fail: &dyn Fail) -> Option<ErrorKind> {
+        if let Some(e) = fail.downcast_ref::<csv::Error>() {
+            if let csv::ErrorKind::Io(ref e) = *e.kind() {
+                return Some(ErrorKind::from_io(path, e));
+            }
+            let column = match *e.kind() {
+                csv::ErrorKind::Deserialize { ref err, .. } => err.field().map(|f| f + 1),
+                _ => None,
+            };
+            return Some(ErrorKind::Csv {
+                path: path.to_path_buf(),
+                line: e.position().map(|p| p.line()),
+                column,
+                message: e.to_string(),
+            });
+        }
+        if let Some(e) = fail.downcast_ref::<io::Error>() {
+            return Some(ErrorKind::from_io(path, e));
+        }
+        None
+    }
+
+    fn from_io(path: &Path, e: &io::Error) -> ErrorKind {
+        if e.kind() == io::ErrorKind::NotFound {
+            ErrorKind::MissingFile {
+                path: path.to_path_buf(),
+            }
+        } else {
+            ErrorKind::Io {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            }
+        }
+    }
+}
+
+/// The context of an error on a file, classifying the CSV and IO
+/// errors (see `ctx_from_path!`).
+#[derive(Debug)]
+pub struct PathContext {
+    path: PathBuf,
+    kind: Option<ErrorKind>,
+}
+
+impl PathContext {
+    pub fn new<P, E>(path: &P, error: &E) -> PathContext
+    where
+        P: AsRef<Path> + ?Sized,
+        E: AsFail + ?Sized,
+    {
+        let path = path.as_ref();
+        PathContext {
+            path: path.to_path_buf(),
+            kind: ErrorKind::from_fail(path, error.as_fail()),
+        }
+    }
+}
+
+impl fmt::Display for PathContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Error reading {:?}", self.path)
+    }
+}
+
+/// The error given back by the readers and writers.  Its messages are
+/// the ones of the error it is built from, and its kind the first one
+/// found in their chain.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    inner: failure::Error,
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+impl Error {
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// The row the error is about, if the provenance was tracked.
+    pub fn source(&self) -> Option<&Source> {
+        self.inner
+            .iter_chain()
+            .filter_map(|c| c.downcast_ref::<Context<Source>>())
+            .map(|ctx| ctx.get_context())
+            .next()
+    }
+}
+
+fn find_kind(fail: &dyn Fail) -> Option<&ErrorKind> {
+    if let Some(kind) = fail.downcast_ref::<ErrorKind>() {
+        return Some(kind);
+    }
+    if let Some(ctx) = fail.downcast_ref::<Context<ErrorKind>>() {
+        return Some(ctx.get_context());
+    }
+    fail.downcast_ref::<Context<PathContext>>()
+        .and_then(|ctx| ctx.get_context().kind.as_ref())
+}
+
+impl From<failure::Error> for Error {
+    fn from(inner: failure::Error) -> Error {
+        let kind = inner
+            .iter_chain()
+            .filter_map(find_kind)
+            .next()
+            .cloned()
+            .unwrap_or(ErrorKind::Other);
+        Error { kind, inner }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error::from(failure::Error::from(kind))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.inner, f)
+    }
+}
+
+impl Fail for Error {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.inner.as_fail().cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        Some(self.inner.backtrace())
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Columns of a known file that are not part of the model.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct ExtraColumns {
+    /// Name of the column identifying the objects of the file.
+    pub id_column: String,
+    /// Names of the unknown columns.
+    pub headers: Vec<String>,
+    /// Values of the unknown columns, in the order of `headers`, by
+    /// object id.
+    pub values: HashMap<String, Vec<String>>,
+}
+
+/// Data of the input that the model does not know about.  It is only
+/// collected on demand (see `ntfs::ReadOptions`) and written back as
+/// is by `ntfs::write`.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct ExtraData {
+    /// Unknown files, by file name, with their raw content.
+    pub files: BTreeMap<String, Vec<u8>>,
+    /// Unknown columns of known files, by file name.
+    pub columns: BTreeMap<String, ExtraColumns>,
+}
+
+impl ExtraData {
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.columns.is_empty()
+    }
+}
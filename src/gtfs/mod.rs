@@ -18,30 +18,51 @@ mod read;
 
 use std::path;
 use {Collections, PtObjects};
-use Result;
+use error;
 use common_format::manage_calendars;
 use collection::add_prefix;
+use objects::*;
+use read_report::{Reader, Strictness};
+
+#[derive(Debug, Default, Clone)]
+pub struct ReadOptions {
+    /// Record the rows the objects are read from in
+    /// `Collections::provenance`, and add them to the errors.
+    pub track_provenance: bool,
+}
 
 pub fn read<P: AsRef<path::Path>>(
     path: P,
     config_path: Option<P>,
     prefix: Option<String>,
-) -> Result<PtObjects> {
+) -> error::Result<PtObjects> {
+    read_with_options(path, config_path, prefix, &ReadOptions::default())
+}
+
+pub fn read_with_options<P: AsRef<path::Path>>(
+    path: P,
+    config_path: Option<P>,
+    prefix: Option<String>,
+    options: &ReadOptions,
+) -> error::Result<PtObjects> {
     let mut collections = Collections::default();
+    let mut reader = Reader::new(Strictness::Strict, options.track_provenance);
 
     let (contributors, datasets) = read::read_config(config_path)?;
     collections.contributors = contributors;
     collections.datasets = datasets;
 
     let path = path.as_ref();
-    let (networks, companies) = read::read_agency(path)?;
+    let (networks, companies) = read::read_agency(&mut reader, path)?;
     collections.networks = networks;
     collections.companies = companies;
-    let (stop_areas, stop_points) = read::read_stops(path)?;
+    let (stop_areas, stop_points) = read::read_stops(&mut reader, path)?;
     collections.stop_areas = stop_areas;
     collections.stop_points = stop_points;
-    manage_calendars(&mut collections, path)?;
-    read::read_routes(path, &mut collections)?;
+    manage_calendars(&mut reader, &mut collections, path)?;
+    read::read_routes(&mut reader, path, &mut collections)?;
+
+    let mut provenance = reader.provenance;
 
     //add prefixes
     if let Some(prefix) = prefix {
@@ -54,7 +75,15 @@ pub fn read<P: AsRef<path::Path>>(
         add_prefix(&mut collections.lines, &prefix)?;
         add_prefix(&mut collections.contributors, &prefix)?;
         add_prefix(&mut collections.datasets, &prefix)?;
+        provenance.add_prefix::<Network>(&prefix);
+        provenance.add_prefix::<Company>(&prefix);
+        provenance.add_prefix::<StopPoint>(&prefix);
+        provenance.add_prefix::<StopArea>(&prefix);
+        provenance.add_prefix::<Route>(&prefix);
+        provenance.add_prefix::<Line>(&prefix);
     }
 
-    Ok(PtObjects::new(collections)?)
+    let mut res = PtObjects::new(collections).map_err(|e| provenance.annotate(e))?;
+    res.collections.provenance = provenance;
+    Ok(res)
 }
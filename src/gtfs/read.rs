@@ -19,6 +19,8 @@ use csv;
 use collection::CollectionWithId;
 use Collections;
 use objects::{self, CommentLinksT, Contributor, Coord, KeysValues};
+use provenance::Source;
+use read_report::Reader;
 use std::collections::HashSet;
 use utils::*;
 use {Result, StdResult};
@@ -39,8 +41,8 @@ struct Agency {
     name: String,
     #[serde(rename = "agency_url")]
     url: String,
-    #[serde(rename = "agency_timezone")]
-    timezone: Option<String>,
+    #[serde(rename = "agency_timezone", default, deserialize_with = "de_lenient_option")]
+    timezone: Option<objects::Timezone>,
     #[serde(rename = "agency_lang")]
     lang: Option<String>,
     #[serde(rename = "agency_phone")]
@@ -54,6 +56,8 @@ impl From<Agency> for objects::Network {
             id: agency.id.unwrap_or_else(default_agency_id),
             name: agency.name,
             codes: KeysValues::default(),
+            object_properties: KeysValues::default(),
+            comment_links: CommentLinksT::default(),
             timezone: agency.timezone,
             url: Some(agency.url),
             lang: agency.lang,
@@ -72,6 +76,9 @@ impl From<Agency> for objects::Company {
             url: Some(agency.url),
             mail: agency.email,
             phone: agency.phone,
+            codes: KeysValues::default(),
+            object_properties: KeysValues::default(),
+            comment_links: CommentLinksT::default(),
         }
     }
 }
@@ -97,8 +104,8 @@ struct Stop {
     #[serde(default)]
     location_type: i32,
     parent_station: Option<String>,
-    #[serde(rename = "stop_timezone")]
-    timezone: Option<String>,
+    #[serde(rename = "stop_timezone", default, deserialize_with = "de_lenient_option")]
+    timezone: Option<objects::Timezone>,
     #[serde(default)]
     wheelchair_boarding: Option<String>,
 }
@@ -122,6 +129,7 @@ impl From<Stop> for objects::StopArea {
             visible: true,
             geometry_id: None,
             equipment_id: None,
+            generated: false,
         }
     }
 }
@@ -272,17 +280,38 @@ struct Trip {
     bikes_allowed: u8,
 }
 
+/// Reads the rows of the CSV file at `path` with `reader`, with their
+/// sources if the provenance is tracked.
+fn read_sourced<T>(reader: &mut Reader, path: &path::Path) -> Result<Vec<(T, Option<Source>)>>
+where
+    T: ::serde::de::DeserializeOwned,
+{
+    let mut rdr = csv::Reader::from_path(path).with_context(ctx_from_path!(path))?;
+    let mut rows = vec![];
+    reader.read_sourced_rows(path, &mut rdr, |row, source| {
+        rows.push((row, source));
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
 pub fn read_agency<P: AsRef<path::Path>>(
+    reader: &mut Reader,
     path: P,
 ) -> Result<(
     CollectionWithId<objects::Network>,
     CollectionWithId<objects::Company>,
 )> {
     let path = path.as_ref().join("agency.txt");
-    let mut rdr = csv::Reader::from_path(&path).with_context(ctx_from_path!(path))?;
-    let gtfs_agencies: Vec<Agency> = rdr.deserialize()
-        .collect::<StdResult<_, _>>()
-        .with_context(ctx_from_path!(path))?;
+    let mut gtfs_agencies: Vec<Agency> = vec![];
+    for (agency, source) in read_sourced::<Agency>(reader, &path)? {
+        if let Some(source) = source {
+            let id = agency.id.clone().unwrap_or_else(default_agency_id);
+            reader.provenance.insert::<objects::Network>(&id, source.clone());
+            reader.provenance.insert::<objects::Company>(&id, source);
+        }
+        gtfs_agencies.push(agency);
+    }
     let networks = gtfs_agencies
         .iter()
         .cloned()
@@ -298,32 +327,45 @@ pub fn read_agency<P: AsRef<path::Path>>(
 }
 
 pub fn read_stops<P: AsRef<path::Path>>(
+    reader: &mut Reader,
     path: P,
 ) -> Result<(
     CollectionWithId<objects::StopArea>,
     CollectionWithId<objects::StopPoint>,
 )> {
     let path = path.as_ref().join("stops.txt");
-    let mut rdr = csv::Reader::from_path(&path).with_context(ctx_from_path!(path))?;
-    let gtfs_stops: Vec<Stop> = rdr.deserialize()
-        .collect::<StdResult<_, _>>()
-        .with_context(ctx_from_path!(path))?;
+    let gtfs_stops: Vec<(Stop, _)> = read_sourced(reader, &path)?;
 
+    let provenance = &mut reader.provenance;
     let mut stop_areas = vec![];
     let mut stop_points = vec![];
-    for mut stop in gtfs_stops {
+    for (mut stop, source) in gtfs_stops {
         match stop.location_type {
             0 => {
                 if stop.parent_station.is_none() {
                     let mut new_stop_area = stop.clone();
-                    new_stop_area.id = format!("Navitia:{}", new_stop_area.id);
+                    new_stop_area.id = objects::StopArea::generated_id(&stop.id);
                     new_stop_area.code = None;
                     stop.parent_station = Some(new_stop_area.id.clone());
-                    stop_areas.push(objects::StopArea::from(new_stop_area));
+                    if let Some(ref source) = source {
+                        provenance.insert::<objects::StopArea>(&new_stop_area.id, source.clone());
+                    }
+                    stop_areas.push(objects::StopArea {
+                        generated: true,
+                        ..objects::StopArea::from(new_stop_area)
+                    });
+                }
+                if let Some(source) = source {
+                    provenance.insert::<objects::StopPoint>(&stop.id, source);
                 }
                 stop_points.push(objects::StopPoint::from(stop));
             }
-            1 => stop_areas.push(objects::StopArea::from(stop)),
+            1 => {
+                if let Some(source) = source {
+                    provenance.insert::<objects::StopArea>(&stop.id, source);
+                }
+                stop_areas.push(objects::StopArea::from(stop))
+            }
             _ => (),
         }
     }
@@ -372,6 +414,8 @@ pub fn read_config<P: AsRef<path::Path>>(
             extrapolation: false,
             desc: None,
             system: None,
+            codes: KeysValues::default(),
+            object_properties: KeysValues::default(),
         };
     } else {
         contributor = Contributor::default();
@@ -567,13 +611,20 @@ fn make_routes(gtfs_trips: &[Trip], map_line_routes: &MapLineRoutes) -> Vec<obje
     routes
 }
 
-pub fn read_routes<P: AsRef<path::Path>>(path: P, collections: &mut Collections) -> Result<()> {
+pub fn read_routes<P: AsRef<path::Path>>(
+    reader: &mut Reader,
+    path: P,
+    collections: &mut Collections,
+) -> Result<()> {
     let path = path.as_ref();
-    let routes_path = path.join("routes.txt");
-    let mut rdr = csv::Reader::from_path(&routes_path).with_context(ctx_from_path!(routes_path))?;
-    let gtfs_routes: Vec<Route> = rdr.deserialize()
-        .collect::<StdResult<_, _>>()
-        .with_context(ctx_from_path!(routes_path))?;
+    let mut gtfs_routes: Vec<Route> = vec![];
+    let mut sources = HashMap::new();
+    for (route, source) in read_sourced::<Route>(reader, &path.join("routes.txt"))? {
+        if let Some(source) = source {
+            sources.insert(route.id.clone(), source);
+        }
+        gtfs_routes.push(route);
+    }
 
     let (commercial_modes, physical_modes) = get_modes_from_gtfs(&gtfs_routes);
     collections.commercial_modes = CollectionWithId::new(commercial_modes)?;
@@ -592,6 +643,22 @@ pub fn read_routes<P: AsRef<path::Path>>(path: P, collections: &mut Collections)
     let routes = make_routes(&gtfs_trips, &map_line_routes);
     collections.routes = CollectionWithId::new(routes)?;
 
+    // the lines and the routes are made from the GTFS routes of same id,
+    // the backward routes having the "_R" suffix
+    for (_, line) in collections.lines.iter() {
+        if let Some(source) = sources.get(&line.id) {
+            reader.provenance.insert::<objects::Line>(&line.id, source.clone());
+        }
+    }
+    for (_, route) in collections.routes.iter() {
+        let source = sources
+            .get(&route.id)
+            .or_else(|| route.id.strip_suffix("_R").and_then(|id| sources.get(id)));
+        if let Some(source) = source {
+            reader.provenance.insert::<objects::Route>(&route.id, source.clone());
+        }
+    }
+
     Ok(())
 }
 
@@ -603,6 +670,7 @@ mod tests {
     use std::io::prelude::*;
     use Collections;
     use collection::add_prefix;
+    use read_report::Reader;
 
     fn create_file_with_content(temp_dir: &TempDir, file_name: &str, content: &str) {
         let file_path = temp_dir.path().join(file_name);
@@ -626,7 +694,8 @@ mod tests {
 
         test_in_tmp_dir(|ref tmp_dir| {
             create_file_with_content(&tmp_dir, "agency.txt", agency_content);
-            let (networks, companies) = super::read_agency(tmp_dir.path()).unwrap();
+            let mut reader = Reader::default();
+            let (networks, companies) = super::read_agency(&mut reader, tmp_dir.path()).unwrap();
             assert_eq!(1, networks.len());
             let agency = networks.iter().next().unwrap().1;
             assert_eq!("default_agency_id", agency.id);
@@ -641,7 +710,8 @@ mod tests {
 
         test_in_tmp_dir(|ref tmp_dir| {
             create_file_with_content(&tmp_dir, "agency.txt", agency_content);
-            let (networks, companies) = super::read_agency(tmp_dir.path()).unwrap();
+            let mut reader = Reader::default();
+            let (networks, companies) = super::read_agency(&mut reader, tmp_dir.path()).unwrap();
             assert_eq!(1, networks.len());
             assert_eq!(1, companies.len());
         });
@@ -657,7 +727,8 @@ mod tests {
 
         test_in_tmp_dir(|ref tmp_dir| {
             create_file_with_content(&tmp_dir, "agency.txt", agency_content);
-            let (networks, companies) = super::read_agency(tmp_dir.path()).unwrap();
+            let mut reader = Reader::default();
+            let (networks, companies) = super::read_agency(&mut reader, tmp_dir.path()).unwrap();
             assert_eq!(1, networks.len());
             let network = networks.iter().next().unwrap().1;
             assert_eq!("id_1", network.id);
@@ -674,7 +745,8 @@ mod tests {
 
         test_in_tmp_dir(|ref tmp_dir| {
             create_file_with_content(&tmp_dir, "agency.txt", agency_content);
-            super::read_agency(tmp_dir.path()).unwrap();
+            let mut reader = Reader::default();
+            super::read_agency(&mut reader, tmp_dir.path()).unwrap();
         });
     }
 
@@ -685,7 +757,8 @@ mod tests {
 
         test_in_tmp_dir(|ref tmp_dir| {
             create_file_with_content(&tmp_dir, "stops.txt", stops_content);
-            let (stop_areas, stop_points) = super::read_stops(tmp_dir.path()).unwrap();
+            let mut reader = Reader::default();
+            let (stop_areas, stop_points) = super::read_stops(&mut reader, tmp_dir.path()).unwrap();
             assert_eq!(1, stop_areas.len());
             assert_eq!(1, stop_points.len());
             let stop_area = stop_areas.iter().next().unwrap().1;
@@ -706,7 +779,8 @@ mod tests {
 
         test_in_tmp_dir(|ref tmp_dir| {
             create_file_with_content(&tmp_dir, "stops.txt", stops_content);
-            let (stop_areas, stop_points) = super::read_stops(tmp_dir.path()).unwrap();
+            let mut reader = Reader::default();
+            let (stop_areas, stop_points) = super::read_stops(&mut reader, tmp_dir.path()).unwrap();
             //validate stop_point code
             assert_eq!(1, stop_points.len());
             let stop_point = stop_points.iter().next().unwrap().1;
@@ -733,7 +807,8 @@ mod tests {
 
         test_in_tmp_dir(|ref tmp_dir| {
             create_file_with_content(&tmp_dir, "stops.txt", stops_content);
-            let (stop_areas, _) = super::read_stops(tmp_dir.path()).unwrap();
+            let mut reader = Reader::default();
+            let (stop_areas, _) = super::read_stops(&mut reader, tmp_dir.path()).unwrap();
             //validate stop_area code
             assert_eq!(1, stop_areas.len());
             let stop_area = stop_areas.iter().next().unwrap().1;
@@ -761,7 +836,8 @@ mod tests {
             create_file_with_content(&tmp_dir, "routes.txt", routes_content);
             create_file_with_content(&tmp_dir, "trips.txt", trips_content);
             let mut collections = Collections::default();
-            super::read_routes(tmp_dir, &mut collections).unwrap();
+            let mut reader = Reader::default();
+            super::read_routes(&mut reader, tmp_dir, &mut collections).unwrap();
             assert_eq!(4, collections.lines.len());
             assert_eq!(2, collections.commercial_modes.len());
 
@@ -828,7 +904,8 @@ mod tests {
             create_file_with_content(&tmp_dir, "routes.txt", routes_content);
             create_file_with_content(&tmp_dir, "trips.txt", trips_content);
             let mut collections = Collections::default();
-            super::read_routes(tmp_dir, &mut collections).unwrap();
+            let mut reader = Reader::default();
+            super::read_routes(&mut reader, tmp_dir, &mut collections).unwrap();
 
             assert_eq!(3, collections.lines.len());
 
@@ -876,7 +953,8 @@ mod tests {
             create_file_with_content(&tmp_dir, "routes.txt", routes_content);
             create_file_with_content(&tmp_dir, "trips.txt", trips_content);
             let mut collections = Collections::default();
-            super::read_routes(tmp_dir, &mut collections).unwrap();
+            let mut reader = Reader::default();
+            super::read_routes(&mut reader, tmp_dir, &mut collections).unwrap();
 
             assert_eq!(2, collections.lines.len());
 
@@ -912,7 +990,8 @@ mod tests {
             create_file_with_content(&tmp_dir, "routes.txt", routes_content);
             create_file_with_content(&tmp_dir, "trips.txt", trips_content);
             let mut collections = Collections::default();
-            super::read_routes(tmp_dir, &mut collections).unwrap();
+            let mut reader = Reader::default();
+            super::read_routes(&mut reader, tmp_dir, &mut collections).unwrap();
 
             assert_eq!(2, collections.lines.len());
 
@@ -960,7 +1039,8 @@ mod tests {
             create_file_with_content(&tmp_dir, "trips.txt", trips_content);
 
             let mut collections = Collections::default();
-            super::read_routes(tmp_dir, &mut collections).unwrap();
+            let mut reader = Reader::default();
+            super::read_routes(&mut reader, tmp_dir, &mut collections).unwrap();
             assert_eq!(1, collections.lines.len());
             assert_eq!(1, collections.routes.len());
         });
@@ -993,13 +1073,16 @@ mod tests {
 
             let mut collections = Collections::default();
             let prefix = "my_prefix:";
-            let (stop_areas, stop_points) = super::read_stops(tmp_dir.path()).unwrap();
+            let mut reader = Reader::default();
+            let (stop_areas, stop_points) = super::read_stops(&mut reader, tmp_dir.path()).unwrap();
             collections.stop_areas = stop_areas;
             collections.stop_points = stop_points;
-            let (networks, companies) = super::read_agency(tmp_dir.path()).unwrap();
+            let mut reader = Reader::default();
+            let (networks, companies) = super::read_agency(&mut reader, tmp_dir.path()).unwrap();
             collections.networks = networks;
             collections.companies = companies;
-            super::read_routes(tmp_dir, &mut collections).unwrap();
+            let mut reader = Reader::default();
+            super::read_routes(&mut reader, tmp_dir, &mut collections).unwrap();
 
             add_prefix(&mut collections.networks, prefix).unwrap();
             add_prefix(&mut collections.companies, &prefix).unwrap();
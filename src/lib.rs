@@ -23,24 +23,45 @@ extern crate failure;
 extern crate get_corresponding_derive;
 #[macro_use]
 extern crate log;
+#[macro_use]
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 
+extern crate bincode;
 extern crate chrono;
+extern crate chrono_tz;
+extern crate rayon;
 #[macro_use]
 pub(crate) mod utils;
+pub mod calendars;
 pub mod collection;
+pub mod error;
+pub mod extra_data;
+pub mod merge;
+pub mod provenance;
+pub mod read_report;
+pub mod restrict;
+pub mod validity_pattern;
+pub mod sanitize;
+pub mod service_date;
+pub mod snapshot;
+pub mod stop_times;
+pub mod timezone;
+pub mod validate;
 pub mod objects;
 pub(crate) mod common_format;
 pub mod relations;
 pub mod ntfs;
 pub mod gtfs;
 
-use std::ops;
+use std::{fmt, ops};
 
 use std::collections::{BTreeMap, HashMap};
 use collection::{Collection, CollectionWithId, Idx};
+use error::ErrorKind;
+use extra_data::ExtraData;
+use provenance::Provenance;
 use objects::*;
 use relations::{IdxSet, ManyToMany, OneToMany, Relation};
 use std::result::Result as StdResult;
@@ -70,6 +91,17 @@ pub struct Collections {
     pub trip_properties: CollectionWithId<TripProperty>,
     pub geometries: CollectionWithId<Geometry>,
     pub admin_stations: Collection<AdminStation>,
+    pub grid_calendars: CollectionWithId<GridCalendar>,
+    pub grid_exception_dates: Collection<GridExceptionDate>,
+    pub grid_periods: Collection<GridPeriod>,
+    pub grid_rel_calendar_line: Collection<GridRelCalendarLine>,
+    pub line_groups: CollectionWithId<LineGroup>,
+    pub line_group_links: Collection<LineGroupLink>,
+    /// The sources of the objects, if the reader was asked to track
+    /// them.
+    #[serde(skip)]
+    pub provenance: Provenance,
+    pub extra_data: ExtraData,
 }
 
 #[derive(GetCorresponding)]
@@ -88,6 +120,7 @@ pub struct PtObjects {
     companies_to_vehicle_journeys: OneToMany<Company, VehicleJourney>,
     vehicle_journeys_to_stop_points: ManyToMany<VehicleJourney, StopPoint>,
     transfers_to_stop_points: ManyToMany<Transfer, StopPoint>,
+    grid_calendars_to_lines: ManyToMany<GridCalendar, Line>,
 
     // shortcuts
     #[get_corresponding(weight = "1.9")]
@@ -104,29 +137,163 @@ pub struct PtObjects {
     datasets_to_physical_modes: ManyToMany<Dataset, PhysicalMode>,
 }
 
+fn forward_vj_to_sp(c: &Collections) -> BTreeMap<Idx<VehicleJourney>, IdxSet<StopPoint>> {
+    c.vehicle_journeys
+        .iter()
+        .map(|(idx, vj)| {
+            let sps = vj.stop_times.iter().map(|st| st.stop_point_idx).collect();
+            (idx, sps)
+        })
+        .collect()
+}
+
+fn forward_tr_to_sp(c: &Collections) -> Result<BTreeMap<Idx<Transfer>, IdxSet<StopPoint>>> {
+    c.transfers
+        .iter()
+        .map(|(idx, tr)| {
+            let mut stop_points = IdxSet::default();
+            for stop_id in &[&tr.from_stop_id, &tr.to_stop_id] {
+                stop_points.insert(c.stop_points.get_idx(stop_id).ok_or_else(|| {
+                    ErrorKind::UnknownReference {
+                        from_type: "Transfer",
+                        from_id: format!("{}-{}", tr.from_stop_id, tr.to_stop_id),
+                        to_type: "StopPoint",
+                        to_id: stop_id.to_string(),
+                    }
+                })?);
+            }
+            Ok((idx, stop_points))
+        })
+        .collect()
+}
+
+fn check_grid_calendar_ids(c: &Collections) -> Result<()> {
+    let ids = c.grid_exception_dates
+        .iter()
+        .map(|(_, d)| ("GridExceptionDate", &d.grid_calendar_id))
+        .chain(
+            c.grid_periods
+                .iter()
+                .map(|(_, p)| ("GridPeriod", &p.grid_calendar_id)),
+        );
+    for (from_type, id) in ids {
+        if c.grid_calendars.get_idx(id).is_none() {
+            return Err(ErrorKind::UnknownReference {
+                from_type,
+                from_id: id.clone(),
+                to_type: "GridCalendar",
+                to_id: id.clone(),
+            }.into());
+        }
+    }
+    Ok(())
+}
+
+fn check_line_group_ids(c: &Collections) -> Result<()> {
+    let unknown = |from_type, from_id: &str, to_type, to_id: &str| ErrorKind::UnknownReference {
+        from_type,
+        from_id: from_id.to_string(),
+        to_type,
+        to_id: to_id.to_string(),
+    };
+    for (_, group) in c.line_groups.iter() {
+        if c.lines.get_idx(&group.main_line_id).is_none() {
+            return Err(unknown("LineGroup", &group.id, "Line", &group.main_line_id).into());
+        }
+    }
+    for (_, link) in c.line_group_links.iter() {
+        let from_id = format!("{}-{}", link.line_group_id, link.line_id);
+        if c.line_groups.get_idx(&link.line_group_id).is_none() {
+            return Err(unknown("LineGroupLink", &from_id, "LineGroup", &link.line_group_id).into());
+        }
+        if c.lines.get_idx(&link.line_id).is_none() {
+            return Err(unknown("LineGroupLink", &from_id, "Line", &link.line_id).into());
+        }
+    }
+    Ok(())
+}
+
+fn forward_gc_to_line(c: &Collections) -> Result<BTreeMap<Idx<GridCalendar>, IdxSet<Line>>> {
+    let mut forward_gc_to_line = BTreeMap::<_, IdxSet<_>>::default();
+    for rel in c.grid_rel_calendar_line.iter().map(|(_, rel)| rel) {
+        let unknown = |to_type, to_id: &str| ErrorKind::UnknownReference {
+            from_type: "GridRelCalendarLine",
+            from_id: format!("{}-{}", rel.grid_calendar_id, rel.line_id),
+            to_type,
+            to_id: to_id.to_string(),
+        };
+        let grid_calendar_idx = c.grid_calendars
+            .get_idx(&rel.grid_calendar_id)
+            .ok_or_else(|| unknown("GridCalendar", &rel.grid_calendar_id))?;
+        let line_idx = rel.line_idx(&c.lines)
+            .ok_or_else(|| unknown("Line", rel.line_ref()))?;
+        forward_gc_to_line
+            .entry(grid_calendar_idx)
+            .or_insert_with(IdxSet::default)
+            .insert(line_idx);
+    }
+    Ok(forward_gc_to_line)
+}
+
+/// The error of `PtObjects::edit`, giving back the collections as they
+/// were edited.
+pub struct EditError {
+    pub error: Error,
+    pub collections: Box<Collections>,
+}
+
+impl fmt::Debug for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.error, f)
+    }
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl failure::Fail for EditError {
+    fn cause(&self) -> Option<&dyn failure::Fail> {
+        self.error.as_fail().cause()
+    }
+
+    fn backtrace(&self) -> Option<&failure::Backtrace> {
+        Some(self.error.backtrace())
+    }
+}
+
 impl PtObjects {
+    /// Builds the relations between the collections, concurrently on
+    /// the current thread pool (see `rayon::ThreadPool::install`).
     pub fn new(c: Collections) -> Result<Self> {
-        let forward_vj_to_sp = c.vehicle_journeys
-            .iter()
-            .map(|(idx, vj)| {
-                let sps = vj.stop_times.iter().map(|st| st.stop_point_idx).collect();
-                (idx, sps)
-            })
-            .collect();
-
-        let forward_tr_to_sp = c.transfers
-            .iter()
-            .map(|(idx, tr)| {
-                let mut stop_points = IdxSet::default();
-                stop_points.insert(c.stop_points.get_idx(&tr.from_stop_id).ok_or_else(|| {
-                    format_err!("Invalid id: transfer.from_stop_id={:?}", tr.from_stop_id)
-                })?);
-                stop_points.insert(c.stop_points.get_idx(&tr.to_stop_id).ok_or_else(|| {
-                    format_err!("Invalid id: transfer.to_stop_id={:?}", tr.to_stop_id)
-                })?);
-                Ok((idx, stop_points))
-            })
-            .collect::<StdResult<BTreeMap<_, _>, Error>>()?;
+        PtObjects::with_collections(c).map_err(|e| e.error)
+    }
+
+    fn with_collections(c: Collections) -> StdResult<Self, EditError> {
+        match PtObjects::relations(&c) {
+            Ok(pt_objects) => Ok(PtObjects {
+                collections: c,
+                ..pt_objects
+            }),
+            Err(error) => Err(EditError {
+                error,
+                collections: Box::new(c),
+            }),
+        }
+    }
+
+    /// The relations between the collections `c`, with empty
+    /// collections.
+    fn relations(c: &Collections) -> Result<Self> {
+        let (forward_vj_to_sp, (forward_tr_to_sp, forward_gc_to_line)) = rayon::join(
+            || forward_vj_to_sp(c),
+            || rayon::join(|| forward_tr_to_sp(c), || forward_gc_to_line(c)),
+        );
+        let (forward_tr_to_sp, forward_gc_to_line) = (forward_tr_to_sp?, forward_gc_to_line?);
+        check_grid_calendar_ids(c)?;
+        check_line_group_ids(c)?;
         let vehicle_journeys_to_stop_points = ManyToMany::from_forward(forward_vj_to_sp);
         let routes_to_vehicle_journeys =
             OneToMany::new(&c.routes, &c.vehicle_journeys, "routes_to_vehicle_journeys")?;
@@ -140,61 +307,117 @@ impl PtObjects {
             &c.vehicle_journeys,
             "datasets_to_vehicle_journeys",
         )?;
+        // the relations deduced from the ones above are built while
+        // the other ones are
+        let (deduced, others) = rayon::join(
+            || {
+                (
+                    ManyToMany::from_relations_chain(
+                        &routes_to_vehicle_journeys,
+                        &vehicle_journeys_to_stop_points,
+                    ),
+                    ManyToMany::from_relations_chain(
+                        &physical_modes_to_vehicle_journeys,
+                        &vehicle_journeys_to_stop_points,
+                    ),
+                    ManyToMany::from_relations_sink(
+                        &physical_modes_to_vehicle_journeys,
+                        &routes_to_vehicle_journeys,
+                    ),
+                    ManyToMany::from_relations_chain(
+                        &datasets_to_vehicle_journeys,
+                        &vehicle_journeys_to_stop_points,
+                    ),
+                    ManyToMany::from_relations_sink(
+                        &datasets_to_vehicle_journeys,
+                        &routes_to_vehicle_journeys,
+                    ),
+                    ManyToMany::from_relations_sink(
+                        &datasets_to_vehicle_journeys,
+                        &physical_modes_to_vehicle_journeys,
+                    ),
+                )
+            },
+            || -> Result<_> {
+                Ok((
+                    ManyToMany::from_forward(forward_tr_to_sp),
+                    ManyToMany::from_forward(forward_gc_to_line),
+                    OneToMany::new(&c.networks, &c.lines, "networks_to_lines")?,
+                    OneToMany::new(&c.commercial_modes, &c.lines, "commercial_modes_to_lines")?,
+                    OneToMany::new(&c.lines, &c.routes, "lines_to_routes")?,
+                    OneToMany::new(&c.stop_areas, &c.stop_points, "stop_areas_to_stop_points")?,
+                    OneToMany::new(&c.contributors, &c.datasets, "contributors_to_datasets")?,
+                    OneToMany::new(
+                        &c.companies,
+                        &c.vehicle_journeys,
+                        "companies_to_vehicle_journeys",
+                    )?,
+                ))
+            },
+        );
+        let (
+            routes_to_stop_points,
+            physical_modes_to_stop_points,
+            physical_modes_to_routes,
+            datasets_to_stop_points,
+            datasets_to_routes,
+            datasets_to_physical_modes,
+        ) = deduced;
+        let (
+            transfers_to_stop_points,
+            grid_calendars_to_lines,
+            networks_to_lines,
+            commercial_modes_to_lines,
+            lines_to_routes,
+            stop_areas_to_stop_points,
+            contributors_to_datasets,
+            companies_to_vehicle_journeys,
+        ) = others?;
         Ok(PtObjects {
-            routes_to_stop_points: ManyToMany::from_relations_chain(
-                &routes_to_vehicle_journeys,
-                &vehicle_journeys_to_stop_points,
-            ),
-            physical_modes_to_stop_points: ManyToMany::from_relations_chain(
-                &physical_modes_to_vehicle_journeys,
-                &vehicle_journeys_to_stop_points,
-            ),
-            physical_modes_to_routes: ManyToMany::from_relations_sink(
-                &physical_modes_to_vehicle_journeys,
-                &routes_to_vehicle_journeys,
-            ),
-            datasets_to_stop_points: ManyToMany::from_relations_chain(
-                &datasets_to_vehicle_journeys,
-                &vehicle_journeys_to_stop_points,
-            ),
-            datasets_to_routes: ManyToMany::from_relations_sink(
-                &datasets_to_vehicle_journeys,
-                &routes_to_vehicle_journeys,
-            ),
-            datasets_to_physical_modes: ManyToMany::from_relations_sink(
-                &datasets_to_vehicle_journeys,
-                &physical_modes_to_vehicle_journeys,
-            ),
-            transfers_to_stop_points: ManyToMany::from_forward(forward_tr_to_sp),
+            routes_to_stop_points,
+            physical_modes_to_stop_points,
+            physical_modes_to_routes,
+            datasets_to_stop_points,
+            datasets_to_routes,
+            datasets_to_physical_modes,
+            transfers_to_stop_points,
+            grid_calendars_to_lines,
             datasets_to_vehicle_journeys,
             routes_to_vehicle_journeys,
             vehicle_journeys_to_stop_points,
             physical_modes_to_vehicle_journeys,
-            networks_to_lines: OneToMany::new(&c.networks, &c.lines, "networks_to_lines")?,
-            commercial_modes_to_lines: OneToMany::new(
-                &c.commercial_modes,
-                &c.lines,
-                "commercial_modes_to_lines",
-            )?,
-            lines_to_routes: OneToMany::new(&c.lines, &c.routes, "lines_to_routes")?,
-            stop_areas_to_stop_points: OneToMany::new(
-                &c.stop_areas,
-                &c.stop_points,
-                "stop_areas_to_stop_points",
-            )?,
-            contributors_to_datasets: OneToMany::new(
-                &c.contributors,
-                &c.datasets,
-                "contributors_to_datasets",
-            )?,
-            companies_to_vehicle_journeys: OneToMany::new(
-                &c.companies,
-                &c.vehicle_journeys,
-                "companies_to_vehicle_journeys",
-            )?,
-            collections: c,
+            networks_to_lines,
+            commercial_modes_to_lines,
+            lines_to_routes,
+            stop_areas_to_stop_points,
+            contributors_to_datasets,
+            companies_to_vehicle_journeys,
+            collections: Collections::default(),
         })
     }
+
+    /// Gives back the collections, dropping the relations.
+    pub fn into_collections(self) -> Collections {
+        self.collections
+    }
+
+    /// Edits the collections with `f`, and then rebuilds the relations
+    /// so that `get_corresponding` reflects the edition.  Fails if `f`
+    /// fails or if the edited collections are not consistent, giving
+    /// them back in the error.
+    pub fn edit<F>(self, f: F) -> StdResult<Self, EditError>
+    where
+        F: FnOnce(&mut Collections) -> Result<()>,
+    {
+        let mut collections = self.into_collections();
+        if let Err(error) = f(&mut collections) {
+            return Err(EditError {
+                error,
+                collections: Box::new(collections),
+            });
+        }
+        PtObjects::with_collections(collections)
+    }
 }
 impl ::serde::Serialize for PtObjects {
     fn serialize<S>(&self, serializer: S) -> StdResult<S::Ok, S::Error>
@@ -0,0 +1,388 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! Merge of several models into one.
+
+use std::collections::btree_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::mem;
+use std::str::FromStr;
+use collection::{Collection, CollectionWithId, Id, Idx};
+use error::ErrorKind;
+use objects::*;
+use {Collections, Error, Result};
+
+/// What to do when an object of a merged model has the id of an
+/// object already in the result.  Identical physical modes, commercial
+/// modes and equipments are never conflicting: they are kept once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Fail.
+    Error,
+    /// Keep the object already in the result, the references to the
+    /// conflicting object now targeting it.
+    KeepFirst,
+    /// Prefix the id of the conflicting object by `<n>:`, `n` being
+    /// the position of its model in the merged models, starting at 0.
+    Prefix,
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let policy = match *self {
+            ConflictPolicy::Error => "error",
+            ConflictPolicy::KeepFirst => "keep-first",
+            ConflictPolicy::Prefix => "prefix",
+        };
+        f.write_str(policy)
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = Error;
+
+    fn from_str(policy: &str) -> Result<ConflictPolicy> {
+        match policy {
+            "error" => Ok(ConflictPolicy::Error),
+            "keep-first" => Ok(ConflictPolicy::KeepFirst),
+            "prefix" => Ok(ConflictPolicy::Prefix),
+            p => bail!("unknown conflict policy {:?}", p),
+        }
+    }
+}
+
+/// Merges `models` in order, resolving the id conflicts with `policy`.
+pub fn merge(models: Vec<Collections>, policy: ConflictPolicy) -> Result<Collections> {
+    let mut models = models.into_iter();
+    let mut merged = models.next().unwrap_or_default();
+    for (i, model) in models.enumerate() {
+        info!("Merging model {}", i + 1);
+        merge_into(&mut merged, model, policy, &format!("{}:", i + 1))?;
+    }
+    Ok(merged)
+}
+
+fn duplicate_id(collection: &'static str, id: String) -> Error {
+    ErrorKind::DuplicateId { collection, id }.into()
+}
+
+/// The resolution of the id conflicts of a collection.
+#[derive(Default)]
+struct Conflicts {
+    renamed: HashMap<String, String>,
+    dropped: HashSet<String>,
+}
+
+impl Conflicts {
+    fn new<T>(
+        target: &CollectionWithId<T>,
+        other: &CollectionWithId<T>,
+        name: &'static str,
+        policy: ConflictPolicy,
+        prefix: &str,
+        dedup: bool,
+    ) -> Result<Conflicts>
+    where
+        T: Id<T> + PartialEq,
+    {
+        let mut conflicts = Conflicts::default();
+        for (_, obj) in other.iter() {
+            let id = obj.id();
+            let existing = match target.get(id) {
+                Some(existing) => existing,
+                None => continue,
+            };
+            if dedup && existing == obj {
+                conflicts.dropped.insert(id.to_string());
+                continue;
+            }
+            match policy {
+                ConflictPolicy::Error => return Err(duplicate_id(name, id.to_string())),
+                ConflictPolicy::KeepFirst => {
+                    warn!("{} {:?} found in several models, keeping the first", name, id);
+                    conflicts.dropped.insert(id.to_string());
+                }
+                ConflictPolicy::Prefix => {
+                    let new_id = format!("{}{}", prefix, id);
+                    if target.get_idx(&new_id).is_some() || other.get_idx(&new_id).is_some() {
+                        return Err(duplicate_id(name, new_id));
+                    }
+                    conflicts.renamed.insert(id.to_string(), new_id);
+                }
+            }
+        }
+        Ok(conflicts)
+    }
+
+    fn rename(&self, id: &mut String) {
+        if let Some(new_id) = self.renamed.get(id) {
+            *id = new_id.clone();
+        }
+    }
+
+    fn rename_opt(&self, id: &mut Option<String>) {
+        if let Some(ref mut id) = *id {
+            self.rename(id);
+        }
+    }
+}
+
+fn update<T, F>(collection: &mut CollectionWithId<T>, f: F) -> Result<()>
+where
+    T: Id<T>,
+    F: FnMut(&mut T),
+{
+    let mut objects = collection.take();
+    objects.iter_mut().for_each(f);
+    *collection = CollectionWithId::new(objects)?;
+    Ok(())
+}
+
+fn update_collection<T, F>(collection: &mut Collection<T>, f: F)
+where
+    F: FnMut(&mut T),
+{
+    let mut objects = collection.take();
+    objects.iter_mut().for_each(f);
+    *collection = Collection::new(objects);
+}
+
+fn append<T>(target: &mut Collection<T>, other: &mut Collection<T>) {
+    let mut objects = target.take();
+    objects.extend(other.take());
+    *target = Collection::new(objects);
+}
+
+fn ids_by_idx<T: Id<T>>(collection: &CollectionWithId<T>) -> HashMap<Idx<T>, String> {
+    collection
+        .iter()
+        .map(|(idx, obj)| (idx, obj.id().to_string()))
+        .collect()
+}
+
+/// The index in `target` of the object at `idx` in the merged model,
+/// `ids` being the ids of the objects of the merged model.
+fn remap<T>(idx: Idx<T>, ids: &HashMap<Idx<T>, String>, target: &CollectionWithId<T>) -> Idx<T> {
+    target
+        .get_idx(&ids[&idx])
+        .expect("merged objects are all in the target")
+}
+
+fn remap_comment_links(
+    links: &mut CommentLinksT,
+    ids: &HashMap<Idx<Comment>, String>,
+    comments: &CollectionWithId<Comment>,
+) {
+    for link in links.iter_mut() {
+        *link = remap(*link, ids, comments);
+    }
+}
+
+fn merge_collection<T: Id<T>>(
+    target: &mut CollectionWithId<T>,
+    other: &mut CollectionWithId<T>,
+    conflicts: &Conflicts,
+) -> Result<()> {
+    let mut other = mem::take(other);
+    other.retain(|obj| !conflicts.dropped.contains(obj.id()));
+    target.merge(other)?;
+    Ok(())
+}
+
+fn merge_into(
+    target: &mut Collections,
+    mut other: Collections,
+    policy: ConflictPolicy,
+    prefix: &str,
+) -> Result<()> {
+    macro_rules! conflicts {
+        ($field:ident, $dedup:expr) => {
+            Conflicts::new(
+                &target.$field,
+                &other.$field,
+                stringify!($field),
+                policy,
+                prefix,
+                $dedup,
+            )?
+        };
+    }
+    let contributors = conflicts!(contributors, false);
+    let datasets = conflicts!(datasets, false);
+    let networks = conflicts!(networks, false);
+    let commercial_modes = conflicts!(commercial_modes, true);
+    let lines = conflicts!(lines, false);
+    let routes = conflicts!(routes, false);
+    let vehicle_journeys = conflicts!(vehicle_journeys, false);
+    let physical_modes = conflicts!(physical_modes, true);
+    let stop_areas = conflicts!(stop_areas, false);
+    let stop_points = conflicts!(stop_points, false);
+    let calendars = conflicts!(calendars, false);
+    let companies = conflicts!(companies, false);
+    let comments = conflicts!(comments, false);
+    let equipments = conflicts!(equipments, true);
+    let trip_properties = conflicts!(trip_properties, false);
+    let geometries = conflicts!(geometries, false);
+    let grid_calendars = conflicts!(grid_calendars, false);
+    let line_groups = conflicts!(line_groups, false);
+
+    // objects without references first, so that the comment links and
+    // stop times can be remapped before merging the objects using them
+    update(&mut other.comments, |o| comments.rename(&mut o.id))?;
+    let comment_ids = ids_by_idx(&other.comments);
+    merge_collection(&mut target.comments, &mut other.comments, &comments)?;
+    let target_comments = &target.comments;
+    let links = |links: &mut CommentLinksT| remap_comment_links(links, &comment_ids, target_comments);
+
+    update(&mut other.contributors, |o| contributors.rename(&mut o.id))?;
+    update(&mut other.commercial_modes, |o| commercial_modes.rename(&mut o.id))?;
+    update(&mut other.physical_modes, |o| physical_modes.rename(&mut o.id))?;
+    update(&mut other.equipments, |o| equipments.rename(&mut o.id))?;
+    update(&mut other.trip_properties, |o| trip_properties.rename(&mut o.id))?;
+    update(&mut other.geometries, |o| geometries.rename(&mut o.id))?;
+    update(&mut other.grid_calendars, |o| grid_calendars.rename(&mut o.id))?;
+    update(&mut other.datasets, |o| {
+        datasets.rename(&mut o.id);
+        contributors.rename(&mut o.contributor_id);
+    })?;
+    update(&mut other.networks, |o| {
+        networks.rename(&mut o.id);
+        links(&mut o.comment_links);
+    })?;
+    update(&mut other.companies, |o| {
+        companies.rename(&mut o.id);
+        links(&mut o.comment_links);
+    })?;
+    update(&mut other.calendars, |o| {
+        calendars.rename(&mut o.id);
+        links(&mut o.comment_links);
+    })?;
+    update(&mut other.lines, |o| {
+        lines.rename(&mut o.id);
+        networks.rename(&mut o.network_id);
+        commercial_modes.rename(&mut o.commercial_mode_id);
+        geometries.rename_opt(&mut o.geometry_id);
+        links(&mut o.comment_links);
+    })?;
+    update(&mut other.routes, |o| {
+        routes.rename(&mut o.id);
+        lines.rename(&mut o.line_id);
+        geometries.rename_opt(&mut o.geometry_id);
+        stop_areas.rename_opt(&mut o.destination_id);
+        links(&mut o.comment_links);
+    })?;
+    update(&mut other.stop_areas, |o| {
+        stop_areas.rename(&mut o.id);
+        geometries.rename_opt(&mut o.geometry_id);
+        equipments.rename_opt(&mut o.equipment_id);
+        links(&mut o.comment_links);
+    })?;
+    update(&mut other.stop_points, |o| {
+        stop_points.rename(&mut o.id);
+        stop_areas.rename(&mut o.stop_area_id);
+        geometries.rename_opt(&mut o.geometry_id);
+        equipments.rename_opt(&mut o.equipment_id);
+        links(&mut o.comment_links);
+    })?;
+    let stop_point_ids = ids_by_idx(&other.stop_points);
+    merge_collection(&mut target.stop_points, &mut other.stop_points, &stop_points)?;
+    let target_stop_points = &target.stop_points;
+    update(&mut other.vehicle_journeys, |o| {
+        vehicle_journeys.rename(&mut o.id);
+        routes.rename(&mut o.route_id);
+        physical_modes.rename(&mut o.physical_mode_id);
+        datasets.rename(&mut o.dataset_id);
+        calendars.rename(&mut o.service_id);
+        companies.rename(&mut o.company_id);
+        trip_properties.rename_opt(&mut o.trip_property_id);
+        geometries.rename_opt(&mut o.geometry_id);
+        links(&mut o.comment_links);
+        for st in &mut o.stop_times {
+            st.stop_point_idx = remap(st.stop_point_idx, &stop_point_ids, target_stop_points);
+            if let Some(ref mut extras) = st.extras {
+                links(&mut extras.comment_links);
+            }
+        }
+    })?;
+    update(&mut other.line_groups, |o| {
+        line_groups.rename(&mut o.id);
+        lines.rename(&mut o.main_line_id);
+        links(&mut o.comment_links);
+    })?;
+    update_collection(&mut other.line_group_links, |o| {
+        line_groups.rename(&mut o.line_group_id);
+        lines.rename(&mut o.line_id);
+    });
+    update_collection(&mut other.transfers, |o| {
+        stop_points.rename(&mut o.from_stop_id);
+        stop_points.rename(&mut o.to_stop_id);
+        equipments.rename_opt(&mut o.equipment_id);
+    });
+    update_collection(&mut other.admin_stations, |o| stop_areas.rename(&mut o.stop_id));
+    update_collection(&mut other.grid_exception_dates, |o| {
+        grid_calendars.rename(&mut o.grid_calendar_id)
+    });
+    update_collection(&mut other.grid_periods, |o| {
+        grid_calendars.rename(&mut o.grid_calendar_id)
+    });
+    update_collection(&mut other.grid_rel_calendar_line, |o| {
+        grid_calendars.rename(&mut o.grid_calendar_id);
+        lines.rename(&mut o.line_id);
+    });
+
+    merge_collection(&mut target.contributors, &mut other.contributors, &contributors)?;
+    merge_collection(&mut target.datasets, &mut other.datasets, &datasets)?;
+    merge_collection(&mut target.networks, &mut other.networks, &networks)?;
+    merge_collection(&mut target.commercial_modes, &mut other.commercial_modes, &commercial_modes)?;
+    merge_collection(&mut target.lines, &mut other.lines, &lines)?;
+    merge_collection(&mut target.routes, &mut other.routes, &routes)?;
+    merge_collection(&mut target.vehicle_journeys, &mut other.vehicle_journeys, &vehicle_journeys)?;
+    merge_collection(&mut target.physical_modes, &mut other.physical_modes, &physical_modes)?;
+    merge_collection(&mut target.stop_areas, &mut other.stop_areas, &stop_areas)?;
+    merge_collection(&mut target.calendars, &mut other.calendars, &calendars)?;
+    merge_collection(&mut target.companies, &mut other.companies, &companies)?;
+    merge_collection(&mut target.equipments, &mut other.equipments, &equipments)?;
+    merge_collection(&mut target.trip_properties, &mut other.trip_properties, &trip_properties)?;
+    merge_collection(&mut target.geometries, &mut other.geometries, &geometries)?;
+    merge_collection(&mut target.grid_calendars, &mut other.grid_calendars, &grid_calendars)?;
+    merge_collection(&mut target.line_groups, &mut other.line_groups, &line_groups)?;
+    append(&mut target.transfers, &mut other.transfers);
+    append(&mut target.admin_stations, &mut other.admin_stations);
+    append(&mut target.grid_exception_dates, &mut other.grid_exception_dates);
+    append(&mut target.grid_periods, &mut other.grid_periods);
+    append(&mut target.grid_rel_calendar_line, &mut other.grid_rel_calendar_line);
+    append(&mut target.line_group_links, &mut other.line_group_links);
+
+    for (key, value) in other.feed_infos {
+        target.feed_infos.entry(key).or_insert(value);
+    }
+    for (file, content) in other.extra_data.files {
+        target.extra_data.files.entry(file).or_insert(content);
+    }
+    for (file, columns) in other.extra_data.columns {
+        match target.extra_data.columns.entry(file) {
+            Entry::Occupied(e) => warn!(
+                "extra columns of {} found in several models, keeping the first",
+                e.key()
+            ),
+            Entry::Vacant(e) => {
+                e.insert(columns);
+            }
+        }
+    }
+    Ok(())
+}
@@ -18,20 +18,29 @@ use common_format;
 mod read;
 mod write;
 
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::mem;
 use std::path;
+use std::str::FromStr;
 use {Collections, PtObjects};
 use utils::*;
 use objects::*;
-use Result;
+use {Error, Result};
 use collection::*;
+use error::{self, ErrorKind};
+use rayon::ThreadPool;
+use read_report::{self, ReadReport, Reader, Strictness, Task};
+use self::read::StopTimeExtrasById;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone)]
 struct StopTime {
     stop_id: String,
     trip_id: String,
     stop_sequence: u32,
-    arrival_time: Time,
-    departure_time: Time,
+    arrival_time: Option<Time>,
+    departure_time: Option<Time>,
     #[serde(default)]
     boarding_duration: u16,
     #[serde(default)]
@@ -40,9 +49,28 @@ struct StopTime {
     pickup_type: u8,
     #[serde(default)]
     dropoff_type: u8,
-    #[serde(default, deserialize_with = "de_from_u8", serialize_with = "ser_from_bool")]
+    #[serde(default, deserialize_with = "de_from_u8")]
+    datetime_estimated: bool,
+    local_zone_id: Option<u16>,
+    stop_time_id: Option<String>,
+}
+
+/// The row written for a stop time, borrowing its strings.
+#[derive(Serialize)]
+struct StopTimeRef<'a> {
+    stop_id: &'a str,
+    trip_id: &'a str,
+    stop_sequence: u32,
+    arrival_time: Time,
+    departure_time: Time,
+    boarding_duration: u16,
+    alighting_duration: u16,
+    pickup_type: u8,
+    dropoff_type: u8,
+    #[serde(serialize_with = "ser_from_bool")]
     datetime_estimated: bool,
     local_zone_id: Option<u16>,
+    stop_time_id: Option<&'a str>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -62,8 +90,8 @@ struct Stop {
     #[serde(deserialize_with = "de_with_empty_default")]
     location_type: i32,
     parent_station: Option<String>,
-    #[serde(rename = "stop_timezone")]
-    timezone: Option<String>,
+    #[serde(rename = "stop_timezone", default, deserialize_with = "de_lenient_option")]
+    timezone: Option<Timezone>,
     geometry_id: Option<String>,
     equipment_id: Option<String>,
 }
@@ -95,43 +123,617 @@ fn default_visible() -> bool {
     true
 }
 
-pub fn read<P: AsRef<path::Path>>(path: P) -> Result<PtObjects> {
-    let path = path.as_ref();
-    info!("Loading NTFS from {:?}", path);
-    let mut collections = Collections::default();
-    collections.contributors = make_collection_with_id(path, "contributors.txt")?;
-    collections.datasets = make_collection_with_id(path, "datasets.txt")?;
-    collections.commercial_modes = make_collection_with_id(path, "commercial_modes.txt")?;
-    collections.networks = make_collection_with_id(path, "networks.txt")?;
-    collections.lines = make_collection_with_id(path, "lines.txt")?;
-    collections.routes = make_collection_with_id(path, "routes.txt")?;
-    collections.vehicle_journeys = make_collection_with_id(path, "trips.txt")?;
-    collections.physical_modes = make_collection_with_id(path, "physical_modes.txt")?;
-    collections.companies = make_collection_with_id(path, "companies.txt")?;
-    collections.equipments = make_opt_collection_with_id(path, "equipments.txt")?;
-    collections.trip_properties = make_opt_collection_with_id(path, "trip_properties.txt")?;
-    collections.geometries = make_opt_collection_with_id(path, "geometries.txt")?;
-    collections.comments = make_opt_collection_with_id(path, "comments.txt")?;
-    collections.transfers = make_opt_collection(path, "transfers.txt")?;
-    collections.admin_stations = make_opt_collection(path, "admin_stations.txt")?;
-    common_format::manage_calendars(&mut collections, path)?;
-    read::manage_feed_infos(&mut collections, path)?;
-    read::manage_stops(&mut collections, path)?;
-    read::manage_stop_times(&mut collections, path)?;
-    read::manage_codes(&mut collections, path)?;
-    read::manage_comments(&mut collections, path)?;
-    read::manage_object_properties(&mut collections, path)?;
+/// Files of an NTFS handled by the model.  Any other file found in an
+/// NTFS is an extra file.
+const NTFS_FILES: &[&str] = &[
+    "admin_stations.txt",
+    "calendar.txt",
+    "calendar_dates.txt",
+    "comment_links.txt",
+    "comments.txt",
+    "commercial_modes.txt",
+    "companies.txt",
+    "contributors.txt",
+    "datasets.txt",
+    "equipments.txt",
+    "feed_infos.txt",
+    "geometries.txt",
+    "grid_calendars.txt",
+    "grid_exception_dates.txt",
+    "grid_periods.txt",
+    "grid_rel_calendar_line.txt",
+    "line_group_links.txt",
+    "line_groups.txt",
+    "lines.txt",
+    "networks.txt",
+    "object_codes.txt",
+    "object_properties.txt",
+    "physical_modes.txt",
+    "routes.txt",
+    "stop_times.txt",
+    "stops.txt",
+    "transfers.txt",
+    "trip_properties.txt",
+    "trips.txt",
+];
+
+/// Versions of the NTFS specification, as found in the `ntfs_version`
+/// of `feed_infos.txt`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Version {
+    V0_5,
+    V0_6,
+    V0_7,
+}
+
+impl Version {
+    pub const CURRENT: Version = Version::V0_7;
+
+    fn from_feed_infos(feed_infos: &HashMap<String, String>) -> Version {
+        match feed_infos.get("ntfs_version") {
+            None => {
+                warn!(
+                    "no ntfs_version in feed_infos.txt, reading as NTFS {}",
+                    Version::V0_5
+                );
+                Version::V0_5
+            }
+            Some(v) => v.parse().unwrap_or_else(|_| {
+                warn!(
+                    "unknown ntfs_version {:?}, reading as NTFS {}",
+                    v,
+                    Version::CURRENT
+                );
+                Version::CURRENT
+            }),
+        }
+    }
+}
+
+impl Default for Version {
+    fn default() -> Version {
+        Version::CURRENT
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let version = match *self {
+            Version::V0_5 => "0.5",
+            Version::V0_6 => "0.6",
+            Version::V0_7 => "0.7",
+        };
+        f.write_str(version)
+    }
+}
+
+impl FromStr for Version {
+    type Err = Error;
+
+    fn from_str(version: &str) -> Result<Version> {
+        match version.trim() {
+            "0.5" => Ok(Version::V0_5),
+            "0.6" => Ok(Version::V0_6),
+            "0.7" => Ok(Version::V0_7),
+            v => Err(ErrorKind::InvalidValue {
+                field: "ntfs_version",
+                value: v.to_string(),
+                message: format!("unsupported NTFS version {:?}", v),
+            }.into()),
+        }
+    }
+}
+
+/// Columns renamed by a version of the NTFS: (file, version of the
+/// renaming, old name, new name).  They are renamed on read when the
+/// NTFS is older than the renaming.
+const RENAMED_COLUMNS: &[(&str, Version, &str, &str)] = &[
+    ("object_codes.txt", Version::V0_6, "code_system", "object_system"),
+    ("object_codes.txt", Version::V0_6, "code", "object_code"),
+];
+
+/// Files introduced by a version of the NTFS, and thus not written for
+/// an older version.
+const INTRODUCED_FILES: &[(&str, Version)] = &[
+    ("grid_calendars.txt", Version::V0_7),
+    ("grid_exception_dates.txt", Version::V0_7),
+    ("grid_periods.txt", Version::V0_7),
+    ("grid_rel_calendar_line.txt", Version::V0_7),
+];
+
+/// What to do with the stop areas generated for the stop points
+/// without parent station when writing an NTFS.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratedStopAreas {
+    /// Write them as any other stop area.
+    Materialize,
+    /// Do not write them; their stop points are written without parent
+    /// station, and the stop areas are generated again on read.
+    #[default]
+    Drop,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct WriteOptions {
+    /// The NTFS version to write.
+    pub version: Version,
+    pub generated_stop_areas: GeneratedStopAreas,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ReadOptions {
+    /// Keep the unknown files and the unknown columns of the known
+    /// files in `Collections::extra_data`, so that `write` can restore
+    /// them.
+    pub keep_extra_data: bool,
+    /// Interpolate the times of the stop times without arrival and
+    /// departure times (see `stop_times::interpolate_times`), instead
+    /// of failing.
+    pub interpolate_missing_times: bool,
+    /// What to do with the rows that cannot be read or whose
+    /// references cannot be resolved.
+    pub strictness: Strictness,
+    /// Record the rows the objects are read from in
+    /// `Collections::provenance`, and add them to the errors.
+    pub track_provenance: bool,
+    /// The number of threads reading the independent files and
+    /// building the relations concurrently, 1 to do everything one
+    /// after another.  By default, the global thread pool of rayon is
+    /// used, with a thread per CPU.
+    pub threads: Option<usize>,
+}
+
+pub fn read<P: AsRef<path::Path>>(path: P) -> error::Result<PtObjects> {
+    read_with_options(path, &ReadOptions::default())
+}
+
+pub fn read_with_options<P: AsRef<path::Path>>(
+    path: P,
+    options: &ReadOptions,
+) -> error::Result<PtObjects> {
+    read_with_report(path, options).map(|(pt_objects, _)| pt_objects)
+}
+
+/// Reads an NTFS, also giving back what was ignored (see
+/// `ReadOptions::strictness`).
+pub fn read_with_report<P: AsRef<path::Path>>(
+    path: P,
+    options: &ReadOptions,
+) -> error::Result<(PtObjects, ReadReport)> {
+    let pool = read_report::thread_pool(options.threads)?;
+    let (mut collections, report) = read_collections_in(path.as_ref(), options, pool.as_ref())?;
     info!("Indexing");
-    let res = PtObjects::new(collections)?;
+    let provenance = mem::take(&mut collections.provenance);
+    let res = read_report::install(pool.as_ref(), || PtObjects::new(collections));
+    let mut res = res.map_err(|e| provenance.annotate(e))?;
+    res.collections.provenance = provenance;
     info!("Loading NTFS done");
-    Ok(res)
+    Ok((res, report))
+}
+
+fn check_ref<T: Id<T>>(
+    collection: &CollectionWithId<T>,
+    field: &'static str,
+    id: &str,
+) -> Result<()> {
+    if collection.get_idx(id).is_none() {
+        return Err(ErrorKind::InvalidValue {
+            field,
+            value: id.to_string(),
+            message: format!("{}={:?} not found", field, id),
+        }.into());
+    }
+    Ok(())
+}
+
+/// Reads the collections of an NTFS, without checking the references
+/// between the objects as `read_with_options` does (see
+/// `Collections::validate`).  In lenient mode, the objects with a
+/// reference needed to build the relations that cannot be resolved are
+/// skipped.
+pub fn read_collections<P: AsRef<path::Path>>(
+    path: P,
+    options: &ReadOptions,
+) -> error::Result<(Collections, ReadReport)> {
+    let pool = read_report::thread_pool(options.threads)?;
+    read_collections_in(path.as_ref(), options, pool.as_ref())
+}
+
+fn read_collections_in(
+    path: &path::Path,
+    options: &ReadOptions,
+    pool: Option<&ThreadPool>,
+) -> error::Result<(Collections, ReadReport)> {
+    let no_visitor: Option<fn(&Collections, &VisitedVehicleJourney) -> Result<()>> = None;
+    read_collections_visiting(path, options, pool, no_visitor)
+}
+
+/// A vehicle journey given by `visit_vehicle_journeys` with its stop
+/// times, which are not in `VehicleJourney::stop_times`.
+pub struct VisitedVehicleJourney<'a> {
+    pub idx: Idx<VehicleJourney>,
+    /// The stop times, sorted by sequence, with their codes, object
+    /// properties and comments.
+    pub stop_times: &'a [::objects::StopTime],
+}
+
+/// Reads the collections of an NTFS like `read_collections`, but
+/// streams `stop_times.txt` instead of keeping the stop times in the
+/// vehicle journeys, so that they are never all in memory: `visitor`
+/// is given each vehicle journey with its stop times.  The stop times
+/// of each trip must be consecutive in `stop_times.txt`.
+pub fn visit_vehicle_journeys<P, F>(
+    path: P,
+    options: &ReadOptions,
+    visitor: F,
+) -> error::Result<(Collections, ReadReport)>
+where
+    P: AsRef<path::Path>,
+    F: FnMut(&Collections, &VisitedVehicleJourney) -> Result<()>,
+{
+    let pool = read_report::thread_pool(options.threads)?;
+    read_collections_visiting(path.as_ref(), options, pool.as_ref(), Some(visitor))
+}
+
+fn read_collections_visiting<F>(
+    path: &path::Path,
+    options: &ReadOptions,
+    pool: Option<&ThreadPool>,
+    visitor: Option<F>,
+) -> error::Result<(Collections, ReadReport)>
+where
+    F: FnMut(&Collections, &VisitedVehicleJourney) -> Result<()>,
+{
+    info!("Loading NTFS from {:?}", path);
+    let mut reader = Reader::new(options.strictness, options.track_provenance);
+    let mut c = Collections::default();
+    read::manage_feed_infos(&mut reader, &mut c, path)?;
+    let version = Version::from_feed_infos(&c.feed_infos);
+    info!("Reading NTFS {}", version);
+    {
+        let Collections {
+            ref mut contributors,
+            ref mut commercial_modes,
+            ref mut networks,
+            ref mut physical_modes,
+            ref mut companies,
+            ref mut equipments,
+            ref mut trip_properties,
+            ref mut geometries,
+            ref mut admin_stations,
+            ref mut grid_calendars,
+            ref mut calendars,
+            ref mut stop_areas,
+            ref mut stop_points,
+            ..
+        } = c;
+        let tasks: Vec<Task> = vec![
+            Box::new(move |r| {
+                *contributors = r.collection_with_id(path, "contributors.txt")?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *commercial_modes = r.collection_with_id(path, "commercial_modes.txt")?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *networks = r.collection_with_id(path, "networks.txt")?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *physical_modes = r.collection_with_id(path, "physical_modes.txt")?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *companies = r.collection_with_id(path, "companies.txt")?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *equipments = r.opt_collection_with_id(path, "equipments.txt")?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *trip_properties = r.opt_collection_with_id(path, "trip_properties.txt")?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *geometries = r.opt_collection_with_id(path, "geometries.txt")?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *admin_stations = r.opt_collection(path, "admin_stations.txt")?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *grid_calendars = r.opt_collection_with_id(path, "grid_calendars.txt")?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                let mut read = Collections::default();
+                common_format::manage_calendars(r, &mut read, path)?;
+                *calendars = read.calendars;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                let mut read = Collections::default();
+                read::manage_stops(r, &mut read, path)?;
+                *stop_areas = read.stop_areas;
+                *stop_points = read.stop_points;
+                Ok(())
+            }),
+        ];
+        reader.read_concurrently(pool, tasks)?;
+    }
+    {
+        let Collections {
+            ref contributors,
+            ref mut datasets,
+            ref networks,
+            ref commercial_modes,
+            ref mut lines,
+            ref stop_points,
+            ref mut transfers,
+            ref grid_calendars,
+            ref mut grid_exception_dates,
+            ref mut grid_periods,
+            ..
+        } = c;
+        let tasks: Vec<Task> = vec![
+            Box::new(move |r| {
+                *datasets = r.checked_collection_with_id(path, "datasets.txt", |d: &Dataset| {
+                    check_ref(contributors, "contributor_id", &d.contributor_id)
+                })?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *lines = r.checked_collection_with_id(path, "lines.txt", |l: &Line| {
+                    check_ref(networks, "network_id", &l.network_id)?;
+                    check_ref(commercial_modes, "commercial_mode_id", &l.commercial_mode_id)
+                })?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *transfers = r.checked_opt_collection(path, "transfers.txt", |t: &Transfer| {
+                    check_ref(stop_points, "from_stop_id", &t.from_stop_id)?;
+                    check_ref(stop_points, "to_stop_id", &t.to_stop_id)
+                })?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *grid_exception_dates = r.checked_opt_collection(
+                    path,
+                    "grid_exception_dates.txt",
+                    |d: &GridExceptionDate| {
+                        check_ref(grid_calendars, "grid_calendar_id", &d.grid_calendar_id)
+                    },
+                )?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *grid_periods =
+                    r.checked_opt_collection(path, "grid_periods.txt", |p: &GridPeriod| {
+                        check_ref(grid_calendars, "grid_calendar_id", &p.grid_calendar_id)
+                    })?;
+                Ok(())
+            }),
+        ];
+        reader.read_concurrently(pool, tasks)?;
+    }
+    {
+        let Collections {
+            ref lines,
+            ref mut routes,
+            ref grid_calendars,
+            ref mut grid_rel_calendar_line,
+            ref mut line_groups,
+            ..
+        } = c;
+        let tasks: Vec<Task> = vec![
+            Box::new(move |r| {
+                *routes = r.checked_collection_with_id(path, "routes.txt", |route: &Route| {
+                    check_ref(lines, "line_id", &route.line_id)
+                })?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *grid_rel_calendar_line = r.checked_opt_collection(
+                    path,
+                    "grid_rel_calendar_line.txt",
+                    |rel: &GridRelCalendarLine| {
+                        check_ref(grid_calendars, "grid_calendar_id", &rel.grid_calendar_id)?;
+                        if rel.line_id.is_empty() {
+                            // resolved with the codes of the lines, read later
+                            if rel.line_external_code.is_none() {
+                                return Err(ErrorKind::InvalidValue {
+                                    field: "line_id",
+                                    value: String::new(),
+                                    message: "no line_id nor line_external_code".to_string(),
+                                }.into());
+                            }
+                            return Ok(());
+                        }
+                        check_ref(lines, "line_id", &rel.line_id)
+                    },
+                )?;
+                Ok(())
+            }),
+            Box::new(move |r| {
+                *line_groups =
+                    r.checked_opt_collection_with_id(path, "line_groups.txt", |g: &LineGroup| {
+                        check_ref(lines, "main_line_id", &g.main_line_id)
+                    })?;
+                Ok(())
+            }),
+        ];
+        reader.read_concurrently(pool, tasks)?;
+    }
+    c.line_group_links =
+        reader.checked_opt_collection(path, "line_group_links.txt", |l: &LineGroupLink| {
+            check_ref(&c.line_groups, "line_group_id", &l.line_group_id)?;
+            check_ref(&c.lines, "line_id", &l.line_id)
+        })?;
+    c.vehicle_journeys =
+        reader.checked_collection_with_id(path, "trips.txt", |vj: &VehicleJourney| {
+            check_ref(&c.routes, "route_id", &vj.route_id)?;
+            check_ref(&c.physical_modes, "physical_mode_id", &vj.physical_mode_id)?;
+            check_ref(&c.datasets, "dataset_id", &vj.dataset_id)?;
+            check_ref(&c.companies, "company_id", &vj.company_id)
+        })?;
+    // the extras of the stop times are read first, to be given to the
+    // stop times as they are read
+    let mut extras = StopTimeExtrasById::default();
+    read::manage_codes(&mut reader, &mut c, path, version, &mut extras)?;
+    read::manage_comments(&mut reader, &mut c, path, &mut extras)?;
+    read::manage_object_properties(&mut reader, &mut c, path, &mut extras)?;
+    let interpolate = options.interpolate_missing_times;
+    match visitor {
+        None => read::manage_stop_times(&mut reader, &mut c, path, interpolate, extras)?,
+        Some(visitor) => {
+            read::visit_stop_times(&mut reader, &c, path, interpolate, extras, visitor)?
+        }
+    }
+    if options.keep_extra_data {
+        read::manage_extra_data(&mut c, path)?;
+    }
+    c.provenance = reader.provenance;
+    Ok((c, reader.report))
+}
+
+/// Adds to `extras` the extras of the stop times of `vj` with codes,
+/// object properties or comments, which can only be written with an id.
+fn stop_times_with_extras<'a>(
+    vj: &VehicleJourney,
+    stop_times: &'a [::objects::StopTime],
+    extras: &mut Vec<&'a StopTimeExtras>,
+) {
+    for st in stop_times {
+        let e = match st.extras {
+            Some(ref e) => e,
+            None => continue,
+        };
+        if e.codes.is_empty() && e.object_properties.is_empty() && e.comment_links.is_empty() {
+            continue;
+        }
+        if e.id.is_none() {
+            warn!(
+                "stop time {}/{} has codes, object properties or comments but no id, skipping them",
+                vj.id, st.sequence
+            );
+            continue;
+        }
+        extras.push(e);
+    }
+}
+
+/// Writes the `stop_times.txt` of an NTFS as the stop times are given,
+/// for instance by the visitor of `visit_vehicle_journeys`, so that
+/// they are never all in memory.  The rest of the NTFS is written by
+/// `write_with_stop_times`.
+pub struct StopTimesWriter {
+    path: path::PathBuf,
+    wtr: csv::Writer<fs::File>,
+    /// The extras of the stop times written with codes, object
+    /// properties or comments, which are written with the ones of the
+    /// other objects.
+    extras: Vec<StopTimeExtras>,
+}
+
+impl StopTimesWriter {
+    /// Creates the `stop_times.txt` of the NTFS at `path`.
+    pub fn new<P: AsRef<path::Path>>(path: P) -> error::Result<Self> {
+        let path = path.as_ref().join("stop_times.txt");
+        let wtr = write::create_writer(&path)?;
+        Ok(StopTimesWriter {
+            path,
+            wtr,
+            extras: vec![],
+        })
+    }
+
+    /// Writes the stop times of `vj`, a vehicle journey of `c`.
+    pub fn write(&mut self, c: &Collections, vj: &VisitedVehicleJourney) -> error::Result<()> {
+        let vehicle_journey = &c.vehicle_journeys[vj.idx];
+        write::write_stop_times(
+            &mut self.wtr,
+            &self.path,
+            vehicle_journey,
+            vj.stop_times,
+            &c.stop_points,
+        )?;
+        let mut extras = vec![];
+        stop_times_with_extras(vehicle_journey, vj.stop_times, &mut extras);
+        self.extras.extend(extras.into_iter().cloned());
+        Ok(())
+    }
+
+    /// Flushes the stop times written.
+    pub fn finish(mut self) -> error::Result<()> {
+        Ok(write::flush_writer(&mut self.wtr, &self.path)?)
+    }
 }
 
-pub fn write<P: AsRef<path::Path>>(path: P, pt_objects: &PtObjects) -> Result<()> {
-    let path = path.as_ref();
-    info!("Writing NTFS to {:?}", path);
+/// Writes `pt_objects` as an NTFS of the given `version`.  Only the
+/// versions since 0.6 can be written.
+pub fn write<P: AsRef<path::Path>>(
+    path: P,
+    pt_objects: &PtObjects,
+    version: Version,
+) -> error::Result<()> {
+    let options = WriteOptions {
+        version,
+        ..WriteOptions::default()
+    };
+    write_with_options(path, pt_objects, &options)
+}
+
+pub fn write_with_options<P: AsRef<path::Path>>(
+    path: P,
+    pt_objects: &PtObjects,
+    options: &WriteOptions,
+) -> error::Result<()> {
+    write_model(path.as_ref(), pt_objects, options, None)
+}
+
+/// Writes `pt_objects`, whose vehicle journeys have no stop times, as
+/// `write_with_options` in the NTFS where `stop_times` wrote their stop
+/// times.
+pub fn write_with_stop_times<P: AsRef<path::Path>>(
+    path: P,
+    pt_objects: &PtObjects,
+    options: &WriteOptions,
+    mut stop_times: StopTimesWriter,
+) -> error::Result<()> {
+    write::flush_writer(&mut stop_times.wtr, &stop_times.path)?;
+    write_model(path.as_ref(), pt_objects, options, Some(&stop_times.extras))
+}
 
-    write::write_feed_infos(path, &pt_objects.feed_infos)?;
+/// Writes the NTFS, and its stop times unless they were streamed,
+/// `streamed` being then the extras of the ones with codes, object
+/// properties or comments.
+fn write_model(
+    path: &path::Path,
+    pt_objects: &PtObjects,
+    options: &WriteOptions,
+    streamed: Option<&[StopTimeExtras]>,
+) -> error::Result<()> {
+    let version = options.version;
+    info!("Writing NTFS {} to {:?}", version, path);
+    if version < Version::V0_6 {
+        return Err(ErrorKind::InvalidValue {
+            field: "ntfs_version",
+            value: version.to_string(),
+            message: format!("writing NTFS {} is not supported", version),
+        }.into());
+    }
+    let is_written = |file: &str| {
+        INTRODUCED_FILES
+            .iter()
+            .all(|&(f, introduced)| f != file || version >= introduced)
+    };
+
+    let mut feed_infos = pt_objects.feed_infos.clone();
+    feed_infos.insert("ntfs_version".to_string(), version.to_string());
+    write::write_feed_infos(path, &feed_infos)?;
     write::write_collection_with_id(path, "contributors.txt", &pt_objects.contributors)?;
     write::write_collection_with_id(path, "datasets.txt", &pt_objects.datasets)?;
     write::write_collection_with_id(path, "networks.txt", &pt_objects.networks)?;
@@ -145,16 +747,52 @@ pub fn write<P: AsRef<path::Path>>(path: P, pt_objects: &PtObjects) -> Result<()
     write::write_collection_with_id(path, "geometries.txt", &pt_objects.geometries)?;
     write::write_collection(path, "transfers.txt", &pt_objects.transfers)?;
     write::write_collection(path, "admin_stations.txt", &pt_objects.admin_stations)?;
-    write::write_vehicle_journeys_and_stop_times(
+    if is_written("grid_calendars.txt") {
+        write::write_collection_with_id(path, "grid_calendars.txt", &pt_objects.grid_calendars)?;
+        write::write_collection(
+            path,
+            "grid_exception_dates.txt",
+            &pt_objects.grid_exception_dates,
+        )?;
+        write::write_collection(path, "grid_periods.txt", &pt_objects.grid_periods)?;
+        write::write_collection(
+            path,
+            "grid_rel_calendar_line.txt",
+            &pt_objects.grid_rel_calendar_line,
+        )?;
+    } else if pt_objects.grid_calendars.len() > 0 {
+        warn!("grid calendars are not supported by NTFS {}, skipping", version);
+    }
+    write::write_collection_with_id(path, "line_groups.txt", &pt_objects.line_groups)?;
+    write::write_collection(path, "line_group_links.txt", &pt_objects.line_group_links)?;
+    let mut stop_times = vec![];
+    match streamed {
+        Some(streamed) => {
+            write::write_collection_with_id(path, "trips.txt", &pt_objects.vehicle_journeys)?;
+            stop_times.extend(streamed);
+        }
+        None => {
+            write::write_vehicle_journeys_and_stop_times(
+                path,
+                &pt_objects.vehicle_journeys,
+                &pt_objects.stop_points,
+            )?;
+            for (_, vj) in pt_objects.vehicle_journeys.iter() {
+                stop_times_with_extras(vj, &vj.stop_times, &mut stop_times);
+            }
+        }
+    }
+    write::write_calendar_and_calendar_dates(path, &pt_objects.calendars)?;
+    write::write_stops(
         path,
-        &pt_objects.vehicle_journeys,
         &pt_objects.stop_points,
+        &pt_objects.stop_areas,
+        options.generated_stop_areas,
     )?;
-    write::write_calendar_and_calendar_dates(path, &pt_objects.calendars)?;
-    write::write_stops(path, &pt_objects.stop_points, &pt_objects.stop_areas)?;
-    write::write_comments(path, pt_objects)?;
-    write::write_codes(path, pt_objects)?;
-    write::write_object_properties(path, pt_objects)?;
+    write::write_comments(path, pt_objects, &stop_times)?;
+    write::write_codes(path, pt_objects, &stop_times)?;
+    write::write_object_properties(path, pt_objects, &stop_times)?;
+    write::write_extra_data(path, &pt_objects.extra_data)?;
 
     Ok(())
 }
@@ -165,7 +803,8 @@ mod tests {
     use self::tempdir::TempDir;
     use objects::*;
     use {Collection, CollectionWithId};
-    use super::{read, write};
+    use super::{read, write, GeneratedStopAreas, Version};
+    use super::read::StopTimeExtrasById;
     use super::Collections;
     use std::collections::HashMap;
     use serde;
@@ -174,6 +813,7 @@ mod tests {
     use std::path;
     use collection::*;
     use common_format::*;
+    use read_report::Reader;
 
     fn ser_deser_in_tmp_dir<F>(func: F)
     where
@@ -222,12 +862,72 @@ mod tests {
 
         ser_deser_in_tmp_dir(|path| {
             write::write_feed_infos(path, &feed_infos).unwrap();
-            read::manage_feed_infos(&mut collections, path).unwrap();
+            read::manage_feed_infos(&mut Reader::default(), &mut collections, path).unwrap();
         });
         assert_eq!(collections.feed_infos.len(), 2);
         assert_eq!(collections.feed_infos, feed_infos);
     }
 
+    #[test]
+    fn version_parsing() {
+        assert_eq!("0.6".parse::<Version>().unwrap(), Version::V0_6);
+        assert_eq!(Version::V0_5.to_string(), "0.5");
+        assert!("0.42".parse::<Version>().is_err());
+        assert!(Version::V0_5 < Version::V0_6);
+
+        let mut feed_infos = HashMap::default();
+        assert_eq!(Version::from_feed_infos(&feed_infos), Version::V0_5);
+        feed_infos.insert("ntfs_version".to_string(), "0.6".to_string());
+        assert_eq!(Version::from_feed_infos(&feed_infos), Version::V0_6);
+        feed_infos.insert("ntfs_version".to_string(), "9.9".to_string());
+        assert_eq!(Version::from_feed_infos(&feed_infos), Version::CURRENT);
+    }
+
+    #[test]
+    fn legacy_object_codes_columns() {
+        use std::fs::File;
+        use std::io::Write;
+
+        ser_deser_in_tmp_dir(|path| {
+            let mut collections = Collections::default();
+            collections.lines = CollectionWithId::new(vec![
+                Line {
+                    id: "L1".to_string(),
+                    code: None,
+                    codes: KeysValues::default(),
+                    object_properties: KeysValues::default(),
+                    comment_links: CommentLinksT::default(),
+                    name: "Line 1".to_string(),
+                    forward_name: None,
+                    forward_direction: None,
+                    backward_name: None,
+                    backward_direction: None,
+                    color: None,
+                    text_color: None,
+                    sort_order: None,
+                    network_id: "N1".to_string(),
+                    commercial_mode_id: "Bus".to_string(),
+                    geometry_id: None,
+                    opening_time: None,
+                    closing_time: None,
+                },
+            ]).unwrap();
+            let mut file = File::create(path.join("object_codes.txt")).unwrap();
+            file.write_all(b"object_type,object_id,code_system,code\nline,L1,source,42\n")
+                .unwrap();
+
+            let (mut reader, mut extras) = (Reader::default(), StopTimeExtrasById::default());
+            let c = &mut collections;
+            read::manage_codes(&mut reader, c, path, Version::V0_5, &mut extras).unwrap();
+            assert_eq!(
+                c.lines.get("L1").unwrap().codes,
+                vec![("source".to_string(), "42".to_string())]
+            );
+            let res = read::manage_codes(&mut reader, c, path, Version::V0_6, &mut extras);
+            assert!(res.is_err());
+        });
+    }
+
     #[test]
     fn networks_serialization_deserialization() {
         test_serialize_deserialize_collection_with_id(vec![
@@ -235,12 +935,14 @@ mod tests {
                 id: "OIF:101".to_string(),
                 name: "SAVAC".to_string(),
                 url: Some("http://www.vianavigo.com,Europe/Paris".to_string()),
-                timezone: Some("Europe/Paris".to_string()),
+                timezone: Some(chrono_tz::Europe::Paris),
                 lang: Some("fr".to_string()),
                 phone: Some("0123456789".to_string()),
                 address: Some("somewhere".to_string()),
                 sort_order: Some(1),
                 codes: KeysValues::default(),
+                object_properties: KeysValues::default(),
+                comment_links: CommentLinksT::default(),
             },
             Network {
                 id: "OIF:102".to_string(),
@@ -252,6 +954,8 @@ mod tests {
                 address: None,
                 sort_order: None,
                 codes: KeysValues::default(),
+                object_properties: KeysValues::default(),
+                comment_links: CommentLinksT::default(),
             },
         ]);
     }
@@ -280,6 +984,9 @@ mod tests {
                 url: Some("http://www.foo.fr/".to_string()),
                 mail: Some("contact@foo.fr".to_string()),
                 phone: Some("0123456789".to_string()),
+                codes: KeysValues::default(),
+                object_properties: KeysValues::default(),
+                comment_links: CommentLinksT::default(),
             },
             Company {
                 id: "OIF:102".to_string(),
@@ -288,6 +995,9 @@ mod tests {
                 url: None,
                 mail: None,
                 phone: None,
+                codes: KeysValues::default(),
+                object_properties: KeysValues::default(),
+                comment_links: CommentLinksT::default(),
             },
         ]);
     }
@@ -405,7 +1115,7 @@ mod tests {
                     lat: 48.799115,
                 },
                 stop_area_id: "OIF:SA:8739322".to_string(),
-                timezone: Some("Europe/Paris".to_string()),
+                timezone: Some(chrono_tz::Europe::Paris),
                 geometry_id: None,
                 equipment_id: None,
                 fare_zone_id: Some("1".to_string()),
@@ -422,7 +1132,7 @@ mod tests {
                     lat: 48.800598,
                 },
                 stop_area_id: "OIF:SA:2:1468".to_string(),
-                timezone: Some("Europe/Paris".to_string()),
+                timezone: Some(chrono_tz::Europe::Paris),
                 geometry_id: None,
                 equipment_id: None,
                 fare_zone_id: None,
@@ -455,6 +1165,7 @@ mod tests {
                         dropoff_type: 1,
                         datetime_estimated: false,
                         local_zone_id: None,
+                        extras: None,
                     },
                     StopTime {
                         stop_point_idx: stop_points.get_idx("OIF:SP:36:2127").unwrap(),
@@ -467,6 +1178,10 @@ mod tests {
                         dropoff_type: 0,
                         datetime_estimated: false,
                         local_zone_id: None,
+                        extras: Some(Box::new(StopTimeExtras {
+                            id: Some("StopTime:OIF:87604986-1_11595-1:1".to_string()),
+                            ..StopTimeExtras::default()
+                        })),
                     },
                 ],
             },
@@ -497,7 +1212,9 @@ mod tests {
                 make_collection_with_id::<VehicleJourney>(path, "trips.txt").unwrap();
             collections.stop_points = stop_points;
 
-            read::manage_stop_times(&mut collections, path).unwrap();
+            let extras = StopTimeExtrasById::default();
+            read::manage_stop_times(&mut Reader::default(), &mut collections, path, false, extras)
+                .unwrap();
             assert_eq!(collections.vehicle_journeys, vehicle_journeys);
         });
     }
@@ -510,12 +1227,16 @@ mod tests {
                 name: "Foo".to_string(),
                 license: Some("ODbL".to_string()),
                 website: Some("http://www.foo.com".to_string()),
+                codes: KeysValues::default(),
+                object_properties: KeysValues::default(),
             },
             Contributor {
                 id: "Bar".to_string(),
                 name: "Bar".to_string(),
                 license: None,
                 website: None,
+                codes: KeysValues::default(),
+                object_properties: KeysValues::default(),
             },
         ]);
     }
@@ -532,6 +1253,8 @@ mod tests {
                 extrapolation: false,
                 desc: Some("description".to_string()),
                 system: Some("GTFS V2".to_string()),
+                codes: KeysValues::default(),
+                object_properties: KeysValues::default(),
             },
             Dataset {
                 id: "Bar:0".to_string(),
@@ -542,6 +1265,8 @@ mod tests {
                 extrapolation: false,
                 desc: None,
                 system: None,
+                codes: KeysValues::default(),
+                object_properties: KeysValues::default(),
             },
         ]);
     }
@@ -606,6 +1331,9 @@ mod tests {
                     ),
                     (chrono::NaiveDate::from_ymd(2018, 1, 15), ExceptionType::Add),
                 ],
+                codes: KeysValues::default(),
+                object_properties: KeysValues::default(),
+                comment_links: CommentLinksT::default(),
             },
             Calendar {
                 id: "1".to_string(),
@@ -619,6 +1347,9 @@ mod tests {
                 start_date: chrono::NaiveDate::from_ymd(2018, 1, 6),
                 end_date: chrono::NaiveDate::from_ymd(2018, 1, 27),
                 calendar_dates: vec![],
+                codes: KeysValues::default(),
+                object_properties: KeysValues::default(),
+                comment_links: CommentLinksT::default(),
             },
         ]).unwrap();
 
@@ -626,7 +1357,7 @@ mod tests {
             write::write_calendar_and_calendar_dates(path, &calendars).unwrap();
 
             let mut collections = Collections::default();
-            manage_calendars(&mut collections, path).unwrap();
+            manage_calendars(&mut Reader::default(), &mut collections, path).unwrap();
 
             assert_eq!(collections.calendars, calendars);
         });
@@ -646,7 +1377,7 @@ mod tests {
                     lon: 2.073034,
                     lat: 48.799115,
                 },
-                timezone: Some("Europe/Paris".to_string()),
+                timezone: Some(chrono_tz::Europe::Paris),
                 geometry_id: Some("geometry_1".to_string()),
                 equipment_id: Some("equipment_1".to_string()),
                 stop_area_id: "sa_1".to_string(),
@@ -667,7 +1398,7 @@ mod tests {
                 timezone: None,
                 geometry_id: None,
                 equipment_id: None,
-                stop_area_id: "sa_2".to_string(),
+                stop_area_id: "Navitia:sa_2".to_string(),
                 fare_zone_id: None,
             },
         ]).unwrap();
@@ -687,6 +1418,7 @@ mod tests {
                 timezone: None,
                 geometry_id: None,
                 equipment_id: None,
+                generated: true,
             },
             StopArea {
                 id: "sa_1".to_string(),
@@ -699,21 +1431,39 @@ mod tests {
                     lon: 2.073034,
                     lat: 48.799115,
                 },
-                timezone: Some("Europe/Paris".to_string()),
+                timezone: Some(chrono_tz::Europe::Paris),
                 geometry_id: Some("geometry_3".to_string()),
                 equipment_id: Some("equipment_1".to_string()),
+                generated: false,
             },
         ]).unwrap();
 
         ser_deser_in_tmp_dir(|path| {
-            write::write_stops(path, &stop_points, &stop_areas).unwrap();
+            write::write_stops(path, &stop_points, &stop_areas, GeneratedStopAreas::Drop)
+                .unwrap();
 
             let mut collections = Collections::default();
-            read::manage_stops(&mut collections, path).unwrap();
+            read::manage_stops(&mut Reader::default(), &mut collections, path).unwrap();
 
             assert_eq!(collections.stop_points, stop_points);
             assert_eq!(collections.stop_areas, stop_areas);
         });
+
+        ser_deser_in_tmp_dir(|path| {
+            write::write_stops(
+                path,
+                &stop_points,
+                &stop_areas,
+                GeneratedStopAreas::Materialize,
+            ).unwrap();
+
+            let mut collections = Collections::default();
+            read::manage_stops(&mut Reader::default(), &mut collections, path).unwrap();
+
+            assert_eq!(collections.stop_points, stop_points);
+            let stop_area = collections.stop_areas.get("Navitia:sa_2").unwrap();
+            assert!(!stop_area.generated);
+        });
     }
 
     #[test]
@@ -778,6 +1528,7 @@ mod tests {
                 timezone: None,
                 geometry_id: None,
                 equipment_id: None,
+                generated: false,
             },
         ]).unwrap();
 
@@ -839,7 +1590,30 @@ mod tests {
                 company_id: "OIF:743".to_string(),
                 trip_property_id: None,
                 geometry_id: None,
-                stop_times: vec![],
+                stop_times: vec![
+                    StopTime {
+                        stop_point_idx: stop_points.get_idx("sp_1").unwrap(),
+                        sequence: 0,
+                        arrival_time: Time::new(14, 40, 0),
+                        departure_time: Time::new(14, 40, 0),
+                        boarding_duration: 0,
+                        alighting_duration: 0,
+                        pickup_type: 0,
+                        dropoff_type: 0,
+                        datetime_estimated: false,
+                        local_zone_id: None,
+                        extras: Some(Box::new(StopTimeExtras {
+                            id: Some("st:1".to_string()),
+                            codes: vec![
+                                ("object_system:9".to_string(), "object_code:9".to_string()),
+                            ],
+                            object_properties: vec![
+                                ("prop_name:9".to_string(), "prop_value:9".to_string()),
+                            ],
+                            comment_links: vec![comments.get_idx("c:1").unwrap()],
+                        })),
+                    },
+                ],
             },
         ]).unwrap();
 
@@ -854,6 +1628,49 @@ mod tests {
                 address: None,
                 sort_order: None,
                 codes: KeysValues::default(),
+                object_properties: vec![("prop_name:7".to_string(), "prop_value:7".to_string())],
+                comment_links: vec![comments.get_idx("c:2").unwrap()],
+            },
+        ]).unwrap();
+
+        let companies = CollectionWithId::new(vec![
+            Company {
+                id: "OIF:743".to_string(),
+                name: "Foo".to_string(),
+                address: None,
+                url: None,
+                mail: None,
+                phone: None,
+                codes: vec![("object_system:8".to_string(), "object_code:8".to_string())],
+                object_properties: vec![("prop_name:8".to_string(), "prop_value:8".to_string())],
+                comment_links: vec![comments.get_idx("c:3").unwrap()],
+            },
+        ]).unwrap();
+
+        let contributors = CollectionWithId::new(vec![
+            Contributor {
+                codes: vec![("object_system:10".to_string(), "object_code:10".to_string())],
+                object_properties: vec![("prop_name:10".to_string(), "prop_value:10".to_string())],
+                ..Contributor::default()
+            },
+        ]).unwrap();
+
+        let datasets = CollectionWithId::new(vec![
+            Dataset {
+                codes: vec![("object_system:11".to_string(), "object_code:11".to_string())],
+                object_properties: vec![("prop_name:11".to_string(), "prop_value:11".to_string())],
+                ..Dataset::default()
+            },
+        ]).unwrap();
+
+        let line_groups = CollectionWithId::new(vec![
+            LineGroup {
+                id: "lg:1".to_string(),
+                name: "Line group".to_string(),
+                main_line_id: "OIF:002002003:3OIF829".to_string(),
+                codes: vec![("object_system:12".to_string(), "object_code:12".to_string())],
+                object_properties: vec![("prop_name:12".to_string(), "prop_value:12".to_string())],
+                comment_links: vec![comments.get_idx("c:3").unwrap()],
             },
         ]).unwrap();
 
@@ -864,6 +1681,10 @@ mod tests {
         ser_collections.routes = routes;
         ser_collections.vehicle_journeys = vehicle_journeys;
         ser_collections.networks = networks;
+        ser_collections.companies = companies;
+        ser_collections.contributors = contributors;
+        ser_collections.datasets = datasets;
+        ser_collections.line_groups = line_groups;
 
         ser_deser_in_tmp_dir(|path| {
             write::write_collection_with_id(path, "lines.txt", &ser_collections.lines).unwrap();
@@ -871,25 +1692,53 @@ mod tests {
                 path,
                 &ser_collections.stop_points,
                 &ser_collections.stop_areas,
+                GeneratedStopAreas::Drop,
             ).unwrap();
             write::write_collection_with_id(path, "routes.txt", &ser_collections.routes).unwrap();
-            write::write_collection_with_id(path, "trips.txt", &ser_collections.vehicle_journeys)
-                .unwrap();
+            write::write_vehicle_journeys_and_stop_times(
+                path,
+                &ser_collections.vehicle_journeys,
+                &ser_collections.stop_points,
+            ).unwrap();
             write::write_collection_with_id(path, "networks.txt", &ser_collections.networks)
                 .unwrap();
-            write::write_comments(path, &ser_collections).unwrap();
-            write::write_codes(path, &ser_collections).unwrap();
-            write::write_object_properties(path, &ser_collections).unwrap();
+            write::write_collection_with_id(path, "companies.txt", &ser_collections.companies)
+                .unwrap();
+            write::write_collection_with_id(path, "contributors.txt", &ser_collections.contributors)
+                .unwrap();
+            write::write_collection_with_id(path, "datasets.txt", &ser_collections.datasets)
+                .unwrap();
+            write::write_collection_with_id(path, "line_groups.txt", &ser_collections.line_groups)
+                .unwrap();
+            let stop_times: Vec<_> = ser_collections
+                .vehicle_journeys
+                .iter()
+                .flat_map(|(_, vj)| vj.stop_times.iter())
+                .filter_map(|st| st.extras.as_deref())
+                .collect();
+            write::write_comments(path, &ser_collections, &stop_times).unwrap();
+            write::write_codes(path, &ser_collections, &stop_times).unwrap();
+            write::write_object_properties(path, &ser_collections, &stop_times).unwrap();
 
             let mut des_collections = Collections::default();
             des_collections.lines = make_collection_with_id(path, "lines.txt").unwrap();
             des_collections.routes = make_collection_with_id(path, "routes.txt").unwrap();
             des_collections.vehicle_journeys = make_collection_with_id(path, "trips.txt").unwrap();
             des_collections.networks = make_collection_with_id(path, "networks.txt").unwrap();
-            read::manage_stops(&mut des_collections, path).unwrap();
-            read::manage_comments(&mut des_collections, path).unwrap();
-            read::manage_codes(&mut des_collections, path).unwrap();
-            read::manage_object_properties(&mut des_collections, path).unwrap();
+            des_collections.companies = make_collection_with_id(path, "companies.txt").unwrap();
+            des_collections.contributors =
+                make_collection_with_id(path, "contributors.txt").unwrap();
+            des_collections.datasets = make_collection_with_id(path, "datasets.txt").unwrap();
+            des_collections.line_groups = make_collection_with_id(path, "line_groups.txt").unwrap();
+            let mut reader = Reader::default();
+            let mut extras = StopTimeExtrasById::default();
+            let c = &mut des_collections;
+            read::manage_stops(&mut reader, c, path).unwrap();
+            read::manage_comments(&mut reader, c, path, &mut extras).unwrap();
+            read::manage_codes(&mut reader, c, path, Version::CURRENT, &mut extras).unwrap();
+            read::manage_object_properties(&mut reader, c, path, &mut extras).unwrap();
+            read::manage_stop_times(&mut reader, c, path, false, extras).unwrap();
+            assert!(reader.report.is_empty());
 
             assert_eq!(ser_collections.comments, des_collections.comments);
 
@@ -1031,6 +1880,53 @@ mod tests {
                 ser_collections.networks.get("OIF:102").unwrap().codes,
                 des_collections.networks.get("OIF:102").unwrap().codes
             );
+            assert_eq!(
+                ser_collections.networks.get("OIF:102").unwrap().comment_links,
+                des_collections.networks.get("OIF:102").unwrap().comment_links
+            );
+            assert_eq!(
+                ser_collections
+                    .networks
+                    .get("OIF:102")
+                    .unwrap()
+                    .object_properties,
+                des_collections
+                    .networks
+                    .get("OIF:102")
+                    .unwrap()
+                    .object_properties
+            );
+
+            assert_eq!(
+                ser_collections.companies.get("OIF:743").unwrap().codes,
+                des_collections.companies.get("OIF:743").unwrap().codes
+            );
+            assert_eq!(
+                ser_collections.companies.get("OIF:743").unwrap().comment_links,
+                des_collections.companies.get("OIF:743").unwrap().comment_links
+            );
+            assert_eq!(
+                ser_collections
+                    .companies
+                    .get("OIF:743")
+                    .unwrap()
+                    .object_properties,
+                des_collections
+                    .companies
+                    .get("OIF:743")
+                    .unwrap()
+                    .object_properties
+            );
+
+            assert_eq!(ser_collections.contributors, des_collections.contributors);
+            assert_eq!(ser_collections.datasets, des_collections.datasets);
+            assert_eq!(ser_collections.line_groups, des_collections.line_groups);
+            // the stop times, with their id, codes, object properties
+            // and comments
+            assert_eq!(
+                ser_collections.vehicle_journeys,
+                des_collections.vehicle_journeys
+            );
         });
     }
 
@@ -1076,6 +1972,136 @@ mod tests {
         ]);
     }
 
+    #[test]
+    fn grid_calendars_serialization_deserialization() {
+        test_serialize_deserialize_collection_with_id(vec![
+            GridCalendar {
+                id: "1".to_string(),
+                name: "Calendar 1".to_string(),
+                monday: true,
+                tuesday: true,
+                wednesday: true,
+                thursday: true,
+                friday: true,
+                saturday: false,
+                sunday: false,
+            },
+            GridCalendar {
+                id: "2".to_string(),
+                name: "Calendar 2".to_string(),
+                monday: false,
+                tuesday: false,
+                wednesday: false,
+                thursday: false,
+                friday: false,
+                saturday: true,
+                sunday: true,
+            },
+        ]);
+    }
+
+    #[test]
+    fn grid_exception_dates_serialization_deserialization() {
+        test_serialize_deserialize_collection(vec![
+            GridExceptionDate {
+                grid_calendar_id: "1".to_string(),
+                date: chrono::NaiveDate::from_ymd(2018, 5, 1),
+                included: false,
+            },
+            GridExceptionDate {
+                grid_calendar_id: "2".to_string(),
+                date: chrono::NaiveDate::from_ymd(2018, 5, 8),
+                included: true,
+            },
+        ]);
+    }
+
+    #[test]
+    fn grid_periods_serialization_deserialization() {
+        test_serialize_deserialize_collection(vec![
+            GridPeriod {
+                grid_calendar_id: "1".to_string(),
+                start_date: chrono::NaiveDate::from_ymd(2018, 1, 1),
+                end_date: chrono::NaiveDate::from_ymd(2018, 7, 6),
+            },
+            GridPeriod {
+                grid_calendar_id: "2".to_string(),
+                start_date: chrono::NaiveDate::from_ymd(2018, 7, 7),
+                end_date: chrono::NaiveDate::from_ymd(2018, 9, 2),
+            },
+        ]);
+    }
+
+    #[test]
+    fn grid_rel_calendar_line_serialization_deserialization() {
+        test_serialize_deserialize_collection(vec![
+            GridRelCalendarLine {
+                grid_calendar_id: "1".to_string(),
+                line_id: "M1".to_string(),
+                line_external_code: None,
+            },
+            GridRelCalendarLine {
+                grid_calendar_id: "2".to_string(),
+                line_id: "RERA".to_string(),
+                line_external_code: Some("RATP:RERA".to_string()),
+            },
+        ]);
+    }
+
+    #[test]
+    fn extra_data_serialization_deserialization() {
+        use std::fs;
+
+        ser_deser_in_tmp_dir(|path| {
+            let input = path.join("input");
+            let output = path.join("output");
+            fs::create_dir(&input).unwrap();
+            fs::create_dir(&output).unwrap();
+            fs::write(
+                input.join("lines.txt"),
+                "line_id,line_name,network_id,commercial_mode_id,line_custom\n\
+                 M1,Metro 1,TGN,Metro,foo\n\
+                 M2,Metro 2,TGN,Metro,\n",
+            ).unwrap();
+            fs::write(input.join("custom.txt"), "custom_id\nbar\n").unwrap();
+
+            let mut collections = Collections::default();
+            collections.lines = make_collection_with_id(&input, "lines.txt").unwrap();
+            read::manage_extra_data(&mut collections, &input).unwrap();
+
+            let lines_columns = &collections.extra_data.columns["lines.txt"];
+            assert_eq!(lines_columns.id_column, "line_id");
+            assert_eq!(lines_columns.headers, vec!["line_custom"]);
+            assert_eq!(lines_columns.values["M1"], vec!["foo"]);
+            assert_eq!(
+                collections.extra_data.files.keys().collect::<Vec<_>>(),
+                vec!["custom.txt"]
+            );
+
+            collections
+                .lines
+                .index_mut(collections.lines.get_idx("M2").unwrap())
+                .name = "Metro 2bis".to_string();
+            write::write_collection_with_id(&output, "lines.txt", &collections.lines).unwrap();
+            write::write_extra_data(&output, &collections.extra_data).unwrap();
+
+            assert_eq!(
+                fs::read_to_string(output.join("custom.txt")).unwrap(),
+                "custom_id\nbar\n"
+            );
+            let mut rdr = ::csv::Reader::from_path(output.join("lines.txt")).unwrap();
+            let headers = rdr.headers().unwrap().clone();
+            let custom_position = headers.iter().position(|h| h == "line_custom").unwrap();
+            let custom_values: Vec<_> = rdr.records()
+                .map(|r| r.unwrap()[custom_position].to_string())
+                .collect();
+            assert_eq!(custom_values, vec!["foo", ""]);
+            let des_lines: CollectionWithId<Line> =
+                make_collection_with_id(&output, "lines.txt").unwrap();
+            assert_eq!(des_lines, collections.lines);
+        });
+    }
+
     #[test]
     fn admin_stations_serialization_deserialization() {
         test_serialize_deserialize_collection(vec![
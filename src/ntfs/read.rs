@@ -14,13 +14,24 @@
 // along with this program.  If not, see
 // <http://www.gnu.org/licenses/>.
 
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::mem;
 use std::path;
 use csv;
+use serde;
 
 use objects::*;
 use collection::*;
+use extra_data::{ExtraColumns, ExtraData};
+use stop_times::interpolate_times;
+use error::ErrorKind;
+use provenance::{Provenance, Source};
+use read_report::{ReadIssue, Reader, SkipRow, Strictness};
+use utils::struct_fields;
 use Collections;
-use super::{Code, CommentLink, ObjectProperty, Stop, StopTime};
+use super::{Code, CommentLink, ObjectProperty, Stop, StopTime, Version, VisitedVehicleJourney,
+            NTFS_FILES, RENAMED_COLUMNS};
 use Result;
 use failure::ResultExt;
 
@@ -40,6 +51,7 @@ impl From<Stop> for StopArea {
             timezone: stop.timezone,
             geometry_id: stop.geometry_id,
             equipment_id: stop.equipment_id,
+            generated: false,
         }
     }
 }
@@ -67,80 +79,331 @@ impl From<Stop> for StopPoint {
     }
 }
 
-pub fn manage_stops(collections: &mut Collections, path: &path::Path) -> Result<()> {
+pub fn manage_stops(
+    reader: &mut Reader,
+    collections: &mut Collections,
+    path: &path::Path,
+) -> Result<()> {
     info!("Reading stops.txt");
     let path = path.join("stops.txt");
     let mut rdr = csv::Reader::from_path(&path).with_context(ctx_from_path!(path))?;
-    let mut stop_areas = vec![];
-    let mut stop_points = vec![];
-    for stop in rdr.deserialize() {
-        let stop: Stop = stop.with_context(ctx_from_path!(path))?;
+    let mut stop_areas = CollectionWithId::default();
+    let mut stop_points = CollectionWithId::default();
+    let mut provenance = mem::take(&mut reader.provenance);
+    reader.read_sourced_rows(&path, &mut rdr, |mut stop: Stop, source| {
         match stop.location_type {
             0 => {
                 if stop.parent_station.is_none() {
                     let mut new_stop_area = stop.clone();
-                    new_stop_area.id = format!("Navitia:{}", new_stop_area.id);
-                    stop_areas.push(StopArea::from(new_stop_area));
+                    new_stop_area.id = StopArea::generated_id(&stop.id);
+                    stop.parent_station = Some(new_stop_area.id.clone());
+                    if let Some(ref source) = source {
+                        provenance.insert::<StopArea>(&new_stop_area.id, source.clone());
+                    }
+                    stop_areas.push(StopArea {
+                        generated: true,
+                        ..StopArea::from(new_stop_area)
+                    })?;
                 }
-                stop_points.push(StopPoint::from(stop));
+                if let Some(source) = source {
+                    provenance.insert::<StopPoint>(&stop.id, source);
+                }
+                stop_points.push(StopPoint::from(stop))?;
+            }
+            1 => {
+                if let Some(source) = source {
+                    provenance.insert::<StopArea>(&stop.id, source);
+                }
+                stop_areas.push(StopArea::from(stop))?;
             }
-            1 => stop_areas.push(StopArea::from(stop)),
             i => warn!("stop.location_type = {} not yet supported, skipping.", i),
         }
+        Ok(())
+    })?;
+    reader.provenance = provenance;
+    if reader.strictness == Strictness::Lenient {
+        let skipped = &mut reader.report.skipped_rows;
+        stop_points.retain(|sp| {
+            let found = stop_areas.get_idx(&sp.stop_area_id).is_some();
+            if !found {
+                let message = format!("parent_station={:?} not found", sp.stop_area_id);
+                warn!("stops.txt: stop point {:?} skipped: {}", sp.id, message);
+                skipped.push(ReadIssue {
+                    file: "stops.txt".to_string(),
+                    line: None,
+                    message,
+                });
+            }
+            found
+        });
     }
-    collections.stop_areas = CollectionWithId::new(stop_areas)?;
-    collections.stop_points = CollectionWithId::new(stop_points)?;
+    collections.stop_areas = stop_areas;
+    collections.stop_points = stop_points;
     Ok(())
 }
 
-pub fn manage_stop_times(collections: &mut Collections, path: &path::Path) -> Result<()> {
+type StopTimeIdx = (Idx<VehicleJourney>, u32);
+
+/// The codes, object properties and comments of the stop times, which
+/// are read before the stop times and kept by `stop_time_id` until the
+/// stop times are read.
+#[derive(Default)]
+pub struct StopTimeExtrasById {
+    codes: HashMap<String, KeysValues>,
+    object_properties: HashMap<String, KeysValues>,
+    comment_links: HashMap<String, CommentLinksT>,
+}
+
+impl StopTimeExtrasById {
+    /// The extras of the stop time of id `id`.
+    fn take(&mut self, id: String) -> StopTimeExtras {
+        StopTimeExtras {
+            codes: self.codes.remove(&id).unwrap_or_default(),
+            object_properties: self.object_properties.remove(&id).unwrap_or_default(),
+            comment_links: self.comment_links.remove(&id).unwrap_or_default(),
+            id: Some(id),
+        }
+    }
+
+    /// Skips the extras of the stop times that were not found, as the
+    /// extras of the other unknown objects.
+    fn skip_remaining(self, reader: &mut Reader) {
+        let mut remaining: Vec<_> = self.codes
+            .keys()
+            .map(|id| ("object_codes.txt", id))
+            .chain(
+                self.object_properties
+                    .keys()
+                    .map(|id| ("object_properties.txt", id)),
+            )
+            .chain(self.comment_links.keys().map(|id| ("comment_links.txt", id)))
+            .collect();
+        remaining.sort();
+        for (file, id) in remaining {
+            let message = format!(
+                "{}: object_type=stop_time object_id={} not found",
+                file, id
+            );
+            error!("{}: {}", file, message);
+            reader.report.skipped_rows.push(ReadIssue {
+                file: file.to_string(),
+                line: None,
+                message,
+            });
+        }
+    }
+}
+
+/// Reads the rows of stop_times.txt, giving each stop time with its
+/// extras to `f` with the index of its vehicle journey, and whether its
+/// times are missing and are to be interpolated.
+fn read_stop_time_rows<F>(
+    reader: &mut Reader,
+    collections: &Collections,
+    path: &path::Path,
+    interpolate_missing_times: bool,
+    mut extras: StopTimeExtrasById,
+    mut f: F,
+) -> Result<()>
+where
+    F: FnMut(Idx<VehicleJourney>, ::objects::StopTime, bool) -> Result<()>,
+{
     info!("Reading stop_times.txt");
     let path = path.join("stop_times.txt");
     let mut rdr = csv::Reader::from_path(&path).with_context(ctx_from_path!(path))?;
-    for stop_time in rdr.deserialize() {
-        let stop_time: StopTime = stop_time.with_context(ctx_from_path!(path))?;
+    let mut provenance = mem::take(&mut reader.provenance);
+    reader.read_sourced_rows(&path, &mut rdr, |stop_time: StopTime, source| {
         let stop_point_idx = collections
             .stop_points
             .get_idx(&stop_time.stop_id)
-            .ok_or_else(|| {
-                format_err!(
-                    "Problem reading {:?}: stop_id={:?} not found",
-                    path,
-                    stop_time.stop_id
-                )
+            .ok_or_else(|| ErrorKind::UnknownReference {
+                from_type: "StopTime",
+                from_id: format!("{}:{}", stop_time.trip_id, stop_time.stop_sequence),
+                to_type: "StopPoint",
+                to_id: stop_time.stop_id.clone(),
             })?;
         let vj_idx = collections
             .vehicle_journeys
             .get_idx(&stop_time.trip_id)
-            .ok_or_else(|| {
-                format_err!(
-                    "Problem reading {:?}: trip_id={:?} not found",
-                    path,
-                    stop_time.stop_id
-                )
+            .ok_or_else(|| ErrorKind::UnknownReference {
+                from_type: "StopTime",
+                from_id: format!("{}:{}", stop_time.trip_id, stop_time.stop_sequence),
+                to_type: "VehicleJourney",
+                to_id: stop_time.trip_id.clone(),
             })?;
-        collections
-            .vehicle_journeys
-            .index_mut(vj_idx)
-            .stop_times
-            .push(::objects::StopTime {
-                stop_point_idx,
-                sequence: stop_time.stop_sequence,
-                arrival_time: stop_time.arrival_time,
-                departure_time: stop_time.departure_time,
-                boarding_duration: stop_time.boarding_duration,
-                alighting_duration: stop_time.alighting_duration,
-                pickup_type: stop_time.pickup_type,
-                dropoff_type: stop_time.dropoff_type,
-                datetime_estimated: stop_time.datetime_estimated,
-                local_zone_id: stop_time.local_zone_id,
-            });
+        if let Some(source) = source {
+            provenance.insert_stop_time(&stop_time.trip_id, stop_time.stop_sequence, source);
+        }
+        let (arrival_time, departure_time, missing) =
+            match (stop_time.arrival_time, stop_time.departure_time) {
+                (Some(arrival), Some(departure)) => (arrival, departure, false),
+                (Some(time), None) | (None, Some(time)) => (time, time, false),
+                (None, None) => {
+                    if !interpolate_missing_times {
+                        return Err(ErrorKind::InvalidValue {
+                            field: "arrival_time",
+                            value: String::new(),
+                            message: format!(
+                                "no time for trip_id={:?}, stop_sequence={}",
+                                stop_time.trip_id, stop_time.stop_sequence
+                            ),
+                        }.into());
+                    }
+                    (Time::default(), Time::default(), true)
+                }
+            };
+        let st = ::objects::StopTime {
+            stop_point_idx,
+            sequence: stop_time.stop_sequence,
+            arrival_time,
+            departure_time,
+            boarding_duration: stop_time.boarding_duration,
+            alighting_duration: stop_time.alighting_duration,
+            pickup_type: stop_time.pickup_type,
+            dropoff_type: stop_time.dropoff_type,
+            datetime_estimated: stop_time.datetime_estimated,
+            local_zone_id: stop_time.local_zone_id,
+            extras: stop_time.stop_time_id.map(|id| Box::new(extras.take(id))),
+        };
+        f(vj_idx, st, missing)
+    })?;
+    reader.provenance = provenance;
+    extras.skip_remaining(reader);
+    Ok(())
+}
+
+/// Sorts the stop times of a vehicle journey by sequence, and
+/// interpolates their missing times.
+fn complete_stop_times(
+    vj_idx: Idx<VehicleJourney>,
+    vj_id: &str,
+    stop_times: &mut [::objects::StopTime],
+    missing_times: &HashSet<StopTimeIdx>,
+    stop_points: &CollectionWithId<StopPoint>,
+) -> Result<()> {
+    stop_times.sort_unstable_by_key(|st| st.sequence);
+    if missing_times.is_empty() {
+        return Ok(());
+    }
+    let missing: Vec<_> = stop_times
+        .iter()
+        .map(|st| missing_times.contains(&(vj_idx, st.sequence)))
+        .collect();
+    if missing.contains(&true) {
+        interpolate_times(stop_times, &missing, stop_points)
+            .with_context(|_| format!("Error interpolating the times of trip {:?}", vj_id))?;
+    }
+    Ok(())
+}
+
+/// Reads the stop times in their vehicle journeys, giving them the
+/// `extras` read before.
+pub fn manage_stop_times(
+    reader: &mut Reader,
+    collections: &mut Collections,
+    path: &path::Path,
+    interpolate_missing_times: bool,
+    extras: StopTimeExtrasById,
+) -> Result<()> {
+    let mut stop_times: Vec<Vec<::objects::StopTime>> =
+        collections.vehicle_journeys.iter().map(|_| vec![]).collect();
+    let mut missing_times = HashSet::new();
+    read_stop_time_rows(
+        reader,
+        collections,
+        path,
+        interpolate_missing_times,
+        extras,
+        |vj_idx, stop_time, missing| {
+            if missing {
+                missing_times.insert((vj_idx, stop_time.sequence));
+            }
+            stop_times[vj_idx.get()].push(stop_time);
+            Ok(())
+        },
+    )?;
+    let stop_points = &collections.stop_points;
+    collections
+        .vehicle_journeys
+        .try_for_each_mut(|vj_idx, vj| {
+            vj.stop_times.append(&mut stop_times[vj_idx.get()]);
+            complete_stop_times(vj_idx, &vj.id, &mut vj.stop_times, &missing_times, stop_points)
+        })
+}
+
+/// Streams stop_times.txt, whose stop times must be grouped by trip,
+/// giving each vehicle journey with its complete stop times to
+/// `visitor` as soon as its last stop time is read.  The vehicle
+/// journeys without stop times are given afterwards.
+pub fn visit_stop_times<F>(
+    reader: &mut Reader,
+    collections: &Collections,
+    path: &path::Path,
+    interpolate_missing_times: bool,
+    extras: StopTimeExtrasById,
+    mut visitor: F,
+) -> Result<()>
+where
+    F: FnMut(&Collections, &VisitedVehicleJourney) -> Result<()>,
+{
+    let c = collections;
+    let mut visited = vec![false; c.vehicle_journeys.len()];
+    let mut current: Option<Idx<VehicleJourney>> = None;
+    let mut stop_times = vec![];
+    let mut missing_times = HashSet::new();
+    {
+        let mut visit = |idx: Idx<VehicleJourney>,
+                         stop_times: &mut Vec<::objects::StopTime>,
+                         missing_times: &mut HashSet<StopTimeIdx>|
+         -> Result<()> {
+            let vj_id = &c.vehicle_journeys[idx].id;
+            complete_stop_times(idx, vj_id, stop_times, missing_times, &c.stop_points)?;
+            visitor(c, &VisitedVehicleJourney { idx, stop_times })?;
+            stop_times.clear();
+            missing_times.clear();
+            Ok(())
+        };
+        read_stop_time_rows(
+            reader,
+            c,
+            path,
+            interpolate_missing_times,
+            extras,
+            |vj_idx, stop_time, missing| {
+                if current != Some(vj_idx) {
+                    if let Some(previous) = current {
+                        visit(previous, &mut stop_times, &mut missing_times)?;
+                    }
+                    if visited[vj_idx.get()] {
+                        let trip_id = &c.vehicle_journeys[vj_idx].id;
+                        return Err(ErrorKind::InvalidValue {
+                            field: "trip_id",
+                            value: trip_id.to_string(),
+                            message: format!(
+                                "the stop times of trip {:?} are not grouped",
+                                trip_id
+                            ),
+                        }.into());
+                    }
+                    visited[vj_idx.get()] = true;
+                    current = Some(vj_idx);
+                }
+                if missing {
+                    missing_times.insert((vj_idx, stop_time.sequence));
+                }
+                stop_times.push(stop_time);
+                Ok(())
+            },
+        )?;
+        if let Some(last) = current {
+            visit(last, &mut stop_times, &mut missing_times)?;
+        }
     }
-    let mut vehicle_journeys = collections.vehicle_journeys.take();
-    for vj in &mut vehicle_journeys {
-        vj.stop_times.sort_unstable_by_key(|st| st.sequence);
+    for (idx, _) in c.vehicle_journeys.iter() {
+        if !visited[idx.get()] {
+            visitor(c, &VisitedVehicleJourney { idx, stop_times: &[] })?;
+        }
     }
-    collections.vehicle_journeys = CollectionWithId::new(vehicle_journeys)?;
     Ok(())
 }
 
@@ -153,25 +416,56 @@ where
         .codes_mut()
         .push((code.object_system, code.object_code));
 }
-fn insert_code<T>(collection: &mut CollectionWithId<T>, code: Code)
+fn insert_code<T>(
+    collection: &mut CollectionWithId<T>,
+    code: Code,
+    source: Option<Source>,
+    provenance: &mut Provenance,
+) -> Result<()>
 where
     T: Codes + Id<T>,
 {
-    let idx = match collection.get_idx(&code.object_id) {
-        Some(idx) => idx,
-        None => {
-            error!(
-                "object_codes.txt: object_type={} object_id={} not found",
-                code.object_type.as_str(),
-                code.object_id
-            );
-            return;
-        }
-    };
+    let idx = collection.get_idx(&code.object_id).ok_or_else(|| {
+        SkipRow(format!(
+            "object_codes.txt: object_type={} object_id={} not found",
+            code.object_type.as_str(),
+            code.object_id
+        ))
+    })?;
+    if let Some(source) = source {
+        let (id, system, value) = (&code.object_id, &code.object_system, &code.object_code);
+        provenance.insert_code::<T>(id, system, value, source);
+    }
     insert_code_with_idx(collection, idx, code);
+    Ok(())
 }
 
-pub fn manage_codes(collections: &mut Collections, path: &path::Path) -> Result<()> {
+fn migrate_headers<R: ::std::io::Read>(
+    rdr: &mut csv::Reader<R>,
+    file: &str,
+    version: Version,
+) -> csv::Result<()> {
+    let headers: csv::StringRecord = rdr.headers()?
+        .iter()
+        .map(|header| {
+            RENAMED_COLUMNS
+                .iter()
+                .find(|&&(f, renamed_in, old, _)| f == file && version < renamed_in && old == header)
+                .map_or(header, |&(_, _, _, new)| new)
+        })
+        .collect();
+    rdr.set_headers(headers);
+    Ok(())
+}
+
+/// Reads the codes, keeping those of the stop times in `extras`.
+pub fn manage_codes(
+    reader: &mut Reader,
+    collections: &mut Collections,
+    path: &path::Path,
+    version: Version,
+    extras: &mut StopTimeExtrasById,
+) -> Result<()> {
     let file = "object_codes.txt";
     if !path.join(file).exists() {
         info!("Skipping {}", file);
@@ -180,23 +474,43 @@ pub fn manage_codes(collections: &mut Collections, path: &path::Path) -> Result<
     info!("Reading {}", file);
     let path = path.join(file);
     let mut rdr = csv::Reader::from_path(&path).with_context(ctx_from_path!(path))?;
-    for code in rdr.deserialize() {
-        let code: Code = code.with_context(ctx_from_path!(path))?;
+    migrate_headers(&mut rdr, file, version).with_context(ctx_from_path!(path))?;
+    let mut provenance = mem::take(&mut reader.provenance);
+    let res = reader.read_sourced_rows(&path, &mut rdr, |code: Code, source| {
+        let p = &mut provenance;
         match code.object_type {
-            ObjectType::StopArea => insert_code(&mut collections.stop_areas, code),
-            ObjectType::StopPoint => insert_code(&mut collections.stop_points, code),
-            ObjectType::Network => insert_code(&mut collections.networks, code),
-            ObjectType::Line => insert_code(&mut collections.lines, code),
-            ObjectType::Route => insert_code(&mut collections.routes, code),
-            ObjectType::VehicleJourney => insert_code(&mut collections.vehicle_journeys, code),
-            _ => bail!(
-                "Problem reading {:?}: code does not support {}",
-                path,
-                code.object_type.as_str()
-            ),
+            ObjectType::StopArea => insert_code(&mut collections.stop_areas, code, source, p),
+            ObjectType::StopPoint => insert_code(&mut collections.stop_points, code, source, p),
+            ObjectType::Network => insert_code(&mut collections.networks, code, source, p),
+            ObjectType::Line => insert_code(&mut collections.lines, code, source, p),
+            ObjectType::Route => insert_code(&mut collections.routes, code, source, p),
+            ObjectType::VehicleJourney => {
+                insert_code(&mut collections.vehicle_journeys, code, source, p)
+            }
+            ObjectType::Company => insert_code(&mut collections.companies, code, source, p),
+            ObjectType::Calendar => insert_code(&mut collections.calendars, code, source, p),
+            ObjectType::LineGroup => insert_code(&mut collections.line_groups, code, source, p),
+            ObjectType::Contributor => {
+                insert_code(&mut collections.contributors, code, source, p)
+            }
+            ObjectType::Dataset => insert_code(&mut collections.datasets, code, source, p),
+            ObjectType::StopTime => {
+                if let Some(source) = source {
+                    let (id, system, value) =
+                        (&code.object_id, &code.object_system, &code.object_code);
+                    p.insert_code::<::objects::StopTime>(id, system, value, source);
+                }
+                extras
+                    .codes
+                    .entry(code.object_id)
+                    .or_default()
+                    .push((code.object_system, code.object_code));
+                Ok(())
+            }
         }
-    }
-    Ok(())
+    });
+    reader.provenance = provenance;
+    res
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -207,24 +521,40 @@ struct FeedInfo {
     info_value: String,
 }
 
-pub fn manage_feed_infos(collections: &mut Collections, path: &path::Path) -> Result<()> {
+pub fn manage_feed_infos(
+    reader: &mut Reader,
+    collections: &mut Collections,
+    path: &path::Path,
+) -> Result<()> {
     info!("Reading feed_infos.txt");
     let path = path.join("feed_infos.txt");
     let mut rdr = csv::Reader::from_path(&path).with_context(ctx_from_path!(path))?;
     collections.feed_infos.clear();
-    for feed_info in rdr.deserialize() {
-        let feed_info: FeedInfo = feed_info.with_context(ctx_from_path!(path))?;
-        ensure!(
-            collections
-                .feed_infos
-                .insert(feed_info.info_param.clone(), feed_info.info_value)
-                .is_none(),
-            "Problem reading {:?}: {} already found in file feed_infos.txt",
-            path,
-            feed_info.info_param,
-        );
-    }
-    Ok(())
+    let feed_infos = &mut collections.feed_infos;
+    reader.read_rows(&path, &mut rdr, |feed_info: FeedInfo| {
+        if feed_infos.contains_key(&feed_info.info_param) {
+            return Err(ErrorKind::DuplicateId {
+                collection: "feed_infos",
+                id: feed_info.info_param,
+            }.into());
+        }
+        feed_infos.insert(feed_info.info_param, feed_info.info_value);
+        Ok(())
+    })
+}
+
+fn comment_idx(
+    comments: &CollectionWithId<Comment>,
+    comment_link: &CommentLink,
+) -> Result<Idx<Comment>> {
+    Ok(comments
+        .get_idx(&comment_link.comment_id)
+        .ok_or_else(|| ErrorKind::UnknownReference {
+            from_type: "CommentLink",
+            from_id: comment_link.object_id.clone(),
+            to_type: "Comment",
+            to_id: comment_link.comment_id.clone(),
+        })?)
 }
 
 fn insert_comment_link<T>(
@@ -235,24 +565,14 @@ fn insert_comment_link<T>(
 where
     T: CommentLinks + Id<T>,
 {
-    let idx = match collection.get_idx(&comment_link.object_id) {
-        Some(idx) => idx,
-        None => {
-            error!(
-                "comment_links.txt: object_type={} object_id={} not found",
-                comment_link.object_type.as_str(),
-                comment_link.object_id
-            );
-            return Ok(());
-        }
-    };
-    let comment_idx = match comments.get_idx(&comment_link.comment_id) {
-        Some(comment_idx) => comment_idx,
-        None => bail!(
-            "comment.txt: comment_id={} not found",
-            comment_link.comment_id
-        ),
-    };
+    let idx = collection.get_idx(&comment_link.object_id).ok_or_else(|| {
+        SkipRow(format!(
+            "comment_links.txt: object_type={} object_id={} not found",
+            comment_link.object_type.as_str(),
+            comment_link.object_id
+        ))
+    })?;
+    let comment_idx = comment_idx(comments, comment_link)?;
     collection
         .index_mut(idx)
         .comment_links_mut()
@@ -260,76 +580,119 @@ where
     Ok(())
 }
 
-pub fn manage_comments(collections: &mut Collections, path: &path::Path) -> Result<()> {
+/// Reads the comments, keeping the comment links of the stop times in
+/// `extras`.
+pub fn manage_comments(
+    reader: &mut Reader,
+    collections: &mut Collections,
+    path: &path::Path,
+    extras: &mut StopTimeExtrasById,
+) -> Result<()> {
     if path.join("comments.txt").exists() {
-        collections.comments = make_collection_with_id(path, "comments.txt")?;
+        collections.comments = reader.collection_with_id(path, "comments.txt")?;
 
         let path = path.join("comment_links.txt");
         if let Ok(mut rdr) = csv::Reader::from_path(&path) {
             info!("Reading comment_links.txt");
-            for comment_link in rdr.deserialize() {
-                let comment_link: CommentLink = comment_link.with_context(ctx_from_path!(path))?;
+            reader.read_rows(&path, &mut rdr, |comment_link: CommentLink| {
                 match comment_link.object_type {
                     ObjectType::StopArea => insert_comment_link(
                         &mut collections.stop_areas,
                         &collections.comments,
                         &comment_link,
-                    )?,
+                    ),
                     ObjectType::StopPoint => insert_comment_link(
                         &mut collections.stop_points,
                         &collections.comments,
                         &comment_link,
-                    )?,
+                    ),
                     ObjectType::Line => insert_comment_link(
                         &mut collections.lines,
                         &collections.comments,
                         &comment_link,
-                    )?,
+                    ),
                     ObjectType::Route => insert_comment_link(
                         &mut collections.routes,
                         &collections.comments,
                         &comment_link,
-                    )?,
+                    ),
                     ObjectType::VehicleJourney => insert_comment_link(
                         &mut collections.vehicle_journeys,
                         &collections.comments,
                         &comment_link,
-                    )?,
-                    ObjectType::StopTime => warn!("comments are not added to StopTime yet"),
-                    ObjectType::LineGroup => warn!("line_groups.txt is not parsed yet"),
-                    _ => bail!(
-                        "comment does not support {}",
-                        comment_link.object_type.as_str()
                     ),
+                    ObjectType::Network => insert_comment_link(
+                        &mut collections.networks,
+                        &collections.comments,
+                        &comment_link,
+                    ),
+                    ObjectType::Company => insert_comment_link(
+                        &mut collections.companies,
+                        &collections.comments,
+                        &comment_link,
+                    ),
+                    ObjectType::Calendar => insert_comment_link(
+                        &mut collections.calendars,
+                        &collections.comments,
+                        &comment_link,
+                    ),
+                    ObjectType::LineGroup => insert_comment_link(
+                        &mut collections.line_groups,
+                        &collections.comments,
+                        &comment_link,
+                    ),
+                    ObjectType::StopTime => {
+                        let comment_idx = comment_idx(&collections.comments, &comment_link)?;
+                        extras
+                            .comment_links
+                            .entry(comment_link.object_id)
+                            .or_default()
+                            .push(comment_idx);
+                        Ok(())
+                    }
+                    ObjectType::Contributor | ObjectType::Dataset => {
+                        warn!(
+                            "comment_links.txt: comments are not supported on {}, skipping",
+                            comment_link.object_type.as_str()
+                        );
+                        Ok(())
+                    }
                 }
-            }
+            })?;
         }
     }
     Ok(())
 }
 
-fn insert_object_property<T>(collection: &mut CollectionWithId<T>, obj_prop: ObjectProperty)
+fn insert_object_property<T>(
+    collection: &mut CollectionWithId<T>,
+    obj_prop: ObjectProperty,
+) -> Result<()>
 where
     T: ObjectProperties + Id<T>,
 {
-    let idx = match collection.get_idx(&obj_prop.object_id) {
-        Some(idx) => idx,
-        None => {
-            error!(
-                "object_properties.txt: object_type={} object_id={} not found",
-                obj_prop.object_type.as_str(),
-                obj_prop.object_id
-            );
-            return;
-        }
-    };
+    let idx = collection.get_idx(&obj_prop.object_id).ok_or_else(|| {
+        SkipRow(format!(
+            "object_properties.txt: object_type={} object_id={} not found",
+            obj_prop.object_type.as_str(),
+            obj_prop.object_id
+        ))
+    })?;
     collection.index_mut(idx).object_properties_mut().push((
         obj_prop.object_property_name,
         obj_prop.object_property_value,
     ));
+    Ok(())
 }
 
-pub fn manage_object_properties(collections: &mut Collections, path: &path::Path) -> Result<()> {
+/// Reads the object properties, keeping those of the stop times in
+/// `extras`.
+pub fn manage_object_properties(
+    reader: &mut Reader,
+    collections: &mut Collections,
+    path: &path::Path,
+    extras: &mut StopTimeExtrasById,
+) -> Result<()> {
     let file = "object_properties.txt";
     let path = path.join(file);
     if !path.exists() {
@@ -338,8 +701,7 @@ pub fn manage_object_properties(collections: &mut Collections, path: &path::Path
     }
     info!("Reading {}", file);
     let mut rdr = csv::Reader::from_path(&path).with_context(ctx_from_path!(path))?;
-    for obj_prop in rdr.deserialize() {
-        let obj_prop: ObjectProperty = obj_prop.with_context(ctx_from_path!(path))?;
+    reader.read_rows(&path, &mut rdr, |obj_prop: ObjectProperty| {
         match obj_prop.object_type {
             ObjectType::StopArea => insert_object_property(&mut collections.stop_areas, obj_prop),
             ObjectType::StopPoint => insert_object_property(&mut collections.stop_points, obj_prop),
@@ -348,12 +710,131 @@ pub fn manage_object_properties(collections: &mut Collections, path: &path::Path
             ObjectType::VehicleJourney => {
                 insert_object_property(&mut collections.vehicle_journeys, obj_prop)
             }
-            _ => bail!(
-                "Problem with {:?}: object_property does not support {}",
-                path,
-                obj_prop.object_type.as_str()
-            ),
+            ObjectType::Network => insert_object_property(&mut collections.networks, obj_prop),
+            ObjectType::Company => insert_object_property(&mut collections.companies, obj_prop),
+            ObjectType::Calendar => insert_object_property(&mut collections.calendars, obj_prop),
+            ObjectType::LineGroup => {
+                insert_object_property(&mut collections.line_groups, obj_prop)
+            }
+            ObjectType::Contributor => {
+                insert_object_property(&mut collections.contributors, obj_prop)
+            }
+            ObjectType::Dataset => insert_object_property(&mut collections.datasets, obj_prop),
+            ObjectType::StopTime => {
+                extras
+                    .object_properties
+                    .entry(obj_prop.object_id)
+                    .or_default()
+                    .push((obj_prop.object_property_name, obj_prop.object_property_value));
+                Ok(())
+            }
+        }
+    })
+}
+
+fn read_extra_columns<T>(
+    extra_data: &mut ExtraData,
+    path: &path::Path,
+    file: &str,
+    id_column: &str,
+) -> Result<()>
+where
+    for<'de> T: serde::Deserialize<'de>,
+{
+    let path = path.join(file);
+    if !path.exists() {
+        return Ok(());
+    }
+    let mut rdr = csv::Reader::from_path(&path).with_context(ctx_from_path!(path))?;
+    let headers = rdr.headers().with_context(ctx_from_path!(path))?.clone();
+    let known_fields = struct_fields::<T>();
+    let extra_positions: Vec<_> = headers
+        .iter()
+        .enumerate()
+        .filter(|&(_, h)| !known_fields.contains(&h))
+        .map(|(i, _)| i)
+        .collect();
+    if extra_positions.is_empty() {
+        return Ok(());
+    }
+    let id_position = headers
+        .iter()
+        .position(|h| h == id_column)
+        .ok_or_else(|| ErrorKind::InvalidValue {
+            field: "header",
+            value: headers.iter().collect::<Vec<_>>().join(","),
+            message: format!("Problem reading {:?}: no {} column", path, id_column),
+        })?;
+    info!("Keeping unknown columns of {}", file);
+    let mut extra_columns = ExtraColumns {
+        id_column: id_column.to_string(),
+        headers: extra_positions
+            .iter()
+            .map(|&i| headers[i].to_string())
+            .collect(),
+        ..ExtraColumns::default()
+    };
+    for record in rdr.records() {
+        let record = record.with_context(ctx_from_path!(path))?;
+        let values = extra_positions
+            .iter()
+            .map(|&i| record.get(i).unwrap_or("").to_string())
+            .collect();
+        let id = record.get(id_position).unwrap_or("").to_string();
+        extra_columns.values.insert(id, values);
+    }
+    extra_data.columns.insert(file.to_string(), extra_columns);
+    Ok(())
+}
+
+pub fn manage_extra_data(collections: &mut Collections, path: &path::Path) -> Result<()> {
+    let extra_data = &mut collections.extra_data;
+    read_extra_columns::<Contributor>(extra_data, path, "contributors.txt", "contributor_id")?;
+    read_extra_columns::<Dataset>(extra_data, path, "datasets.txt", "dataset_id")?;
+    read_extra_columns::<CommercialMode>(
+        extra_data,
+        path,
+        "commercial_modes.txt",
+        "commercial_mode_id",
+    )?;
+    read_extra_columns::<Network>(extra_data, path, "networks.txt", "network_id")?;
+    read_extra_columns::<Line>(extra_data, path, "lines.txt", "line_id")?;
+    read_extra_columns::<Route>(extra_data, path, "routes.txt", "route_id")?;
+    read_extra_columns::<VehicleJourney>(extra_data, path, "trips.txt", "trip_id")?;
+    read_extra_columns::<PhysicalMode>(
+        extra_data,
+        path,
+        "physical_modes.txt",
+        "physical_mode_id",
+    )?;
+    read_extra_columns::<Company>(extra_data, path, "companies.txt", "company_id")?;
+    read_extra_columns::<Equipment>(extra_data, path, "equipments.txt", "equipment_id")?;
+    read_extra_columns::<TripProperty>(
+        extra_data,
+        path,
+        "trip_properties.txt",
+        "trip_property_id",
+    )?;
+    read_extra_columns::<Geometry>(extra_data, path, "geometries.txt", "geometry_id")?;
+    read_extra_columns::<Comment>(extra_data, path, "comments.txt", "comment_id")?;
+    read_extra_columns::<GridCalendar>(
+        extra_data,
+        path,
+        "grid_calendars.txt",
+        "grid_calendar_id",
+    )?;
+    read_extra_columns::<Calendar>(extra_data, path, "calendar.txt", "service_id")?;
+    read_extra_columns::<Stop>(extra_data, path, "stops.txt", "stop_id")?;
+
+    for entry in fs::read_dir(path).with_context(ctx_from_path!(path))? {
+        let entry = entry.with_context(ctx_from_path!(path))?;
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if !entry.path().is_file() || NTFS_FILES.contains(&file_name.as_str()) {
+            continue;
         }
+        info!("Keeping unknown file {}", file_name);
+        let content = fs::read(entry.path()).with_context(ctx_from_path!(entry.path()))?;
+        extra_data.files.insert(file_name, content);
     }
     Ok(())
 }
@@ -15,14 +15,17 @@
 // <http://www.gnu.org/licenses/>.
 
 use std::collections::HashMap;
+use std::fs;
+use std::io;
 use std::path;
 use csv;
 use collection::{Collection, CollectionWithId, Id};
+use extra_data::{ExtraColumns, ExtraData};
 use serde;
 use objects::*;
 use Collections;
 use common_format::CalendarDate;
-use super::{Code, CommentLink, ObjectProperty, Result, Stop, StopTime};
+use super::{Code, CommentLink, GeneratedStopAreas, ObjectProperty, Result, Stop, StopTimeRef};
 use failure::ResultExt;
 
 pub fn write_feed_infos(path: &path::Path, feed_infos: &HashMap<String, String>) -> Result<()> {
@@ -38,6 +41,44 @@ pub fn write_feed_infos(path: &path::Path, feed_infos: &HashMap<String, String>)
     Ok(())
 }
 
+pub fn create_writer(path: &path::Path) -> Result<csv::Writer<fs::File>> {
+    Ok(csv::Writer::from_path(path).with_context(ctx_from_path!(path))?)
+}
+
+pub fn flush_writer<W: io::Write>(wtr: &mut csv::Writer<W>, path: &path::Path) -> Result<()> {
+    wtr.flush().with_context(ctx_from_path!(path))?;
+    Ok(())
+}
+
+/// Writes the stop times of a vehicle journey with `wtr`, borrowing
+/// their strings instead of copying them.
+pub fn write_stop_times<W: io::Write>(
+    wtr: &mut csv::Writer<W>,
+    path: &path::Path,
+    vj: &VehicleJourney,
+    stop_times: &[StopTime],
+    stop_points: &CollectionWithId<StopPoint>,
+) -> Result<()> {
+    for st in stop_times {
+        wtr.serialize(StopTimeRef {
+            stop_id: &stop_points[st.stop_point_idx].id,
+            trip_id: &vj.id,
+            stop_sequence: st.sequence,
+            arrival_time: st.arrival_time,
+            departure_time: st.departure_time,
+            boarding_duration: st.boarding_duration,
+            alighting_duration: st.alighting_duration,
+            pickup_type: st.pickup_type,
+            dropoff_type: st.dropoff_type,
+            datetime_estimated: st.datetime_estimated,
+            local_zone_id: st.local_zone_id,
+            stop_time_id: st.id(),
+            // TODO: Add headsign
+        }).with_context(ctx_from_path!(path))?;
+    }
+    Ok(())
+}
+
 pub fn write_vehicle_journeys_and_stop_times(
     path: &path::Path,
     vehicle_journeys: &CollectionWithId<VehicleJourney>,
@@ -53,25 +94,7 @@ pub fn write_vehicle_journeys_and_stop_times(
         vj_wtr
             .serialize(vj)
             .with_context(ctx_from_path!(trip_path))?;
-
-        for st in &vj.stop_times {
-            st_wtr
-                .serialize(StopTime {
-                    stop_id: stop_points[st.stop_point_idx].id.clone(),
-                    trip_id: vj.id.clone(),
-                    stop_sequence: st.sequence,
-                    arrival_time: st.arrival_time,
-                    departure_time: st.departure_time,
-                    boarding_duration: st.boarding_duration,
-                    alighting_duration: st.alighting_duration,
-                    pickup_type: st.pickup_type,
-                    dropoff_type: st.dropoff_type,
-                    datetime_estimated: st.datetime_estimated,
-                    local_zone_id: st.local_zone_id,
-                    // TODO: Add headsign and stop_time_ids
-                })
-                .with_context(ctx_from_path!(st_wtr))?;
-        }
+        write_stop_times(&mut st_wtr, &stop_times_path, vj, &vj.stop_times, stop_points)?;
     }
     st_wtr
         .flush()
@@ -153,10 +176,14 @@ pub fn write_stops(
     path: &path::Path,
     stop_points: &CollectionWithId<StopPoint>,
     stop_areas: &CollectionWithId<StopArea>,
+    generated_stop_areas: GeneratedStopAreas,
 ) -> Result<()> {
     info!("Writing stops.txt");
     let path = path.join("stops.txt");
     let mut wtr = csv::Writer::from_path(&path).with_context(ctx_from_path!(path))?;
+    let is_written = |sa: &StopArea| {
+        !sa.generated || generated_stop_areas == GeneratedStopAreas::Materialize
+    };
     for (_, st) in stop_points.iter() {
         wtr.serialize(Stop {
             id: st.id.clone(),
@@ -166,15 +193,18 @@ pub fn write_stops(
             lon: st.coord.lon,
             fare_zone_id: st.fare_zone_id.clone(),
             location_type: 0,
-            parent_station: stop_areas.get(&st.stop_area_id).map(|sa| sa.id.clone()),
-            timezone: st.timezone.clone(),
+            parent_station: stop_areas
+                .get(&st.stop_area_id)
+                .filter(|sa| is_written(sa))
+                .map(|sa| sa.id.clone()),
+            timezone: st.timezone,
             equipment_id: st.equipment_id.clone(),
             geometry_id: st.geometry_id.clone(),
         }).with_context(ctx_from_path!(path))?;
     }
 
     for (_, sa) in stop_areas.iter() {
-        if !sa.id.starts_with("Navitia:") {
+        if is_written(sa) {
             wtr.serialize(Stop {
                 id: sa.id.clone(),
                 visible: sa.visible,
@@ -184,7 +214,7 @@ pub fn write_stops(
                 fare_zone_id: None,
                 location_type: 1,
                 parent_station: None,
-                timezone: sa.timezone.clone(),
+                timezone: sa.timezone,
                 equipment_id: sa.equipment_id.clone(),
                 geometry_id: sa.geometry_id.clone(),
             }).with_context(ctx_from_path!(path))?;
@@ -217,7 +247,36 @@ where
     Ok(())
 }
 
-pub fn write_comments(path: &path::Path, collections: &Collections) -> Result<()> {
+fn write_stop_time_comment_links<W>(
+    wtr: &mut csv::Writer<W>,
+    stop_times: &[&StopTimeExtras],
+    comments: &CollectionWithId<Comment>,
+    path: &path::Path,
+) -> Result<()>
+where
+    W: ::std::io::Write,
+{
+    for st in stop_times {
+        if let Some(ref id) = st.id {
+            for comment in comments.iter_from(&st.comment_links) {
+                wtr.serialize(CommentLink {
+                    object_id: id.to_string(),
+                    object_type: ObjectType::StopTime,
+                    comment_id: comment.id.to_string(),
+                }).with_context(ctx_from_path!(path))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the comments, and the comment links of the collections and of
+/// `stop_times`.
+pub fn write_comments(
+    path: &path::Path,
+    collections: &Collections,
+    stop_times: &[&StopTimeExtras],
+) -> Result<()> {
     info!("Writing comments.txt and comment_links.txt");
 
     let comments_path = path.join("comments.txt");
@@ -263,7 +322,36 @@ pub fn write_comments(path: &path::Path, collections: &Collections) -> Result<()
         &collections.comments,
         &comment_links_path,
     )?;
-    // TODO: add stop_times and line_groups
+    write_comment_links_from_collection_with_id(
+        &mut cl_wtr,
+        &collections.networks,
+        &collections.comments,
+        &comment_links_path,
+    )?;
+    write_comment_links_from_collection_with_id(
+        &mut cl_wtr,
+        &collections.companies,
+        &collections.comments,
+        &comment_links_path,
+    )?;
+    write_comment_links_from_collection_with_id(
+        &mut cl_wtr,
+        &collections.calendars,
+        &collections.comments,
+        &comment_links_path,
+    )?;
+    write_comment_links_from_collection_with_id(
+        &mut cl_wtr,
+        &collections.line_groups,
+        &collections.comments,
+        &comment_links_path,
+    )?;
+    write_stop_time_comment_links(
+        &mut cl_wtr,
+        stop_times,
+        &collections.comments,
+        &comment_links_path,
+    )?;
 
     cl_wtr
         .flush()
@@ -296,7 +384,35 @@ where
     Ok(())
 }
 
-pub fn write_codes(path: &path::Path, collections: &Collections) -> Result<()> {
+fn write_stop_time_codes<W>(
+    wtr: &mut csv::Writer<W>,
+    stop_times: &[&StopTimeExtras],
+    path: &path::Path,
+) -> Result<()>
+where
+    W: ::std::io::Write,
+{
+    for st in stop_times {
+        if let Some(ref id) = st.id {
+            for c in &st.codes {
+                wtr.serialize(Code {
+                    object_id: id.to_string(),
+                    object_type: ObjectType::StopTime,
+                    object_system: c.0.clone(),
+                    object_code: c.1.clone(),
+                }).with_context(ctx_from_path!(path))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the codes of the collections and of `stop_times`.
+pub fn write_codes(
+    path: &path::Path,
+    collections: &Collections,
+    stop_times: &[&StopTimeExtras],
+) -> Result<()> {
     info!("Writing object_codes.txt");
 
     let path = path.join("object_codes.txt");
@@ -308,6 +424,12 @@ pub fn write_codes(path: &path::Path, collections: &Collections) -> Result<()> {
     write_codes_from_collection_with_id(&mut wtr, &collections.lines, &path)?;
     write_codes_from_collection_with_id(&mut wtr, &collections.routes, &path)?;
     write_codes_from_collection_with_id(&mut wtr, &collections.vehicle_journeys, &path)?;
+    write_codes_from_collection_with_id(&mut wtr, &collections.companies, &path)?;
+    write_codes_from_collection_with_id(&mut wtr, &collections.calendars, &path)?;
+    write_codes_from_collection_with_id(&mut wtr, &collections.line_groups, &path)?;
+    write_codes_from_collection_with_id(&mut wtr, &collections.contributors, &path)?;
+    write_codes_from_collection_with_id(&mut wtr, &collections.datasets, &path)?;
+    write_stop_time_codes(&mut wtr, stop_times, &path)?;
 
     wtr.flush().with_context(ctx_from_path!(path))?;
 
@@ -337,7 +459,36 @@ where
     Ok(())
 }
 
-pub fn write_object_properties(path: &path::Path, collections: &Collections) -> Result<()> {
+fn write_stop_time_object_properties<W>(
+    wtr: &mut csv::Writer<W>,
+    stop_times: &[&StopTimeExtras],
+    path: &path::Path,
+) -> Result<()>
+where
+    W: ::std::io::Write,
+{
+    for st in stop_times {
+        if let Some(ref id) = st.id {
+            for c in &st.object_properties {
+                wtr.serialize(ObjectProperty {
+                    object_id: id.to_string(),
+                    object_type: ObjectType::StopTime,
+                    object_property_name: c.0.clone(),
+                    object_property_value: c.1.clone(),
+                }).with_context(ctx_from_path!(path))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the object properties of the collections and of
+/// `stop_times`.
+pub fn write_object_properties(
+    path: &path::Path,
+    collections: &Collections,
+    stop_times: &[&StopTimeExtras],
+) -> Result<()> {
     info!("Writing object_properties.txt");
 
     let path = path.join("object_properties.txt");
@@ -352,8 +503,65 @@ pub fn write_object_properties(path: &path::Path, collections: &Collections) ->
         &collections.vehicle_journeys,
         &path,
     )?;
+    write_object_properties_from_collection_with_id(&mut wtr, &collections.networks, &path)?;
+    write_object_properties_from_collection_with_id(&mut wtr, &collections.companies, &path)?;
+    write_object_properties_from_collection_with_id(&mut wtr, &collections.calendars, &path)?;
+    write_object_properties_from_collection_with_id(&mut wtr, &collections.line_groups, &path)?;
+    write_object_properties_from_collection_with_id(&mut wtr, &collections.contributors, &path)?;
+    write_object_properties_from_collection_with_id(&mut wtr, &collections.datasets, &path)?;
+    write_stop_time_object_properties(&mut wtr, stop_times, &path)?;
+
+    wtr.flush().with_context(ctx_from_path!(path))?;
+
+    Ok(())
+}
+
+fn append_extra_columns(path: &path::Path, extra_columns: &ExtraColumns) -> Result<()> {
+    let mut rdr = csv::Reader::from_path(path).with_context(ctx_from_path!(path))?;
+    let mut headers = rdr.headers().with_context(ctx_from_path!(path))?.clone();
+    let id_position = match headers.iter().position(|h| h == extra_columns.id_column) {
+        Some(id_position) => id_position,
+        // nothing has been written, so there is nothing to complete
+        None => return Ok(()),
+    };
+    let records = rdr.records()
+        .collect::<::std::result::Result<Vec<_>, _>>()
+        .with_context(ctx_from_path!(path))?;
+    let empty_values = vec![String::new(); extra_columns.headers.len()];
 
+    let mut wtr = csv::Writer::from_path(path).with_context(ctx_from_path!(path))?;
+    headers.extend(&extra_columns.headers);
+    wtr.write_record(&headers)
+        .with_context(ctx_from_path!(path))?;
+    for mut record in records {
+        let values = record
+            .get(id_position)
+            .and_then(|id| extra_columns.values.get(id))
+            .unwrap_or(&empty_values);
+        record.extend(values);
+        wtr.write_record(&record)
+            .with_context(ctx_from_path!(path))?;
+    }
     wtr.flush().with_context(ctx_from_path!(path))?;
 
     Ok(())
 }
+
+pub fn write_extra_data(path: &path::Path, extra_data: &ExtraData) -> Result<()> {
+    for (file, content) in &extra_data.files {
+        info!("Writing unknown file {}", file);
+        let path = path.join(file);
+        fs::write(&path, content).with_context(ctx_from_path!(path))?;
+    }
+    for (file, extra_columns) in &extra_data.columns {
+        let path = path.join(file);
+        if !path.exists() {
+            warn!("{} not written, skipping its unknown columns", file);
+            continue;
+        }
+        info!("Writing unknown columns of {}", file);
+        append_extra_columns(&path, extra_columns)?;
+    }
+
+    Ok(())
+}
@@ -14,9 +14,11 @@
 // along with this program.  If not, see
 // <http://www.gnu.org/licenses/>.
 
-use collection::{Id, Idx};
+use collection::{CollectionWithId, Id, Idx};
 use utils::*;
+use validity_pattern::ValidityPattern;
 use chrono;
+use chrono_tz;
 use std::str::FromStr;
 
 pub trait AddPrefix {
@@ -35,6 +37,10 @@ pub enum ObjectType {
     VehicleJourney,
     StopTime,
     LineGroup,
+    Company,
+    Calendar,
+    Contributor,
+    Dataset,
 }
 
 pub trait GetObjectType {
@@ -52,6 +58,10 @@ impl ObjectType {
             ObjectType::VehicleJourney => "trip",
             ObjectType::StopTime => "stop_time",
             ObjectType::LineGroup => "line_group",
+            ObjectType::Company => "company",
+            ObjectType::Calendar => "calendar",
+            ObjectType::Contributor => "contributor",
+            ObjectType::Dataset => "dataset",
         }
     }
 }
@@ -124,6 +134,10 @@ pub struct Contributor {
     pub license: Option<String>,
     #[serde(rename = "contributor_website")]
     pub website: Option<String>,
+    #[serde(skip)]
+    pub codes: KeysValues,
+    #[serde(skip)]
+    pub object_properties: KeysValues,
 }
 impl Id<Contributor> for Contributor {
     fn id(&self) -> &str {
@@ -143,9 +157,19 @@ impl Default for Contributor {
             name: "Default contributor".to_string(),
             license: Some("Unknown license".to_string()),
             website: None,
+            codes: KeysValues::default(),
+            object_properties: KeysValues::default(),
         }
     }
 }
+impl_codes!(Contributor);
+impl_object_properties!(Contributor);
+
+impl GetObjectType for Contributor {
+    fn get_object_type() -> ObjectType {
+        ObjectType::Contributor
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub enum DatasetType {
@@ -176,6 +200,10 @@ pub struct Dataset {
     pub desc: Option<String>,
     #[serde(rename = "dataset_system")]
     pub system: Option<String>,
+    #[serde(skip)]
+    pub codes: KeysValues,
+    #[serde(skip)]
+    pub object_properties: KeysValues,
 }
 impl Default for Dataset {
     fn default() -> Dataset {
@@ -195,6 +223,8 @@ impl Default for Dataset {
             extrapolation: false,
             desc: None,
             system: None,
+            codes: KeysValues::default(),
+            object_properties: KeysValues::default(),
         }
     }
 }
@@ -214,6 +244,14 @@ impl AddPrefix for Dataset {
         self.contributor_id = prefix.to_string() + &self.contributor_id;
     }
 }
+impl_codes!(Dataset);
+impl_object_properties!(Dataset);
+
+impl GetObjectType for Dataset {
+    fn get_object_type() -> ObjectType {
+        ObjectType::Dataset
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct CommercialMode {
@@ -252,8 +290,12 @@ pub struct Network {
     pub url: Option<String>,
     #[serde(skip)]
     pub codes: KeysValues,
-    #[serde(rename = "network_timezone")]
-    pub timezone: Option<String>,
+    #[serde(skip)]
+    pub object_properties: KeysValues,
+    #[serde(skip)]
+    pub comment_links: CommentLinksT,
+    #[serde(rename = "network_timezone", default, deserialize_with = "de_lenient_option")]
+    pub timezone: Option<Timezone>,
     #[serde(rename = "network_lang")]
     pub lang: Option<String>,
     #[serde(rename = "network_phone")]
@@ -269,6 +311,8 @@ impl Id<Network> for Network {
     }
 }
 impl_codes!(Network);
+impl_object_properties!(Network);
+impl_comment_links!(Network);
 
 impl GetObjectType for Network {
     fn get_object_type() -> ObjectType {
@@ -436,6 +480,43 @@ impl GetObjectType for Line {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct LineGroup {
+    #[serde(rename = "line_group_id")]
+    pub id: String,
+    #[serde(rename = "line_group_name")]
+    pub name: String,
+    pub main_line_id: String,
+    #[serde(skip)]
+    pub codes: KeysValues,
+    #[serde(skip)]
+    pub object_properties: KeysValues,
+    #[serde(skip)]
+    pub comment_links: CommentLinksT,
+}
+
+impl Id<LineGroup> for LineGroup {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+impl_codes!(LineGroup);
+impl_object_properties!(LineGroup);
+impl_comment_links!(LineGroup);
+
+impl GetObjectType for LineGroup {
+    fn get_object_type() -> ObjectType {
+        ObjectType::LineGroup
+    }
+}
+
+/// A line of a line group.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct LineGroupLink {
+    pub line_group_id: String,
+    pub line_id: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Route {
     #[serde(rename = "route_id")]
@@ -479,7 +560,7 @@ impl GetObjectType for Route {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct VehicleJourney {
     #[serde(rename = "trip_id")]
     pub id: String,
@@ -537,7 +618,9 @@ impl GetObjectType for VehicleJourney {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+/// A time of a service day, that can be greater than 24:00:00 for
+/// the vehicle journeys running after midnight.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Default, Hash)]
 pub struct Time(u32);
 impl Time {
     pub fn new(h: u32, m: u32, s: u32) -> Time {
@@ -552,7 +635,109 @@ impl Time {
     pub fn seconds(&self) -> u32 {
         self.0 % 60
     }
+    /// The number of seconds since the midnight of the service day.
+    pub fn total_seconds(&self) -> u32 {
+        self.0
+    }
+    /// Splits the time into a number of days after the service day
+    /// and a time of that day: 24:30:00 gives (1, 00:30:00).
+    pub fn split_days(&self) -> (u32, Time) {
+        (self.0 / SECONDS_PER_DAY, Time(self.0 % SECONDS_PER_DAY))
+    }
+    /// The time `days` days after `self`.
+    pub fn checked_add_days(&self, days: u32) -> Option<Time> {
+        days.checked_mul(SECONDS_PER_DAY)
+            .and_then(|seconds| self.0.checked_add(seconds))
+            .map(Time)
+    }
+    /// `self` shifted by `offset`, or `None` if the result is before
+    /// the midnight of the service day or overflows.
+    pub fn checked_add(&self, offset: TimeOffset) -> Option<Time> {
+        let seconds = i64::from(self.0).checked_add(offset.0)?;
+        if seconds < 0 || seconds > i64::from(u32::MAX) {
+            None
+        } else {
+            Some(Time(seconds as u32))
+        }
+    }
+    /// `self` shifted back by `offset` (see `checked_add`).
+    pub fn checked_sub(&self, offset: TimeOffset) -> Option<Time> {
+        self.checked_add(-offset)
+    }
+    /// The time `duration` after the midnight of the service day, or
+    /// `None` if `duration` is negative or too long.
+    pub fn from_duration(duration: chrono::Duration) -> Option<Time> {
+        Time(0).checked_add(TimeOffset::from(duration))
+    }
+}
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+/// Shifting a `Time` before midnight or after `u32::MAX` seconds
+/// panics: use `checked_add` to handle it.
+impl std::ops::Add<TimeOffset> for Time {
+    type Output = Time;
+    fn add(self, offset: TimeOffset) -> Time {
+        self.checked_add(offset)
+            .unwrap_or_else(|| panic!("{:?} + {:?} is out of range", self, offset))
+    }
+}
+impl std::ops::Sub<TimeOffset> for Time {
+    type Output = Time;
+    fn sub(self, offset: TimeOffset) -> Time {
+        self.checked_sub(offset)
+            .unwrap_or_else(|| panic!("{:?} - {:?} is out of range", self, offset))
+    }
+}
+impl std::ops::Sub for Time {
+    type Output = TimeOffset;
+    fn sub(self, other: Time) -> TimeOffset {
+        TimeOffset(i64::from(self.0) - i64::from(other.0))
+    }
 }
+impl From<Time> for chrono::Duration {
+    fn from(time: Time) -> chrono::Duration {
+        chrono::Duration::seconds(i64::from(time.0))
+    }
+}
+
+#[derive(Debug)]
+pub enum TimeError {
+    WrongFormat,
+    WrongValue,
+}
+impl std::fmt::Display for TimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            TimeError::WrongFormat => f.write_str("format should be HH:MM:SS"),
+            TimeError::WrongValue => f.write_str("minutes and seconds should be below 60"),
+        }
+    }
+}
+impl Error for TimeError {}
+
+impl FromStr for Time {
+    type Err = TimeError;
+    fn from_str(time: &str) -> Result<Self, Self::Err> {
+        let mut t = time.split(':');
+        let (hours, minutes, seconds) = match (t.next(), t.next(), t.next(), t.next()) {
+            (Some(h), Some(m), Some(s), None) => (h, m, s),
+            _ => return Err(TimeError::WrongFormat),
+        };
+        let hours: u32 = hours.parse().map_err(|_| TimeError::WrongFormat)?;
+        let minutes: u32 = minutes.parse().map_err(|_| TimeError::WrongFormat)?;
+        let seconds: u32 = seconds.parse().map_err(|_| TimeError::WrongFormat)?;
+        if minutes >= 60 || seconds >= 60 {
+            return Err(TimeError::WrongValue);
+        }
+        hours
+            .checked_mul(60 * 60)
+            .and_then(|h| h.checked_add(minutes * 60 + seconds))
+            .map(Time)
+            .ok_or(TimeError::WrongValue)
+    }
+}
+
 impl ::serde::Serialize for Time {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -583,16 +768,7 @@ impl<'de> ::serde::Deserialize<'de> for Time {
                 formatter.write_str("a time in the format HH:MM:SS")
             }
             fn visit_str<E: de::Error>(self, time: &str) -> Result<Time, E> {
-                let mut t = time.split(':');
-                let (hours, minutes, seconds) = match (t.next(), t.next(), t.next(), t.next()) {
-                    (Some(h), Some(m), Some(s), None) => (h, m, s),
-                    _ => return Err(Error::custom("format should be HH:MM:SS")),
-                };
-                let hours: u32 = hours.parse().map_err(Error::custom)?;
-                let minutes: u32 = minutes.parse().map_err(Error::custom)?;
-                let seconds: u32 = seconds.parse().map_err(Error::custom)?;
-                // TODO: check 0 <= minutes, seconds < 60?
-                Ok(Time::new(hours, minutes, seconds))
+                time.parse().map_err(Error::custom)
             }
         }
 
@@ -600,7 +776,48 @@ impl<'de> ::serde::Deserialize<'de> for Time {
     }
 }
 
-#[derive(Debug, PartialEq)]
+/// A signed number of seconds by which a `Time` can be shifted, for
+/// example when changing its timezone.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Default, Hash)]
+pub struct TimeOffset(i64);
+impl TimeOffset {
+    pub fn seconds(seconds: i64) -> TimeOffset {
+        TimeOffset(seconds)
+    }
+    pub fn num_seconds(&self) -> i64 {
+        self.0
+    }
+}
+impl std::ops::Neg for TimeOffset {
+    type Output = TimeOffset;
+    fn neg(self) -> TimeOffset {
+        TimeOffset(-self.0)
+    }
+}
+impl std::ops::Add for TimeOffset {
+    type Output = TimeOffset;
+    fn add(self, other: TimeOffset) -> TimeOffset {
+        TimeOffset(self.0 + other.0)
+    }
+}
+impl std::ops::Sub for TimeOffset {
+    type Output = TimeOffset;
+    fn sub(self, other: TimeOffset) -> TimeOffset {
+        TimeOffset(self.0 - other.0)
+    }
+}
+impl From<chrono::Duration> for TimeOffset {
+    fn from(duration: chrono::Duration) -> TimeOffset {
+        TimeOffset(duration.num_seconds())
+    }
+}
+impl From<TimeOffset> for chrono::Duration {
+    fn from(offset: TimeOffset) -> chrono::Duration {
+        chrono::Duration::seconds(offset.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct StopTime {
     pub stop_point_idx: Idx<StopPoint>,
     pub sequence: u32,
@@ -612,8 +829,40 @@ pub struct StopTime {
     pub dropoff_type: u8,
     pub datetime_estimated: bool,
     pub local_zone_id: Option<u16>,
+    /// The id, codes, object properties and comments of the stop time,
+    /// boxed as almost no stop time has them.
+    pub extras: Option<Box<StopTimeExtras>>,
+}
+impl StopTime {
+    /// The `stop_time_id` of the stop time, if any.
+    pub fn id(&self) -> Option<&str> {
+        self.extras.as_ref().and_then(|e| e.id.as_deref())
+    }
+
+    /// The comments of the stop time.
+    pub fn comment_links(&self) -> &[Idx<Comment>] {
+        self.extras.as_ref().map_or(&[], |e| &e.comment_links)
+    }
+
+    /// The extras of the stop time, empty ones being created if needed.
+    pub fn extras_mut(&mut self) -> &mut StopTimeExtras {
+        self.extras.get_or_insert_with(Default::default)
+    }
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct StopTimeExtras {
+    /// The `stop_time_id`, only needed by the codes, object properties
+    /// and comments of the stop time.
+    pub id: Option<String>,
+    pub codes: KeysValues,
+    pub object_properties: KeysValues,
+    pub comment_links: CommentLinksT,
+}
+impl_codes!(StopTimeExtras);
+impl_object_properties!(StopTimeExtras);
+impl_comment_links!(StopTimeExtras);
+
 impl GetObjectType for StopTime {
     fn get_object_type() -> ObjectType {
         ObjectType::StopTime
@@ -626,6 +875,24 @@ pub struct Coord {
     pub lat: f64,
 }
 
+impl Coord {
+    /// Whether the coordinate is (0, 0), the one of the stops whose
+    /// coordinate is missing.
+    pub fn is_unknown(&self) -> bool {
+        self.lon == 0. && self.lat == 0.
+    }
+
+    /// The distance to `other` in meters, on a spherical earth.
+    pub fn distance_to(&self, other: &Coord) -> f64 {
+        const EARTH_RADIUS: f64 = 6_371_000.;
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let half_dlat = (lat2 - lat1) / 2.;
+        let half_dlon = (other.lon - self.lon).to_radians() / 2.;
+        let a = half_dlat.sin().powi(2) + lat1.cos() * lat2.cos() * half_dlon.sin().powi(2);
+        2. * EARTH_RADIUS * a.sqrt().asin()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct StopArea {
     pub id: String,
@@ -638,9 +905,13 @@ pub struct StopArea {
     pub comment_links: CommentLinksT,
     pub visible: bool,
     pub coord: Coord,
-    pub timezone: Option<String>,
+    pub timezone: Option<Timezone>,
     pub geometry_id: Option<String>,
     pub equipment_id: Option<String>,
+    /// Whether this stop area does not come from the input, but was
+    /// generated for a stop point without parent station.
+    #[serde(default)]
+    pub generated: bool,
 }
 impl Id<StopArea> for StopArea {
     fn id(&self) -> &str {
@@ -652,6 +923,13 @@ impl AddPrefix for StopArea {
         self.id = prefix.to_string() + &self.id;
     }
 }
+impl StopArea {
+    /// The id of the stop area generated for the stop point
+    /// `stop_point_id`.
+    pub fn generated_id(stop_point_id: &str) -> String {
+        format!("Navitia:{}", stop_point_id)
+    }
+}
 impl_codes!(StopArea);
 impl_object_properties!(StopArea);
 impl_comment_links!(StopArea);
@@ -675,7 +953,7 @@ pub struct StopPoint {
     pub visible: bool,
     pub coord: Coord,
     pub stop_area_id: String,
-    pub timezone: Option<String>,
+    pub timezone: Option<Timezone>,
     pub geometry_id: Option<String>,
     pub equipment_id: Option<String>,
     pub fare_zone_id: Option<String>,
@@ -707,6 +985,8 @@ impl GetObjectType for StopPoint {
 }
 
 pub type Date = chrono::NaiveDate;
+/// An IANA timezone, as "Europe/Paris".
+pub type Timezone = chrono_tz::Tz;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum ExceptionType {
@@ -742,6 +1022,12 @@ pub struct Calendar {
     pub end_date: Date,
     #[serde(skip)]
     pub calendar_dates: CalendarDates,
+    #[serde(skip)]
+    pub codes: KeysValues,
+    #[serde(skip)]
+    pub object_properties: KeysValues,
+    #[serde(skip)]
+    pub comment_links: CommentLinksT,
 }
 
 impl Id<Calendar> for Calendar {
@@ -749,6 +1035,174 @@ impl Id<Calendar> for Calendar {
         &self.id
     }
 }
+impl Calendar {
+    /// Whether the calendar runs on `weekday`, before the exceptions.
+    pub fn runs_on_weekday(&self, weekday: chrono::Weekday) -> bool {
+        use chrono::Weekday::*;
+        match weekday {
+            Mon => self.monday,
+            Tue => self.tuesday,
+            Wed => self.wednesday,
+            Thu => self.thursday,
+            Fri => self.friday,
+            Sat => self.saturday,
+            Sun => self.sunday,
+        }
+    }
+
+    /// Whether the calendar runs on `date`.  When several exceptions
+    /// are given for a date, the last one applies, as in
+    /// `validity_pattern`.
+    pub fn is_active(&self, date: Date) -> bool {
+        use chrono::Datelike;
+        match self.calendar_dates.iter().rev().find(|&&(d, _)| d == date) {
+            Some(&(_, ExceptionType::Add)) => true,
+            Some(&(_, ExceptionType::Remove)) => false,
+            None => {
+                self.start_date <= date && date <= self.end_date
+                    && self.runs_on_weekday(date.weekday())
+            }
+        }
+    }
+
+    /// The days the calendar runs.
+    pub fn validity_pattern(&self) -> ValidityPattern {
+        use chrono::Datelike;
+        let mut vp = ValidityPattern::new(self.start_date, self.end_date);
+        let end_date = self.end_date;
+        for date in self.start_date.iter_days().take_while(|&date| date <= end_date) {
+            if self.runs_on_weekday(date.weekday()) {
+                vp.set(date, true);
+            }
+        }
+        for &(date, ref exception) in &self.calendar_dates {
+            vp.set(date, *exception == ExceptionType::Add);
+        }
+        vp
+    }
+
+    /// The days the calendar runs, in order.
+    pub fn dates(&self) -> Vec<Date> {
+        self.validity_pattern().dates()
+    }
+
+    /// A calendar running exactly on the days of `vp`, with the
+    /// weekdays and period giving as few exceptions as possible.  The
+    /// period starts on the first active day of a weekday and ends on
+    /// the last active day of a weekday, the active days outside of it
+    /// being added.
+    pub fn from_validity_pattern(id: String, vp: &ValidityPattern) -> Calendar {
+        use chrono::Datelike;
+        let weekday = |date: Date| date.weekday().num_days_from_monday() as usize;
+        let dates = vp.dates();
+        // by weekday, the number of days of the period and of active days
+        let counts = |start: Date, end: Date| {
+            let nb = (end - start).num_days() + 1;
+            let mut nb_days = [0; 7];
+            let mut nb_active_days = [0; 7];
+            for i in 0..7 {
+                nb_days[(weekday(start) + i) % 7] = (nb + 6 - i as i64) / 7;
+            }
+            for &date in dates.iter().filter(|&&date| start <= date && date <= end) {
+                nb_active_days[weekday(date)] += 1;
+            }
+            (nb_days, nb_active_days)
+        };
+        // the removed inactive days, or the added active days, by
+        // weekday, and the added active days outside of the period
+        let nb_exceptions = |start: Date, end: Date| {
+            let (nb_days, nb_active_days) = counts(start, end);
+            let outside = dates.len() as i64 - nb_active_days.iter().sum::<i64>();
+            (0..7)
+                .map(|w| ::std::cmp::min(nb_days[w] - nb_active_days[w], nb_active_days[w]))
+                .sum::<i64>() + outside
+        };
+        let (start_date, end_date) = match (dates.first(), dates.last()) {
+            (Some(&first), Some(&last)) => {
+                let mut firsts = [None; 7];
+                let mut lasts = [None; 7];
+                for &date in &dates {
+                    firsts[weekday(date)].get_or_insert(date);
+                    lasts[weekday(date)] = Some(date);
+                }
+                let mut best = ((first, last), nb_exceptions(first, last));
+                for &start in firsts.iter().filter_map(|d| d.as_ref()) {
+                    for &end in lasts.iter().filter_map(|d| d.as_ref()) {
+                        if start > end {
+                            continue;
+                        }
+                        let nb = nb_exceptions(start, end);
+                        if nb < best.1 {
+                            best = ((start, end), nb);
+                        }
+                    }
+                }
+                best.0
+            }
+            _ => (vp.start_date(), vp.end_date()),
+        };
+        let (nb_days, nb_active_days) = counts(start_date, end_date);
+        let runs = |w: usize| nb_days[w] - nb_active_days[w] < nb_active_days[w];
+        let mut calendar = Calendar {
+            id,
+            monday: runs(0),
+            tuesday: runs(1),
+            wednesday: runs(2),
+            thursday: runs(3),
+            friday: runs(4),
+            saturday: runs(5),
+            sunday: runs(6),
+            start_date,
+            end_date,
+            calendar_dates: CalendarDates::default(),
+            codes: KeysValues::default(),
+            object_properties: KeysValues::default(),
+            comment_links: CommentLinksT::default(),
+        };
+        for &date in dates.iter().filter(|&&date| date < start_date) {
+            calendar.calendar_dates.push((date, ExceptionType::Add));
+        }
+        for date in start_date.iter_days().take_while(|&date| date <= end_date) {
+            match (calendar.runs_on_weekday(date.weekday()), vp.is_active(date)) {
+                (true, false) => calendar.calendar_dates.push((date, ExceptionType::Remove)),
+                (false, true) => calendar.calendar_dates.push((date, ExceptionType::Add)),
+                _ => (),
+            }
+        }
+        for &date in dates.iter().filter(|&&date| date > end_date) {
+            calendar.calendar_dates.push((date, ExceptionType::Add));
+        }
+        calendar
+    }
+
+    /// A calendar running on the days of `self` or `other`.
+    pub fn union(&self, other: &Calendar, id: String) -> Calendar {
+        let vp = self.validity_pattern().union(&other.validity_pattern());
+        Calendar::from_validity_pattern(id, &vp)
+    }
+
+    /// A calendar running on the days of both `self` and `other`.
+    pub fn intersection(&self, other: &Calendar, id: String) -> Calendar {
+        let vp = self.validity_pattern()
+            .intersection(&other.validity_pattern());
+        Calendar::from_validity_pattern(id, &vp)
+    }
+
+    /// A calendar running on the days of `self` that are not in `other`.
+    pub fn difference(&self, other: &Calendar, id: String) -> Calendar {
+        let vp = self.validity_pattern().difference(&other.validity_pattern());
+        Calendar::from_validity_pattern(id, &vp)
+    }
+}
+impl_codes!(Calendar);
+impl_object_properties!(Calendar);
+impl_comment_links!(Calendar);
+
+impl GetObjectType for Calendar {
+    fn get_object_type() -> ObjectType {
+        ObjectType::Calendar
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct Company {
@@ -764,6 +1218,12 @@ pub struct Company {
     pub mail: Option<String>,
     #[serde(rename = "company_phone")]
     pub phone: Option<String>,
+    #[serde(skip)]
+    pub codes: KeysValues,
+    #[serde(skip)]
+    pub object_properties: KeysValues,
+    #[serde(skip)]
+    pub comment_links: CommentLinksT,
 }
 
 impl Id<Company> for Company {
@@ -771,6 +1231,15 @@ impl Id<Company> for Company {
         &self.id
     }
 }
+impl_codes!(Company);
+impl_object_properties!(Company);
+impl_comment_links!(Company);
+
+impl GetObjectType for Company {
+    fn get_object_type() -> ObjectType {
+        ObjectType::Company
+    }
+}
 
 impl AddPrefix for Company {
     fn add_prefix(&mut self, prefix: &str) {
@@ -791,7 +1260,7 @@ pub enum CommentType {
 pub struct Comment {
     #[serde(rename = "comment_id")]
     pub id: String,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub comment_type: CommentType,
     #[serde(rename = "comment_label")]
     pub label: Option<String>,
@@ -823,25 +1292,25 @@ pub enum Availability {
 pub struct Equipment {
     #[serde(rename = "equipment_id")]
     pub id: String,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub wheelchair_boarding: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub sheltered: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub elevator: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub escalator: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub bike_accepted: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub bike_depot: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub visual_announcement: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub audible_announcement: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub appropriate_escort: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub appropriate_signage: Availability,
 }
 
@@ -876,21 +1345,21 @@ pub enum TransportType {
 pub struct TripProperty {
     #[serde(rename = "trip_property_id")]
     pub id: String,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub wheelchair_accessible: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub bike_accepted: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub air_conditioned: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub visual_announcement: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub audible_announcement: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub appropriate_escort: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub appropriate_signage: Availability,
-    #[serde(deserialize_with = "de_with_empty_default")]
+    #[serde(deserialize_with = "de_with_empty_default", serialize_with = "ser_as_some")]
     pub school_vehicle_type: TransportType,
 }
 
@@ -914,6 +1383,84 @@ impl Id<Geometry> for Geometry {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct GridCalendar {
+    #[serde(rename = "grid_calendar_id")]
+    pub id: String,
+    pub name: String,
+    #[serde(deserialize_with = "de_from_u8", serialize_with = "ser_from_bool")]
+    pub monday: bool,
+    #[serde(deserialize_with = "de_from_u8", serialize_with = "ser_from_bool")]
+    pub tuesday: bool,
+    #[serde(deserialize_with = "de_from_u8", serialize_with = "ser_from_bool")]
+    pub wednesday: bool,
+    #[serde(deserialize_with = "de_from_u8", serialize_with = "ser_from_bool")]
+    pub thursday: bool,
+    #[serde(deserialize_with = "de_from_u8", serialize_with = "ser_from_bool")]
+    pub friday: bool,
+    #[serde(deserialize_with = "de_from_u8", serialize_with = "ser_from_bool")]
+    pub saturday: bool,
+    #[serde(deserialize_with = "de_from_u8", serialize_with = "ser_from_bool")]
+    pub sunday: bool,
+}
+
+impl Id<GridCalendar> for GridCalendar {
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct GridExceptionDate {
+    pub grid_calendar_id: String,
+    #[serde(deserialize_with = "de_from_date_string", serialize_with = "ser_from_naive_date")]
+    pub date: Date,
+    /// `true` if the date is added to the grid calendar, `false` if
+    /// it is removed.
+    #[serde(rename = "type", deserialize_with = "de_from_u8", serialize_with = "ser_from_bool")]
+    pub included: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct GridPeriod {
+    pub grid_calendar_id: String,
+    #[serde(deserialize_with = "de_from_date_string", serialize_with = "ser_from_naive_date")]
+    pub start_date: Date,
+    #[serde(deserialize_with = "de_from_date_string", serialize_with = "ser_from_naive_date")]
+    pub end_date: Date,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct GridRelCalendarLine {
+    pub grid_calendar_id: String,
+    /// Empty when the line is given by `line_external_code`.
+    pub line_id: String,
+    pub line_external_code: Option<String>,
+}
+
+impl GridRelCalendarLine {
+    /// The `line_id`, or the `line_external_code` when it is empty.
+    pub fn line_ref(&self) -> &str {
+        match self.line_external_code {
+            Some(ref code) if self.line_id.is_empty() => code,
+            _ => &self.line_id,
+        }
+    }
+
+    /// The line of the relation, given by its id, or when `line_id` is
+    /// empty by one of its codes, whatever its system.
+    pub fn line_idx(&self, lines: &CollectionWithId<Line>) -> Option<Idx<Line>> {
+        if !self.line_id.is_empty() {
+            return lines.get_idx(&self.line_id);
+        }
+        let code = self.line_external_code.as_ref()?;
+        lines
+            .iter()
+            .find(|&(_, line)| line.codes.iter().any(|(_, c)| c == code))
+            .map(|(idx, _)| idx)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
 pub struct AdminStation {
     pub admin_id: String,
@@ -1014,5 +1561,150 @@ mod tests {
         assert!(de("AA:00:00").is_err());
         assert!(de("00:AA:00").is_err());
         assert!(de("00:00:AA").is_err());
+        assert!(de("10:75:00").is_err());
+        assert!(de("10:00:60").is_err());
+        assert!(de("4294967295:00:00").is_err());
+    }
+
+    #[test]
+    fn time_arithmetic() {
+        let offset = |h: i64| TimeOffset::seconds(h * 60 * 60);
+
+        assert_eq!(Time::new(8, 30, 0) + offset(2), Time::new(10, 30, 0));
+        assert_eq!(Time::new(8, 30, 0) - offset(2), Time::new(6, 30, 0));
+        assert_eq!(Time::new(8, 30, 0) + -offset(2), Time::new(6, 30, 0));
+        assert_eq!(Time::new(1, 0, 0).checked_sub(offset(2)), None);
+        assert_eq!(Time::new(1, 0, 0) - Time::new(3, 0, 0), offset(-2));
+        assert_eq!(
+            Time::new(23, 0, 0).checked_add(offset(2)),
+            Some(Time::new(25, 0, 0))
+        );
+        assert_eq!(Time::new(24, 30, 0).split_days(), (1, Time::new(0, 30, 0)));
+        assert_eq!(Time::new(23, 59, 59).split_days(), (0, Time::new(23, 59, 59)));
+        assert_eq!(
+            Time::new(0, 30, 0).checked_add_days(1),
+            Some(Time::new(24, 30, 0))
+        );
+
+        let duration = chrono::Duration::minutes(90);
+        assert_eq!(Time::from_duration(duration), Some(Time::new(1, 30, 0)));
+        assert_eq!(chrono::Duration::from(Time::new(1, 30, 0)), duration);
+        assert_eq!(Time::from_duration(-duration), None);
+        assert_eq!(TimeOffset::from(-duration), TimeOffset::seconds(-5400));
+        assert_eq!(chrono::Duration::from(TimeOffset::seconds(-5400)), -duration);
+    }
+
+    fn calendar() -> Calendar {
+        let date = |d| chrono::NaiveDate::from_ymd_opt(2018, 1, d).unwrap();
+        Calendar {
+            id: "c".to_string(),
+            monday: true,
+            tuesday: false,
+            wednesday: false,
+            thursday: false,
+            friday: false,
+            saturday: true,
+            sunday: false,
+            start_date: date(1),
+            end_date: date(14),
+            calendar_dates: vec![
+                (date(6), ExceptionType::Remove),
+                (date(20), ExceptionType::Add),
+            ],
+            codes: KeysValues::default(),
+            object_properties: KeysValues::default(),
+            comment_links: CommentLinksT::default(),
+        }
+    }
+
+    #[test]
+    fn calendar_dates() {
+        let date = |d| chrono::NaiveDate::from_ymd_opt(2018, 1, d).unwrap();
+        let mut calendar = calendar();
+        // the last exception of a date applies
+        calendar.calendar_dates.push((date(8), ExceptionType::Add));
+        calendar.calendar_dates.push((date(8), ExceptionType::Remove));
+        calendar.calendar_dates.push((date(9), ExceptionType::Remove));
+        calendar.calendar_dates.push((date(9), ExceptionType::Add));
+        // 2018-01-01 is a monday
+        assert_eq!(
+            calendar.dates(),
+            vec![date(1), date(9), date(13), date(20)]
+        );
+        assert!(calendar.is_active(date(13)));
+        assert!(!calendar.is_active(date(6)));
+        assert!(!calendar.is_active(date(2)));
+        assert!(calendar.is_active(date(20)));
+        assert!(!calendar.is_active(date(27)));
+        for d in 1..31 {
+            assert_eq!(
+                calendar.is_active(date(d)),
+                calendar.validity_pattern().is_active(date(d))
+            );
+        }
+    }
+
+    #[test]
+    fn calendar_set_operations() {
+        let date = |d| chrono::NaiveDate::from_ymd_opt(2018, 1, d).unwrap();
+        let a = calendar();
+        let mut b = calendar();
+        b.monday = false;
+        b.sunday = true;
+        b.calendar_dates = vec![];
+        assert_eq!(b.dates(), vec![date(6), date(7), date(13), date(14)]);
+
+        let union = a.union(&b, "u".to_string());
+        assert_eq!(union.id, "u");
+        assert_eq!(
+            union.dates(),
+            vec![date(1), date(6), date(7), date(8), date(13), date(14), date(20)]
+        );
+        assert_eq!(a.intersection(&b, "i".to_string()).dates(), vec![date(13)]);
+        assert_eq!(
+            a.difference(&b, "d".to_string()).dates(),
+            vec![date(1), date(8), date(20)]
+        );
+    }
+
+    #[test]
+    fn calendar_from_validity_pattern() {
+        let date = |d| chrono::NaiveDate::from_ymd_opt(2018, 1, d).unwrap();
+        let mut vp = ValidityPattern::new(date(1), date(31));
+        // every working day of 3 weeks, but the 10th, and the 14th
+        for d in (1..6).chain(8..13).chain(15..20).filter(|&d| d != 10).chain(Some(14)) {
+            vp.set(date(d), true);
+        }
+        let calendar = Calendar::from_validity_pattern("c".to_string(), &vp);
+        assert_eq!(calendar.dates(), vp.dates());
+        assert_eq!((calendar.start_date, calendar.end_date), (date(1), date(19)));
+        assert!(calendar.monday && calendar.friday && !calendar.saturday && !calendar.sunday);
+        assert_eq!(
+            calendar.calendar_dates,
+            vec![(date(10), ExceptionType::Remove), (date(14), ExceptionType::Add)]
+        );
+    }
+
+    #[test]
+    fn calendar_from_validity_pattern_with_an_outlier() {
+        let date = |m, d| chrono::NaiveDate::from_ymd_opt(2018, m, d).unwrap();
+        // every monday of the first half of 2018, and christmas
+        let mut vp = ValidityPattern::new(date(1, 1), date(12, 31));
+        let mondays = date(1, 1).iter_days().step_by(7);
+        for monday in mondays.take_while(|&d| d < date(7, 1)) {
+            vp.set(monday, true);
+        }
+        vp.set(date(12, 25), true);
+        let calendar = Calendar::from_validity_pattern("c".to_string(), &vp);
+        assert_eq!(calendar.dates(), vp.dates());
+        assert_eq!((calendar.start_date, calendar.end_date), (date(1, 1), date(6, 25)));
+        assert!(calendar.monday && !calendar.tuesday && !calendar.sunday);
+        assert_eq!(calendar.calendar_dates, vec![(date(12, 25), ExceptionType::Add)]);
+    }
+
+    #[test]
+    fn stop_time_keeps_its_extras_out_of_line() {
+        // a stop time without extras only pays a pointer for them
+        assert!(::std::mem::size_of::<StopTime>() <= 48);
     }
 }
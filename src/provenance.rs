@@ -0,0 +1,153 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! The rows the objects of a model were read from.
+
+use std::collections::HashMap;
+use std::fmt;
+use collection::{CollectionWithId, Id, Idx};
+use error::{type_name, ErrorKind};
+use failure;
+
+/// A row of a CSV file.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub file: String,
+    /// The line of the row in the file, the header being line 1.
+    pub line: u64,
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} line {}", self.file, self.line)
+    }
+}
+
+/// The sources of the objects, recorded by the readers when asked to
+/// (see `ntfs::ReadOptions::track_provenance`).  The objects are
+/// identified by their type and id, so that the sources are kept by
+/// the editions that do not change the ids.
+#[derive(Debug, Default)]
+pub struct Provenance {
+    objects: HashMap<&'static str, HashMap<String, Source>>,
+    stop_times: HashMap<String, HashMap<u32, Source>>,
+    codes: HashMap<(&'static str, String, String, String), Source>,
+}
+
+impl Provenance {
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty() && self.stop_times.is_empty() && self.codes.is_empty()
+    }
+
+    /// The source of the object `idx` of `collection`.
+    pub fn get<T: Id<T>>(&self, collection: &CollectionWithId<T>, idx: Idx<T>) -> Option<&Source> {
+        self.get_by_id::<T>(collection[idx].id())
+    }
+
+    pub fn get_by_id<T>(&self, id: &str) -> Option<&Source> {
+        self.get_by_type(type_name::<T>(), id)
+    }
+
+    /// The source of an object of the type named `type_name`, as
+    /// "StopPoint".
+    pub fn get_by_type(&self, type_name: &str, id: &str) -> Option<&Source> {
+        self.objects.get(type_name).and_then(|sources| sources.get(id))
+    }
+
+    pub fn stop_time(&self, vehicle_journey_id: &str, sequence: u32) -> Option<&Source> {
+        self.stop_times
+            .get(vehicle_journey_id)
+            .and_then(|sources| sources.get(&sequence))
+    }
+
+    /// The source of the code `(system, code)` of the object `id` of
+    /// type `T`.
+    pub fn code<T>(&self, id: &str, system: &str, code: &str) -> Option<&Source> {
+        let key = (
+            type_name::<T>(),
+            id.to_string(),
+            system.to_string(),
+            code.to_string(),
+        );
+        self.codes.get(&key)
+    }
+
+    pub(crate) fn insert<T>(&mut self, id: &str, source: Source) {
+        self.objects
+            .entry(type_name::<T>())
+            .or_default()
+            .insert(id.to_string(), source);
+    }
+
+    pub(crate) fn insert_stop_time(&mut self, vj_id: &str, sequence: u32, source: Source) {
+        self.stop_times
+            .entry(vj_id.to_string())
+            .or_default()
+            .insert(sequence, source);
+    }
+
+    pub(crate) fn insert_code<T>(&mut self, id: &str, system: &str, code: &str, source: Source) {
+        let key = (
+            type_name::<T>(),
+            id.to_string(),
+            system.to_string(),
+            code.to_string(),
+        );
+        self.codes.insert(key, source);
+    }
+
+    /// Adds the sources of `other`, recorded while reading other files.
+    pub(crate) fn merge(&mut self, other: Provenance) {
+        for (type_name, sources) in other.objects {
+            self.objects.entry(type_name).or_default().extend(sources);
+        }
+        for (vj_id, sources) in other.stop_times {
+            self.stop_times.entry(vj_id).or_default().extend(sources);
+        }
+        self.codes.extend(other.codes);
+    }
+
+    /// Prefixes the ids of the objects of type `T`, as `add_prefix`.
+    pub(crate) fn add_prefix<T>(&mut self, prefix: &str) {
+        if let Some(sources) = self.objects.get_mut(type_name::<T>()) {
+            *sources = sources
+                .drain()
+                .map(|(id, source)| (format!("{}{}", prefix, id), source))
+                .collect();
+        }
+    }
+
+    /// Adds to `err` the source of the object it is about, if known.
+    pub(crate) fn annotate(&self, err: failure::Error) -> failure::Error {
+        let source = err.iter_chain()
+            .filter_map(|c| c.downcast_ref::<ErrorKind>())
+            .filter_map(|kind| match *kind {
+                ErrorKind::UnknownReference {
+                    from_type,
+                    ref from_id,
+                    ..
+                } => self.get_by_type(from_type, from_id),
+                ErrorKind::DuplicateId { collection, ref id } => self.get_by_type(collection, id),
+                _ => None,
+            })
+            .next()
+            .cloned();
+        match source {
+            Some(source) => err.context(source).into(),
+            None => err,
+        }
+    }
+}
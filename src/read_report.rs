@@ -0,0 +1,485 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! Strictness of the reading of the CSV files, and report of the rows
+//! and values it ignored.
+
+use std::cell::{Cell, RefCell};
+use std::{fmt, io, path};
+use csv;
+use failure::{Fail, ResultExt};
+use rayon::{self, ThreadPool};
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use collection::{Collection, CollectionWithId, Id};
+use error::PathContext;
+use provenance::{Provenance, Source};
+use {Error, Result};
+
+/// How the rows that cannot be read are handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// The reading fails on the first row that cannot be deserialized
+    /// or whose references cannot be resolved.
+    #[default]
+    Strict,
+    /// These rows are skipped, and recorded in the `ReadReport`.
+    Lenient,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ReadIssue {
+    pub file: String,
+    /// The line of the row in the file, the header being line 1.
+    pub line: Option<u64>,
+    pub message: String,
+}
+
+/// What was ignored when reading a model.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct ReadReport {
+    /// The rows that were skipped.
+    pub skipped_rows: Vec<ReadIssue>,
+    /// The invalid values that were read as empty, keeping their row.
+    pub ignored_values: Vec<ReadIssue>,
+}
+
+impl ReadReport {
+    pub fn is_empty(&self) -> bool {
+        self.skipped_rows.is_empty() && self.ignored_values.is_empty()
+    }
+}
+
+/// An error skipping its row whatever the strictness, as a code of an
+/// unknown object.
+#[derive(Debug)]
+pub(crate) struct SkipRow(pub String);
+impl fmt::Display for SkipRow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+impl Fail for SkipRow {}
+
+thread_local! {
+    // the invalid values of the row being read, if they are collected
+    static IGNORED_VALUES: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+    // whether the rows being read are read strictly
+    static STRICT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the row being read fails on the invalid values that are
+/// only ignored in lenient mode.
+pub(crate) fn is_strict() -> bool {
+    STRICT.with(Cell::get)
+}
+
+/// Records an invalid value of the row being read, read as empty.
+pub(crate) fn ignore_value(message: String) {
+    IGNORED_VALUES.with(|values| {
+        if let Some(ref mut values) = *values.borrow_mut() {
+            values.push(message);
+        }
+    });
+}
+
+/// The reading of some independent files, given to
+/// `Reader::read_concurrently`.
+pub(crate) type Task<'a> = Box<dyn FnOnce(&mut Reader) -> Result<()> + Send + 'a>;
+
+/// A thread pool of `threads` threads, `None` to use the global one.
+pub(crate) fn thread_pool(threads: Option<usize>) -> Result<Option<ThreadPool>> {
+    match threads {
+        Some(threads) => {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()?;
+            Ok(Some(pool))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Runs `f` on `pool`, or on the current thread without it.
+pub(crate) fn install<T, F>(pool: Option<&ThreadPool>, f: F) -> T
+where
+    T: Send,
+    F: FnOnce() -> T + Send,
+{
+    match pool {
+        Some(pool) => pool.install(f),
+        None => f(),
+    }
+}
+
+/// The reading of CSV files with a strictness, and its report.
+#[derive(Debug, Default)]
+pub(crate) struct Reader {
+    pub strictness: Strictness,
+    pub report: ReadReport,
+    /// Whether the sources of the rows are given to the readers, and
+    /// added to the errors of the rows.
+    pub track_provenance: bool,
+    pub provenance: Provenance,
+}
+
+impl Reader {
+    pub fn new(strictness: Strictness, track_provenance: bool) -> Reader {
+        Reader {
+            strictness,
+            track_provenance,
+            ..Reader::default()
+        }
+    }
+
+    /// A reader with the same options, to read other files
+    /// concurrently (see `merge`).
+    pub fn fork(&self) -> Reader {
+        Reader::new(self.strictness, self.track_provenance)
+    }
+
+    /// Adds the report and the provenance of `other`, a fork of this
+    /// reader.
+    pub fn merge(&mut self, other: Reader) {
+        self.report.skipped_rows.extend(other.report.skipped_rows);
+        self.report.ignored_values.extend(other.report.ignored_values);
+        self.provenance.merge(other.provenance);
+    }
+
+    /// Runs `tasks`, which read independent files, concurrently on
+    /// `pool`, or on the global thread pool without it.  Each task has
+    /// its own fork of this reader, merged back in the order of the
+    /// tasks, so that the report does not depend on the scheduling.
+    /// Fails with the error of the first task that failed.
+    pub fn read_concurrently(&mut self, pool: Option<&ThreadPool>, tasks: Vec<Task>) -> Result<()> {
+        let mut readers: Vec<_> = tasks.iter().map(|_| self.fork()).collect();
+        let results: Vec<_> = install(pool, || {
+            tasks
+                .into_par_iter()
+                .zip(readers.par_iter_mut())
+                .map(|(task, reader)| task(reader))
+                .collect()
+        });
+        for (reader, result) in readers.into_iter().zip(results) {
+            result?;
+            self.merge(reader);
+        }
+        Ok(())
+    }
+
+    /// Deserializes the rows of the CSV file at `path` from `rdr`, and
+    /// gives them to `f`.  A row that cannot be deserialized, or for
+    /// which `f` fails, fails the reading in strict mode, and is
+    /// skipped in lenient mode.  A row for which `f` fails with a
+    /// `SkipRow` is always skipped.
+    pub fn read_rows<T, R, F>(
+        &mut self,
+        path: &path::Path,
+        rdr: &mut csv::Reader<R>,
+        mut f: F,
+    ) -> Result<()>
+    where
+        T: DeserializeOwned,
+        R: io::Read,
+        F: FnMut(T) -> Result<()>,
+    {
+        self.read_sourced_rows(path, rdr, |obj, _| f(obj))
+    }
+
+    /// As `read_rows`, also giving to `f` the source of the row if the
+    /// provenance is tracked.
+    pub fn read_sourced_rows<T, R, F>(
+        &mut self,
+        path: &path::Path,
+        rdr: &mut csv::Reader<R>,
+        f: F,
+    ) -> Result<()>
+    where
+        T: DeserializeOwned,
+        R: io::Read,
+        F: FnMut(T, Option<Source>) -> Result<()>,
+    {
+        // `f` may read other rows, for instance in a visitor, so the
+        // values of the enclosing reading are restored afterwards
+        let ignored = IGNORED_VALUES.with(|values| values.replace(Some(vec![])));
+        let strict = STRICT.with(|s| s.replace(self.strictness == Strictness::Strict));
+        let res = self.read_records(path, rdr, f);
+        STRICT.with(|s| s.set(strict));
+        IGNORED_VALUES.with(|values| *values.borrow_mut() = ignored);
+        res
+    }
+
+    fn read_records<T, R, F>(
+        &mut self,
+        path: &path::Path,
+        rdr: &mut csv::Reader<R>,
+        mut f: F,
+    ) -> Result<()>
+    where
+        T: DeserializeOwned,
+        R: io::Read,
+        F: FnMut(T, Option<Source>) -> Result<()>,
+    {
+        let file = path.file_name()
+            .map_or_else(|| path.display().to_string(), |f| f.to_string_lossy().into_owned());
+        let headers = rdr.headers().with_context(ctx_from_path!(path))?.clone();
+        let mut record = csv::StringRecord::new();
+        loop {
+            let (line, res) = match rdr.read_record(&mut record) {
+                Ok(false) => break,
+                Ok(true) => {
+                    let line = record.position().map(|p| p.line());
+                    let source = match line {
+                        Some(line) if self.track_provenance => Some(Source {
+                            file: file.clone(),
+                            line,
+                        }),
+                        _ => None,
+                    };
+                    let res = record
+                        .deserialize(Some(&headers))
+                        .with_context(ctx_from_path!(path))
+                        .map_err(Error::from)
+                        .and_then(|obj| {
+                            Ok(f(obj, source.clone()).with_context(ctx_from_path!(path))?)
+                        });
+                    let res = match (res, source) {
+                        (Err(err), Some(source)) => Err(err.context(source).into()),
+                        (res, _) => res,
+                    };
+                    (line, res)
+                }
+                Err(e) => {
+                    let is_io = matches!(*e.kind(), csv::ErrorKind::Io(_));
+                    let line = e.position().map(|p| p.line());
+                    let ctx = PathContext::new(path, &e);
+                    let err = Error::from(e.context(ctx));
+                    if is_io {
+                        return Err(err);
+                    }
+                    (line, Err(err))
+                }
+            };
+            let values = IGNORED_VALUES.with(|values| {
+                values.borrow_mut().as_mut().map(|v| v.split_off(0)).unwrap_or_default()
+            });
+            for message in values {
+                self.report.ignored_values.push(ReadIssue {
+                    file: file.clone(),
+                    line,
+                    message,
+                });
+            }
+            let err = match res {
+                Ok(()) => continue,
+                Err(err) => err,
+            };
+            let skip = err.iter_chain().any(|c| c.downcast_ref::<SkipRow>().is_some());
+            if !skip && self.strictness == Strictness::Strict {
+                return Err(err);
+            }
+            let message = err.find_root_cause().to_string();
+            if skip {
+                error!("{}: {}", file, message);
+            } else {
+                warn!("{} line {:?} skipped: {}", file, line, message);
+            }
+            self.report.skipped_rows.push(ReadIssue {
+                file: file.clone(),
+                line,
+                message,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn collection_with_id<T>(
+        &mut self,
+        path: &path::Path,
+        file: &str,
+    ) -> Result<CollectionWithId<T>>
+    where
+        T: Id<T> + DeserializeOwned,
+    {
+        self.checked_collection_with_id(path, file, |_| Ok(()))
+    }
+
+    /// Reads a collection, skipping in lenient mode the objects for
+    /// which `check` fails, as the rows that cannot be deserialized.
+    pub fn checked_collection_with_id<T, F>(
+        &mut self,
+        path: &path::Path,
+        file: &str,
+        mut check: F,
+    ) -> Result<CollectionWithId<T>>
+    where
+        T: Id<T> + DeserializeOwned,
+        F: FnMut(&T) -> Result<()>,
+    {
+        info!("Reading {}", file);
+        let path = path.join(file);
+        let mut rdr = csv::Reader::from_path(&path).with_context(ctx_from_path!(path))?;
+        let lenient = self.strictness == Strictness::Lenient;
+        let mut collection = CollectionWithId::default();
+        let mut sources = vec![];
+        self.read_sourced_rows(&path, &mut rdr, |obj: T, source| {
+            if lenient {
+                check(&obj)?;
+            }
+            if let Some(source) = source {
+                sources.push((obj.id().to_string(), source));
+            }
+            collection.push(obj).map(|_| ())
+        })?;
+        for (id, source) in sources {
+            self.provenance.insert::<T>(&id, source);
+        }
+        Ok(collection)
+    }
+
+    pub fn opt_collection_with_id<T>(
+        &mut self,
+        path: &path::Path,
+        file: &str,
+    ) -> Result<CollectionWithId<T>>
+    where
+        T: Id<T> + DeserializeOwned,
+    {
+        if !path.join(file).exists() {
+            info!("Skipping {}", file);
+            Ok(CollectionWithId::default())
+        } else {
+            self.collection_with_id(path, file)
+        }
+    }
+
+    pub fn checked_opt_collection_with_id<T, F>(
+        &mut self,
+        path: &path::Path,
+        file: &str,
+        check: F,
+    ) -> Result<CollectionWithId<T>>
+    where
+        T: Id<T> + DeserializeOwned,
+        F: FnMut(&T) -> Result<()>,
+    {
+        if !path.join(file).exists() {
+            info!("Skipping {}", file);
+            Ok(CollectionWithId::default())
+        } else {
+            self.checked_collection_with_id(path, file, check)
+        }
+    }
+
+    pub fn collection<T>(&mut self, path: &path::Path, file: &str) -> Result<Collection<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.checked_collection(path, file, |_| Ok(()))
+    }
+
+    /// Reads a collection, skipping in lenient mode the objects for
+    /// which `check` fails, as the rows that cannot be deserialized.
+    pub fn checked_collection<T, F>(
+        &mut self,
+        path: &path::Path,
+        file: &str,
+        mut check: F,
+    ) -> Result<Collection<T>>
+    where
+        T: DeserializeOwned,
+        F: FnMut(&T) -> Result<()>,
+    {
+        info!("Reading {}", file);
+        let path = path.join(file);
+        let mut rdr = csv::Reader::from_path(&path).with_context(ctx_from_path!(path))?;
+        let lenient = self.strictness == Strictness::Lenient;
+        let mut objects = vec![];
+        self.read_rows(&path, &mut rdr, |obj| {
+            if lenient {
+                check(&obj)?;
+            }
+            objects.push(obj);
+            Ok(())
+        })?;
+        Ok(Collection::new(objects))
+    }
+
+    pub fn opt_collection<T>(&mut self, path: &path::Path, file: &str) -> Result<Collection<T>>
+    where
+        T: DeserializeOwned,
+    {
+        self.checked_opt_collection(path, file, |_| Ok(()))
+    }
+
+    pub fn checked_opt_collection<T, F>(
+        &mut self,
+        path: &path::Path,
+        file: &str,
+        check: F,
+    ) -> Result<Collection<T>>
+    where
+        T: DeserializeOwned,
+        F: FnMut(&T) -> Result<()>,
+    {
+        if !path.join(file).exists() {
+            info!("Skipping {}", file);
+            Ok(Collection::default())
+        } else {
+            self.checked_collection(path, file, check)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use objects::Rgb;
+
+    #[derive(Deserialize)]
+    struct Row {
+        #[serde(default, deserialize_with = "::utils::de_invalid_option")]
+        color: Option<Rgb>,
+    }
+
+    #[test]
+    fn nested_readings_keep_their_ignored_values() {
+        let mut reader = Reader::default();
+        let mut inner = Reader::default();
+        let mut colors = vec![];
+        let mut rdr = csv::Reader::from_reader("color\nyellow\nFF0000\n".as_bytes());
+        reader
+            .read_rows(path::Path::new("outer.txt"), &mut rdr, |row: Row| {
+                colors.push(row.color.is_some());
+                let mut rdr = csv::Reader::from_reader("color\nblue\n".as_bytes());
+                inner.read_rows(path::Path::new("inner.txt"), &mut rdr, |_: Row| Ok(()))
+            })
+            .unwrap();
+        assert_eq!(colors, &[false, true]);
+        let ignored = |report: &ReadReport| -> Vec<(String, Option<u64>)> {
+            report
+                .ignored_values
+                .iter()
+                .map(|i| (i.file.clone(), i.line))
+                .collect()
+        };
+        assert_eq!(ignored(&reader.report), &[("outer.txt".to_string(), Some(2))]);
+        assert_eq!(
+            ignored(&inner.report),
+            &[("inner.txt".to_string(), Some(2)), ("inner.txt".to_string(), Some(2))]
+        );
+    }
+}
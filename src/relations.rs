@@ -16,8 +16,10 @@
 
 use std::collections::{BTreeMap, BTreeSet};
 use collection::{CollectionWithId, Id, Idx};
+use error::{type_name, ErrorKind};
 use Result;
 use failure::ResultExt;
+use rayon::prelude::*;
 
 pub type IdxSet<T> = BTreeSet<Idx<T>>;
 
@@ -29,6 +31,8 @@ pub trait Relation {
     fn get_corresponding_backward(&self, from: &IdxSet<Self::To>) -> IdxSet<Self::From>;
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct OneToMany<T, U> {
     one_to_many: BTreeMap<Idx<T>, IdxSet<U>>,
     many_to_one: BTreeMap<Idx<U>, Idx<T>>,
@@ -44,8 +48,12 @@ where
         let mut many_to_one = BTreeMap::default();
         for (many_idx, obj) in many.iter() {
             let one_id = <U as Id<T>>::id(obj);
-            let one_idx = one.get_idx(one_id)
-                .ok_or_else(|| format_err!("id={:?} not found", one_id))?;
+            let one_idx = one.get_idx(one_id).ok_or_else(|| ErrorKind::UnknownReference {
+                from_type: type_name::<U>(),
+                from_id: <U as Id<U>>::id(obj).to_string(),
+                to_type: type_name::<T>(),
+                to_id: one_id.to_string(),
+            })?;
             many_to_one.insert(many_idx, one_idx);
             one_to_many
                 .entry(one_idx)
@@ -66,6 +74,16 @@ where
     }
 }
 
+impl<T, U> OneToMany<T, U> {
+    /// Whether the indexes are lower than the lengths of the
+    /// collections, as expected of a deserialized relation.
+    pub(crate) fn is_within(&self, one_len: usize, many_len: usize) -> bool {
+        is_within(&self.one_to_many, one_len, many_len)
+            && self.many_to_one.keys().next_back().map_or(true, |idx| idx.get() < many_len)
+            && self.many_to_one.values().all(|idx| idx.get() < one_len)
+    }
+}
+
 impl<T, U> Relation for OneToMany<T, U> {
     type From = T;
     type To = U;
@@ -83,6 +101,8 @@ impl<T, U> Relation for OneToMany<T, U> {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct ManyToMany<T, U> {
     forward: BTreeMap<Idx<T>, IdxSet<U>>,
     backward: BTreeMap<Idx<U>, IdxSet<T>>,
@@ -102,13 +122,18 @@ impl<T, U> ManyToMany<T, U> {
             });
         ManyToMany { forward, backward }
     }
+    /// The relation from the objects of `r1` to the objects of `r2`
+    /// corresponding to them, computed concurrently on the current
+    /// thread pool.
     pub fn from_relations_chain<R1, R2>(r1: &R1, r2: &R2) -> Self
     where
-        R1: Relation<From = T>,
-        R2: Relation<From = R1::To, To = U>,
+        T: Send,
+        U: Send,
+        R1: Relation<From = T> + Sync,
+        R2: Relation<From = R1::To, To = U> + Sync,
     {
         let forward = r1.get_from()
-            .into_iter()
+            .into_par_iter()
             .map(|idx| {
                 let from = Some(idx).into_iter().collect();
                 let tmp = r1.get_corresponding_forward(&from);
@@ -117,13 +142,18 @@ impl<T, U> ManyToMany<T, U> {
             .collect();
         Self::from_forward(forward)
     }
+    /// The relation from the objects of `r1` to the objects of `r2`
+    /// sharing their corresponding objects, computed concurrently on
+    /// the current thread pool.
     pub fn from_relations_sink<R1, R2>(r1: &R1, r2: &R2) -> Self
     where
-        R1: Relation<From = T>,
-        R2: Relation<From = U, To = R1::To>,
+        T: Send,
+        U: Send,
+        R1: Relation<From = T> + Sync,
+        R2: Relation<From = U, To = R1::To> + Sync,
     {
         let forward = r1.get_from()
-            .into_iter()
+            .into_par_iter()
             .map(|idx| {
                 let from = Some(idx).into_iter().collect();
                 let tmp = r1.get_corresponding_forward(&from);
@@ -132,6 +162,12 @@ impl<T, U> ManyToMany<T, U> {
             .collect();
         Self::from_forward(forward)
     }
+
+    /// Whether the indexes are lower than the lengths of the
+    /// collections, as expected of a deserialized relation.
+    pub(crate) fn is_within(&self, from_len: usize, to_len: usize) -> bool {
+        is_within(&self.forward, from_len, to_len) && is_within(&self.backward, to_len, from_len)
+    }
 }
 
 impl<T, U> Relation for ManyToMany<T, U> {
@@ -154,3 +190,8 @@ fn get_corresponding<T, U>(map: &BTreeMap<Idx<T>, IdxSet<U>>, from: &IdxSet<T>)
         .flat_map(|indices| indices.iter().cloned())
         .collect()
 }
+
+fn is_within<T, U>(map: &BTreeMap<Idx<T>, IdxSet<U>>, from_len: usize, to_len: usize) -> bool {
+    map.keys().next_back().map_or(true, |idx| idx.get() < from_len)
+        && map.values().all(|set| set.iter().next_back().map_or(true, |idx| idx.get() < to_len))
+}
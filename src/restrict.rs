@@ -0,0 +1,252 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! Restriction of a model to a part of it.
+
+use std::cmp;
+use std::str::FromStr;
+use collection::{Collection, CollectionWithId, Id};
+use error::{type_name, ErrorKind};
+use objects::*;
+use relations::IdxSet;
+use sanitize::SanitizeReport;
+use {Collections, Error, GetCorresponding, PtObjects, Result};
+
+/// A rectangle of coordinates, bounds included.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+}
+
+impl BoundingBox {
+    pub fn contains(&self, coord: &Coord) -> bool {
+        self.min_lon <= coord.lon && coord.lon <= self.max_lon && self.min_lat <= coord.lat
+            && coord.lat <= self.max_lat
+    }
+}
+
+impl FromStr for BoundingBox {
+    type Err = Error;
+
+    /// Parses `min_lon,min_lat,max_lon,max_lat`.
+    fn from_str(s: &str) -> Result<BoundingBox> {
+        let values = s.split(',')
+            .map(|v| v.trim().parse::<f64>())
+            .collect::<::std::result::Result<Vec<_>, _>>()
+            .map_err(|e| format_err!("invalid bounding box {:?}: {}", s, e))?;
+        ensure!(
+            values.len() == 4,
+            "invalid bounding box {:?}: min_lon,min_lat,max_lon,max_lat expected",
+            s
+        );
+        Ok(BoundingBox {
+            min_lon: values[0],
+            min_lat: values[1],
+            max_lon: values[2],
+            max_lat: values[3],
+        })
+    }
+}
+
+/// The part of a model to extract.  A vehicle journey is kept if it
+/// matches all the given criteria; an empty list of ids is no
+/// criterion.
+#[derive(Debug, Default, Clone)]
+pub struct Selection {
+    pub networks: Vec<String>,
+    pub lines: Vec<String>,
+    pub physical_modes: Vec<String>,
+    pub contributors: Vec<String>,
+    /// The vehicle journeys stopping at least once in the bounding box
+    /// are kept, with all their stops.
+    pub bounding_box: Option<BoundingBox>,
+}
+
+impl Selection {
+    /// Whether no criterion is given, all the vehicle journeys being
+    /// selected.
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+            && self.lines.is_empty()
+            && self.physical_modes.is_empty()
+            && self.contributors.is_empty()
+            && self.bounding_box.is_none()
+    }
+}
+
+/// The vehicle journeys of the objects of `ids`, the `field` of a
+/// selection, or `None` if none is selected.
+fn vehicle_journeys_of<T>(
+    pt_objects: &PtObjects,
+    collection: &CollectionWithId<T>,
+    ids: &[String],
+    field: &'static str,
+) -> Result<Option<IdxSet<VehicleJourney>>>
+where
+    T: Id<T>,
+    IdxSet<T>: GetCorresponding<VehicleJourney>,
+{
+    if ids.is_empty() {
+        return Ok(None);
+    }
+    let idxs = ids.iter()
+        .map(|id| {
+            collection.get_idx(id).ok_or_else(|| {
+                ErrorKind::UnknownReference {
+                    from_type: "Selection",
+                    from_id: field.to_string(),
+                    to_type: type_name::<T>(),
+                    to_id: id.to_string(),
+                }.into()
+            })
+        })
+        .collect::<Result<IdxSet<T>>>()?;
+    Ok(Some(pt_objects.get_corresponding(&idxs)))
+}
+
+impl PtObjects {
+    /// Extracts the part of the model given by `selection`: the
+    /// selected vehicle journeys, and the objects they use (see
+    /// `Collections::sanitize`).
+    pub fn extract(self, selection: &Selection) -> Result<PtObjects> {
+        let mut selected = vec![
+            vehicle_journeys_of(&self, &self.networks, &selection.networks, "networks")?,
+            vehicle_journeys_of(&self, &self.lines, &selection.lines, "lines")?,
+            vehicle_journeys_of(
+                &self,
+                &self.physical_modes,
+                &selection.physical_modes,
+                "physical_modes",
+            )?,
+            vehicle_journeys_of(
+                &self,
+                &self.contributors,
+                &selection.contributors,
+                "contributors",
+            )?,
+        ];
+        if let Some(ref bounding_box) = selection.bounding_box {
+            let stop_points = self.stop_points
+                .iter()
+                .filter(|&(_, sp)| bounding_box.contains(&sp.coord))
+                .map(|(idx, _)| idx)
+                .collect();
+            selected.push(Some(self.get_corresponding(&stop_points)));
+        }
+        let selected = selected.into_iter().fold(None, |acc, vjs| match (acc, vjs) {
+            (None, vjs) | (vjs, None) => vjs,
+            (Some(acc), Some(vjs)) => Some(&acc & &vjs),
+        });
+        let selected = match selected {
+            Some(selected) => selected,
+            None => return Ok(self),
+        };
+        info!("Extracting {} vehicle journeys", selected.len());
+        let kept: ::std::collections::HashSet<String> = selected
+            .iter()
+            .map(|&idx| self.vehicle_journeys[idx].id.clone())
+            .collect();
+        self.edit(|collections| {
+            collections.vehicle_journeys.retain(|vj| kept.contains(&vj.id));
+            collections.sanitize();
+            Ok(())
+        }).map_err(|e| e.error)
+    }
+}
+
+fn has_active_day(calendar: &Calendar) -> bool {
+    !calendar.validity_pattern().is_empty()
+}
+
+fn clip_calendar(calendar: &mut Calendar, start: Date, end: Date) {
+    calendar.start_date = cmp::max(calendar.start_date, start);
+    calendar.end_date = cmp::min(calendar.end_date, end);
+    if calendar.start_date > calendar.end_date {
+        // no regular day left, only the exceptions
+        calendar.monday = false;
+        calendar.tuesday = false;
+        calendar.wednesday = false;
+        calendar.thursday = false;
+        calendar.friday = false;
+        calendar.saturday = false;
+        calendar.sunday = false;
+        calendar.start_date = start;
+        calendar.end_date = end;
+    }
+    calendar
+        .calendar_dates
+        .retain(|&(date, _)| start <= date && date <= end);
+}
+
+impl Collections {
+    /// Restricts the model to the days between `start` and `end`,
+    /// included.  The calendars are clipped to the period, the ones
+    /// without any active day left are removed, and then the objects
+    /// that are no longer used (see `Collections::sanitize`).  The
+    /// datasets and the feed infos are updated to the period, a dataset
+    /// without any day in the period, but still used, getting the whole
+    /// period as the calendars without regular day.  The grid periods
+    /// are clipped to the period, and removed if they end before it or
+    /// start after it.
+    pub fn restrict_validity_period(&mut self, start: Date, end: Date) -> Result<SanitizeReport> {
+        ensure!(
+            start <= end,
+            "invalid validity period: {} is after {}",
+            start,
+            end
+        );
+        info!("Restricting validity period to {} - {}", start, end);
+        let idxs: Vec<_> = self.calendars.iter().map(|(idx, _)| idx).collect();
+        for idx in idxs {
+            clip_calendar(&mut self.calendars.index_mut(idx), start, end);
+        }
+        let mut report = SanitizeReport::default();
+        report.retain("calendars", &mut self.calendars, has_active_day);
+        let idxs: Vec<_> = self.datasets.iter().map(|(idx, _)| idx).collect();
+        for idx in idxs {
+            let mut dataset = self.datasets.index_mut(idx);
+            dataset.start_date = cmp::max(dataset.start_date, start);
+            dataset.end_date = cmp::min(dataset.end_date, end);
+            if dataset.start_date > dataset.end_date {
+                dataset.start_date = start;
+                dataset.end_date = end;
+            }
+        }
+        let mut grid_periods = self.grid_periods.take();
+        for period in &mut grid_periods {
+            period.start_date = cmp::max(period.start_date, start);
+            period.end_date = cmp::min(period.end_date, end);
+        }
+        self.grid_periods = Collection::new(grid_periods);
+        report.retain_collection("grid_periods", &mut self.grid_periods, |p| {
+            p.start_date <= p.end_date
+        });
+        self.feed_infos.insert(
+            "feed_start_date".to_string(),
+            start.format("%Y%m%d").to_string(),
+        );
+        self.feed_infos.insert(
+            "feed_end_date".to_string(),
+            end.format("%Y%m%d").to_string(),
+        );
+
+        report.extend(self.sanitize());
+        Ok(report)
+    }
+}
@@ -0,0 +1,280 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! Removal of the objects that are not used by any vehicle journey.
+
+use std::collections::{BTreeMap, HashSet};
+use collection::{Collection, CollectionWithId, Id, IdxMapping};
+use objects::*;
+use {Collections, PtObjects, Result};
+
+/// What was removed by a sanitization.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct SanitizeReport {
+    /// Ids of the removed objects, by collection.
+    pub removed_ids: BTreeMap<&'static str, Vec<String>>,
+    /// Number of removed objects without id, by collection.
+    pub removed_counts: BTreeMap<&'static str, usize>,
+}
+
+impl SanitizeReport {
+    pub fn is_empty(&self) -> bool {
+        self.removed_ids.is_empty() && self.removed_counts.is_empty()
+    }
+
+    pub(crate) fn extend(&mut self, other: SanitizeReport) {
+        for (name, ids) in other.removed_ids {
+            self.removed_ids.entry(name).or_default().extend(ids);
+        }
+        for (name, count) in other.removed_counts {
+            *self.removed_counts.entry(name).or_default() += count;
+        }
+    }
+
+    pub(crate) fn retain<T, F>(
+        &mut self,
+        name: &'static str,
+        collection: &mut CollectionWithId<T>,
+        mut f: F,
+    ) -> IdxMapping<T>
+    where
+        T: Id<T>,
+        F: FnMut(&T) -> bool,
+    {
+        let mut removed = vec![];
+        let mapping = collection.retain(|obj| {
+            let keep = f(obj);
+            if !keep {
+                removed.push(obj.id().to_string());
+            }
+            keep
+        });
+        if !removed.is_empty() {
+            info!("Removing {} unused {}", removed.len(), name);
+            self.removed_ids.insert(name, removed);
+        }
+        mapping
+    }
+
+    pub(crate) fn retain_collection<T, F>(
+        &mut self,
+        name: &'static str,
+        collection: &mut Collection<T>,
+        f: F,
+    ) where
+        F: FnMut(&T) -> bool,
+    {
+        let mut objects = collection.take();
+        let len = objects.len();
+        objects.retain(f);
+        if objects.len() != len {
+            info!("Removing {} unused {}", len - objects.len(), name);
+            self.removed_counts.insert(name, len - objects.len());
+        }
+        *collection = Collection::new(objects);
+    }
+}
+
+fn ids<'a, I: IntoIterator<Item = &'a String>>(ids: I) -> HashSet<String> {
+    ids.into_iter().cloned().collect()
+}
+
+/// Gives each object to `f` in place, which must not change its id.
+fn for_each_mut<T: Id<T>, F: FnMut(&mut T)>(collection: &mut CollectionWithId<T>, mut f: F) {
+    collection
+        .try_for_each_mut(|_, o| {
+            f(o);
+            Ok(())
+        })
+        .expect("the ids are not changed by the sanitization");
+}
+
+impl Collections {
+    /// Removes the objects that are not used, directly or not, by a
+    /// vehicle journey with stop times and an existing calendar, and
+    /// returns what was removed.
+    /// The stop areas without stop points are removed, and the routes
+    /// lose their destination if it was removed.
+    pub fn sanitize(&mut self) -> SanitizeReport {
+        let mut report = SanitizeReport::default();
+
+        let calendars = &self.calendars;
+        report.retain("vehicle_journeys", &mut self.vehicle_journeys, |vj| {
+            !vj.stop_times.is_empty() && calendars.get_idx(&vj.service_id).is_some()
+        });
+
+        let vjs = &self.vehicle_journeys;
+        let used = ids(vjs.iter().map(|(_, vj)| &vj.route_id));
+        report.retain("routes", &mut self.routes, |o| used.contains(&o.id));
+        let used = ids(self.routes.iter().map(|(_, r)| &r.line_id));
+        report.retain("lines", &mut self.lines, |o| used.contains(&o.id));
+        let used = ids(self.lines.iter().map(|(_, l)| &l.network_id));
+        report.retain("networks", &mut self.networks, |o| used.contains(&o.id));
+        let used = ids(self.lines.iter().map(|(_, l)| &l.commercial_mode_id));
+        report.retain("commercial_modes", &mut self.commercial_modes, |o| {
+            used.contains(&o.id)
+        });
+        let used = ids(vjs.iter().map(|(_, vj)| &vj.physical_mode_id));
+        report.retain("physical_modes", &mut self.physical_modes, |o| {
+            used.contains(&o.id)
+        });
+        let used = ids(vjs.iter().map(|(_, vj)| &vj.dataset_id));
+        report.retain("datasets", &mut self.datasets, |o| used.contains(&o.id));
+        let used = ids(self.datasets.iter().map(|(_, d)| &d.contributor_id));
+        report.retain("contributors", &mut self.contributors, |o| used.contains(&o.id));
+        let used = ids(vjs.iter().map(|(_, vj)| &vj.company_id));
+        report.retain("companies", &mut self.companies, |o| used.contains(&o.id));
+        let used = ids(vjs.iter().map(|(_, vj)| &vj.service_id));
+        report.retain("calendars", &mut self.calendars, |o| used.contains(&o.id));
+        let used = ids(vjs.iter().filter_map(|(_, vj)| vj.trip_property_id.as_ref()));
+        report.retain("trip_properties", &mut self.trip_properties, |o| {
+            used.contains(&o.id)
+        });
+
+        let used: HashSet<_> = vjs.iter()
+            .flat_map(|(_, vj)| vj.stop_times.iter().map(|st| st.stop_point_idx))
+            .collect();
+        let sp_mapping = {
+            let stop_points = &self.stop_points;
+            let used = ids(used.iter().map(|&idx| &stop_points[idx].id));
+            report.retain("stop_points", &mut self.stop_points, |o| used.contains(&o.id))
+        };
+        for_each_mut(&mut self.vehicle_journeys, |vj| {
+            for st in &mut vj.stop_times {
+                st.stop_point_idx = sp_mapping
+                    .get(st.stop_point_idx)
+                    .expect("used stop points are kept");
+            }
+        });
+        let used = ids(self.stop_points.iter().map(|(_, sp)| &sp.stop_area_id));
+        report.retain("stop_areas", &mut self.stop_areas, |o| used.contains(&o.id));
+        let stop_areas = &self.stop_areas;
+        for_each_mut(&mut self.routes, |route| {
+            if route
+                .destination_id
+                .as_ref()
+                .map_or(false, |id| stop_areas.get_idx(id).is_none())
+            {
+                route.destination_id = None;
+            }
+        });
+
+        let vjs = &self.vehicle_journeys;
+        let stop_points = &self.stop_points;
+        report.retain_collection("transfers", &mut self.transfers, |t| {
+            stop_points.get_idx(&t.from_stop_id).is_some()
+                && stop_points.get_idx(&t.to_stop_id).is_some()
+        });
+        report.retain_collection("admin_stations", &mut self.admin_stations, |a| {
+            stop_areas.get_idx(&a.stop_id).is_some() || stop_points.get_idx(&a.stop_id).is_some()
+        });
+        let lines = &self.lines;
+        report.retain_collection(
+            "grid_rel_calendar_line",
+            &mut self.grid_rel_calendar_line,
+            |rel| rel.line_idx(lines).is_some(),
+        );
+        let used = ids(self.grid_rel_calendar_line
+            .iter()
+            .map(|(_, rel)| &rel.grid_calendar_id));
+        report.retain("grid_calendars", &mut self.grid_calendars, |o| {
+            used.contains(&o.id)
+        });
+        let grid_calendars = &self.grid_calendars;
+        report.retain_collection("grid_exception_dates", &mut self.grid_exception_dates, |d| {
+            grid_calendars.get_idx(&d.grid_calendar_id).is_some()
+        });
+        report.retain_collection("grid_periods", &mut self.grid_periods, |p| {
+            grid_calendars.get_idx(&p.grid_calendar_id).is_some()
+        });
+        report.retain("line_groups", &mut self.line_groups, |g| {
+            lines.get_idx(&g.main_line_id).is_some()
+        });
+        let line_groups = &self.line_groups;
+        report.retain_collection("line_group_links", &mut self.line_group_links, |l| {
+            line_groups.get_idx(&l.line_group_id).is_some() && lines.get_idx(&l.line_id).is_some()
+        });
+
+        let used = ids(self.stop_points
+            .iter()
+            .filter_map(|(_, sp)| sp.equipment_id.as_ref())
+            .chain(stop_areas.iter().filter_map(|(_, sa)| sa.equipment_id.as_ref()))
+            .chain(self.transfers.iter().filter_map(|(_, t)| t.equipment_id.as_ref())));
+        report.retain("equipments", &mut self.equipments, |o| used.contains(&o.id));
+        let used = ids(self.lines
+            .iter()
+            .filter_map(|(_, l)| l.geometry_id.as_ref())
+            .chain(self.routes.iter().filter_map(|(_, r)| r.geometry_id.as_ref()))
+            .chain(vjs.iter().filter_map(|(_, vj)| vj.geometry_id.as_ref()))
+            .chain(stop_points.iter().filter_map(|(_, sp)| sp.geometry_id.as_ref()))
+            .chain(stop_areas.iter().filter_map(|(_, sa)| sa.geometry_id.as_ref())));
+        report.retain("geometries", &mut self.geometries, |o| used.contains(&o.id));
+
+        let used: HashSet<_> = self.networks
+            .iter()
+            .flat_map(|(_, o)| o.comment_links.iter())
+            .chain(lines.iter().flat_map(|(_, o)| o.comment_links.iter()))
+            .chain(self.routes.iter().flat_map(|(_, o)| o.comment_links.iter()))
+            .chain(vjs.iter().flat_map(|(_, o)| o.comment_links.iter()))
+            .chain(stop_points.iter().flat_map(|(_, o)| o.comment_links.iter()))
+            .chain(stop_areas.iter().flat_map(|(_, o)| o.comment_links.iter()))
+            .chain(self.calendars.iter().flat_map(|(_, o)| o.comment_links.iter()))
+            .chain(self.companies.iter().flat_map(|(_, o)| o.comment_links.iter()))
+            .chain(line_groups.iter().flat_map(|(_, o)| o.comment_links.iter()))
+            .chain(
+                vjs.iter()
+                    .flat_map(|(_, vj)| vj.stop_times.iter())
+                    .flat_map(|st| st.comment_links().iter()),
+            )
+            .cloned()
+            .collect();
+        let comment_mapping = {
+            let comments = &self.comments;
+            let used = ids(used.iter().map(|&idx| &comments[idx].id));
+            report.retain("comments", &mut self.comments, |o| used.contains(&o.id))
+        };
+        if report.removed_ids.contains_key("comments") {
+            let links = |links: &mut CommentLinksT| comment_mapping.apply(links);
+            for_each_mut(&mut self.networks, |o| links(&mut o.comment_links));
+            for_each_mut(&mut self.lines, |o| links(&mut o.comment_links));
+            for_each_mut(&mut self.routes, |o| links(&mut o.comment_links));
+            for_each_mut(&mut self.vehicle_journeys, |o| {
+                links(&mut o.comment_links);
+                o.stop_times
+                    .iter_mut()
+                    .filter_map(|st| st.extras.as_mut())
+                    .for_each(|extras| links(&mut extras.comment_links));
+            });
+            for_each_mut(&mut self.stop_points, |o| links(&mut o.comment_links));
+            for_each_mut(&mut self.stop_areas, |o| links(&mut o.comment_links));
+            for_each_mut(&mut self.calendars, |o| links(&mut o.comment_links));
+            for_each_mut(&mut self.companies, |o| links(&mut o.comment_links));
+            for_each_mut(&mut self.line_groups, |o| links(&mut o.comment_links));
+        }
+
+        report
+    }
+}
+
+impl PtObjects {
+    /// Sanitizes the collections (see `Collections::sanitize`) and
+    /// rebuilds the relations.
+    pub fn sanitize(self) -> Result<(PtObjects, SanitizeReport)> {
+        let mut collections = self.into_collections();
+        let report = collections.sanitize();
+        Ok((PtObjects::new(collections)?, report))
+    }
+}
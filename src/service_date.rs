@@ -0,0 +1,104 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! Vehicle journeys running on a given day.
+
+use chrono::Duration;
+use collection::Idx;
+use objects::*;
+use relations::IdxSet;
+use {GetCorresponding, PtObjects};
+
+/// A vehicle journey running on a day.
+#[derive(Debug)]
+pub struct Departure<'a> {
+    pub vehicle_journey_idx: Idx<VehicleJourney>,
+    pub vehicle_journey: &'a VehicleJourney,
+    /// The day of the calendar of the vehicle journey: n days before
+    /// the requested one for the stop times after n times 24:00.
+    pub service_date: Date,
+    /// The stop times happening on the requested day.
+    pub stop_times: &'a [StopTime],
+}
+
+impl<'a> Departure<'a> {
+    /// The departure time of the first stop time, from the midnight of
+    /// the requested day.
+    pub fn departure_time(&self) -> Time {
+        self.stop_times[0].departure_time.split_days().1
+    }
+}
+
+impl PtObjects {
+    /// The vehicle journeys running on `date`, ordered by departure.
+    /// A vehicle journey of a day before running after midnight is
+    /// given with its stop times of `date`, as its stop times after
+    /// 24:00 for the day before, after 48:00 for two days before...
+    pub fn vehicle_journeys_on<'a>(&'a self, date: Date) -> Vec<Departure<'a>> {
+        let idxs = self.vehicle_journeys.iter().map(|(idx, _)| idx).collect();
+        self.departures(date, &idxs)
+    }
+
+    /// The vehicle journeys running on `date` that correspond to
+    /// `from` (for example a line, a route, a stop point or a network),
+    /// ordered by departure (see `vehicle_journeys_on`).
+    pub fn vehicle_journeys_on_from<'a, T>(
+        &'a self,
+        date: Date,
+        from: &IdxSet<T>,
+    ) -> Vec<Departure<'a>>
+    where
+        IdxSet<T>: GetCorresponding<VehicleJourney>,
+    {
+        self.departures(date, &self.get_corresponding(from))
+    }
+
+    fn departures<'a>(&'a self, date: Date, idxs: &IdxSet<VehicleJourney>) -> Vec<Departure<'a>> {
+        let mut departures = vec![];
+        for &idx in idxs {
+            let vj = &self.vehicle_journeys[idx];
+            let calendar = match self.calendars.get(&vj.service_id) {
+                Some(calendar) => calendar,
+                None => continue,
+            };
+            // the stop times by number of days after the service day
+            let mut rest = &vj.stop_times[..];
+            while let Some(first) = rest.first() {
+                let days = first.departure_time.split_days().0;
+                let len = rest
+                    .iter()
+                    .position(|st| st.departure_time.split_days().0 != days)
+                    .unwrap_or(rest.len());
+                let (stop_times, others) = rest.split_at(len);
+                rest = others;
+                let service_date = date - Duration::days(i64::from(days));
+                if calendar.is_active(service_date) {
+                    departures.push(Departure {
+                        vehicle_journey_idx: idx,
+                        vehicle_journey: vj,
+                        service_date,
+                        stop_times,
+                    });
+                }
+            }
+        }
+        departures.sort_by(|a, b| {
+            (a.departure_time(), &a.vehicle_journey.id)
+                .cmp(&(b.departure_time(), &b.vehicle_journey.id))
+        });
+        departures
+    }
+}
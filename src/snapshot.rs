@@ -0,0 +1,394 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! Binary snapshots of a model, much faster to load than the NTFS
+//! they are made from.
+//!
+//! A snapshot is the magic bytes `NTMSNAP\0`, the version of the
+//! format as a little endian `u32`, and then, encoded with bincode:
+//! the collections, the fields that their serialization skips (as the
+//! codes, the comment links and the stop times), and the relations if
+//! they were saved.  The provenance of the objects is not saved.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path;
+use bincode::{self, Options};
+use failure::ResultExt;
+use collection::{CollectionWithId, Id, Idx};
+use error::{self, ErrorKind};
+use objects::*;
+use {Collections, Error, PtObjects, Result};
+
+const MAGIC: &[u8; 8] = b"NTMSNAP\0";
+
+/// The version of the format, changed with the model: a snapshot is
+/// only read by the version of the format that wrote it.
+pub const VERSION: u32 = 3;
+
+#[derive(Debug, Default, Clone)]
+pub struct WriteOptions {
+    /// Also save the relations, making a bigger snapshot that is read
+    /// without rebuilding them.
+    pub relations: bool,
+}
+
+fn encoding() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+type ExtrasRef<'a> = (&'a KeysValues, &'a KeysValues, &'a CommentLinksT);
+type Extras = (KeysValues, KeysValues, CommentLinksT);
+type CodesRef<'a> = (&'a KeysValues, &'a KeysValues);
+type CodesAndProperties = (KeysValues, KeysValues);
+
+/// The fields skipped by the serialization of the collections, by
+/// object, in the order of the collections.
+#[derive(Serialize)]
+struct SkippedRef<'a> {
+    contributors: Vec<CodesRef<'a>>,
+    datasets: Vec<CodesRef<'a>>,
+    networks: Vec<ExtrasRef<'a>>,
+    lines: Vec<ExtrasRef<'a>>,
+    routes: Vec<ExtrasRef<'a>>,
+    vehicle_journeys: Vec<ExtrasRef<'a>>,
+    stop_areas: Vec<ExtrasRef<'a>>,
+    stop_points: Vec<ExtrasRef<'a>>,
+    calendars: Vec<ExtrasRef<'a>>,
+    companies: Vec<ExtrasRef<'a>>,
+    line_groups: Vec<ExtrasRef<'a>>,
+    calendar_dates: Vec<&'a CalendarDates>,
+    stop_times: Vec<&'a [StopTime]>,
+}
+
+/// `SkippedRef` as it is read back.
+#[derive(Deserialize)]
+struct Skipped {
+    contributors: Vec<CodesAndProperties>,
+    datasets: Vec<CodesAndProperties>,
+    networks: Vec<Extras>,
+    lines: Vec<Extras>,
+    routes: Vec<Extras>,
+    vehicle_journeys: Vec<Extras>,
+    stop_areas: Vec<Extras>,
+    stop_points: Vec<Extras>,
+    calendars: Vec<Extras>,
+    companies: Vec<Extras>,
+    line_groups: Vec<Extras>,
+    calendar_dates: Vec<CalendarDates>,
+    stop_times: Vec<Vec<StopTime>>,
+}
+
+fn extras<'a, T>(collection: &'a CollectionWithId<T>) -> Vec<ExtrasRef<'a>>
+where
+    T: Codes + ObjectProperties + CommentLinks,
+{
+    collection
+        .iter()
+        .map(|(_, obj)| (obj.codes(), obj.object_properties(), obj.comment_links()))
+        .collect()
+}
+
+fn codes_and_properties<'a, T>(collection: &'a CollectionWithId<T>) -> Vec<CodesRef<'a>>
+where
+    T: Codes + ObjectProperties,
+{
+    collection
+        .iter()
+        .map(|(_, obj)| (obj.codes(), obj.object_properties()))
+        .collect()
+}
+
+/// The error of a snapshot whose content cannot be read.
+fn invalid_snapshot(message: String) -> Error {
+    ErrorKind::InvalidValue {
+        field: "snapshot",
+        value: String::new(),
+        message,
+    }.into()
+}
+
+/// Gives to each object of `collection` its value of `values`,
+/// checking that there is one value by object.
+fn restore<T, V, F>(collection: &mut CollectionWithId<T>, values: Vec<V>, mut f: F) -> Result<()>
+where
+    T: Id<T>,
+    F: FnMut(&mut T, V),
+{
+    if values.len() != collection.len() {
+        return Err(invalid_snapshot(format!(
+            "{} values for {} objects",
+            values.len(),
+            collection.len()
+        )));
+    }
+    let indexes: Vec<_> = collection.iter().map(|(idx, _)| idx).collect();
+    for (idx, value) in indexes.into_iter().zip(values) {
+        f(&mut collection.index_mut(idx), value);
+    }
+    Ok(())
+}
+
+fn restore_extras<T>(collection: &mut CollectionWithId<T>, extras: Vec<Extras>) -> Result<()>
+where
+    T: Id<T> + Codes + ObjectProperties + CommentLinks,
+{
+    restore(collection, extras, |obj, (codes, object_properties, comment_links)| {
+        *obj.codes_mut() = codes;
+        *obj.object_properties_mut() = object_properties;
+        *obj.comment_links_mut() = comment_links;
+    })
+}
+
+fn restore_codes_and_properties<T>(
+    collection: &mut CollectionWithId<T>,
+    values: Vec<CodesAndProperties>,
+) -> Result<()>
+where
+    T: Id<T> + Codes + ObjectProperties,
+{
+    restore(collection, values, |obj, (codes, object_properties)| {
+        *obj.codes_mut() = codes;
+        *obj.object_properties_mut() = object_properties;
+    })
+}
+
+/// Writes a snapshot of `objects` in the file at `path`.
+pub fn write<P: AsRef<path::Path>>(
+    objects: &PtObjects,
+    path: P,
+    options: &WriteOptions,
+) -> error::Result<()> {
+    let path = path.as_ref();
+    info!("Writing snapshot to {:?}", path);
+    Ok(write_file(objects, path, options)?)
+}
+
+fn write_file(objects: &PtObjects, path: &path::Path, options: &WriteOptions) -> Result<()> {
+    let file = File::create(path).with_context(ctx_from_path!(path))?;
+    let mut wtr = BufWriter::new(file);
+    write_to(objects, &mut wtr, options).with_context(ctx_from_path!(path))?;
+    wtr.flush().with_context(ctx_from_path!(path))?;
+    Ok(())
+}
+
+/// Writes a snapshot of `objects` to `wtr`.
+pub fn write_to<W: Write>(objects: &PtObjects, mut wtr: W, options: &WriteOptions) -> Result<()> {
+    let c = &objects.collections;
+    let skipped = SkippedRef {
+        contributors: codes_and_properties(&c.contributors),
+        datasets: codes_and_properties(&c.datasets),
+        networks: extras(&c.networks),
+        lines: extras(&c.lines),
+        routes: extras(&c.routes),
+        vehicle_journeys: extras(&c.vehicle_journeys),
+        stop_areas: extras(&c.stop_areas),
+        stop_points: extras(&c.stop_points),
+        calendars: extras(&c.calendars),
+        companies: extras(&c.companies),
+        line_groups: extras(&c.line_groups),
+        calendar_dates: c.calendars.iter().map(|(_, cal)| &cal.calendar_dates).collect(),
+        stop_times: c.vehicle_journeys
+            .iter()
+            .map(|(_, vj)| vj.stop_times.as_slice())
+            .collect(),
+    };
+    wtr.write_all(MAGIC)?;
+    wtr.write_all(&VERSION.to_le_bytes())?;
+    encoding().serialize_into(&mut wtr, c)?;
+    encoding().serialize_into(&mut wtr, &skipped)?;
+    encoding().serialize_into(&mut wtr, &options.relations)?;
+    if options.relations {
+        write_relations(objects, &mut wtr)?;
+    }
+    Ok(())
+}
+
+// in the order of the fields of `PtObjects`, as `read_relations`
+fn write_relations<W: Write>(o: &PtObjects, mut wtr: W) -> Result<()> {
+    encoding().serialize_into(&mut wtr, &o.networks_to_lines)?;
+    encoding().serialize_into(&mut wtr, &o.commercial_modes_to_lines)?;
+    encoding().serialize_into(&mut wtr, &o.lines_to_routes)?;
+    encoding().serialize_into(&mut wtr, &o.routes_to_vehicle_journeys)?;
+    encoding().serialize_into(&mut wtr, &o.physical_modes_to_vehicle_journeys)?;
+    encoding().serialize_into(&mut wtr, &o.stop_areas_to_stop_points)?;
+    encoding().serialize_into(&mut wtr, &o.contributors_to_datasets)?;
+    encoding().serialize_into(&mut wtr, &o.datasets_to_vehicle_journeys)?;
+    encoding().serialize_into(&mut wtr, &o.companies_to_vehicle_journeys)?;
+    encoding().serialize_into(&mut wtr, &o.vehicle_journeys_to_stop_points)?;
+    encoding().serialize_into(&mut wtr, &o.transfers_to_stop_points)?;
+    encoding().serialize_into(&mut wtr, &o.grid_calendars_to_lines)?;
+    encoding().serialize_into(&mut wtr, &o.routes_to_stop_points)?;
+    encoding().serialize_into(&mut wtr, &o.physical_modes_to_stop_points)?;
+    encoding().serialize_into(&mut wtr, &o.physical_modes_to_routes)?;
+    encoding().serialize_into(&mut wtr, &o.datasets_to_stop_points)?;
+    encoding().serialize_into(&mut wtr, &o.datasets_to_routes)?;
+    encoding().serialize_into(&mut wtr, &o.datasets_to_physical_modes)?;
+    Ok(())
+}
+
+/// Reads the snapshot in the file at `path`.
+pub fn read<P: AsRef<path::Path>>(path: P) -> error::Result<PtObjects> {
+    let path = path.as_ref();
+    info!("Reading snapshot from {:?}", path);
+    Ok(read_file(path)?)
+}
+
+fn read_file(path: &path::Path) -> Result<PtObjects> {
+    let file = File::open(path).with_context(ctx_from_path!(path))?;
+    Ok(read_from(BufReader::new(file)).with_context(ctx_from_path!(path))?)
+}
+
+/// Reads a snapshot from `rdr`, rebuilding the relations if they were
+/// not saved.
+pub fn read_from<R: Read>(mut rdr: R) -> Result<PtObjects> {
+    let mut magic = [0; 8];
+    rdr.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_snapshot("not a snapshot".to_string()));
+    }
+    let mut version = [0; 4];
+    rdr.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != VERSION {
+        return Err(ErrorKind::InvalidValue {
+            field: "snapshot_version",
+            value: version.to_string(),
+            message: format!(
+                "reading snapshot version {} is not supported, expecting version {}",
+                version, VERSION
+            ),
+        }.into());
+    }
+
+    let mut c: Collections = encoding().deserialize_from(&mut rdr)?;
+    let skipped: Skipped = encoding().deserialize_from(&mut rdr)?;
+    restore_codes_and_properties(&mut c.contributors, skipped.contributors)?;
+    restore_codes_and_properties(&mut c.datasets, skipped.datasets)?;
+    restore_extras(&mut c.networks, skipped.networks)?;
+    restore_extras(&mut c.lines, skipped.lines)?;
+    restore_extras(&mut c.routes, skipped.routes)?;
+    restore_extras(&mut c.vehicle_journeys, skipped.vehicle_journeys)?;
+    restore_extras(&mut c.stop_areas, skipped.stop_areas)?;
+    restore_extras(&mut c.stop_points, skipped.stop_points)?;
+    restore_extras(&mut c.calendars, skipped.calendars)?;
+    restore_extras(&mut c.companies, skipped.companies)?;
+    restore_extras(&mut c.line_groups, skipped.line_groups)?;
+    restore(&mut c.calendars, skipped.calendar_dates, |cal, dates| {
+        cal.calendar_dates = dates
+    })?;
+    restore(&mut c.vehicle_journeys, skipped.stop_times, |vj, stop_times| {
+        vj.stop_times = stop_times
+    })?;
+    check_indexes(&c)?;
+
+    let with_relations: bool = encoding().deserialize_from(&mut rdr)?;
+    if with_relations {
+        read_relations(c, rdr)
+    } else {
+        info!("Indexing");
+        PtObjects::new(c)
+    }
+}
+
+/// Checks that the indexes of the collections are valid, as they
+/// are not checked by their deserialization.
+fn check_indexes(c: &Collections) -> Result<()> {
+    let nb_comments = c.comments.len();
+    let valid_links = |links: &[Idx<Comment>]| links.iter().all(|idx| idx.get() < nb_comments);
+    let valid = c.networks.iter().all(|(_, o)| valid_links(&o.comment_links))
+        && c.lines.iter().all(|(_, o)| valid_links(&o.comment_links))
+        && c.routes.iter().all(|(_, o)| valid_links(&o.comment_links))
+        && c.vehicle_journeys.iter().all(|(_, o)| valid_links(&o.comment_links))
+        && c.stop_areas.iter().all(|(_, o)| valid_links(&o.comment_links))
+        && c.stop_points.iter().all(|(_, o)| valid_links(&o.comment_links))
+        && c.calendars.iter().all(|(_, o)| valid_links(&o.comment_links))
+        && c.companies.iter().all(|(_, o)| valid_links(&o.comment_links))
+        && c.line_groups.iter().all(|(_, o)| valid_links(&o.comment_links))
+        && c.vehicle_journeys.iter().all(|(_, vj)| {
+            vj.stop_times.iter().all(|st| {
+                st.stop_point_idx.get() < c.stop_points.len() && valid_links(st.comment_links())
+            })
+        });
+    if !valid {
+        return Err(invalid_snapshot("invalid index in the snapshot".to_string()));
+    }
+    Ok(())
+}
+
+fn read_relations<R: Read>(c: Collections, mut rdr: R) -> Result<PtObjects> {
+    let objects = PtObjects {
+        networks_to_lines: encoding().deserialize_from(&mut rdr)?,
+        commercial_modes_to_lines: encoding().deserialize_from(&mut rdr)?,
+        lines_to_routes: encoding().deserialize_from(&mut rdr)?,
+        routes_to_vehicle_journeys: encoding().deserialize_from(&mut rdr)?,
+        physical_modes_to_vehicle_journeys: encoding().deserialize_from(&mut rdr)?,
+        stop_areas_to_stop_points: encoding().deserialize_from(&mut rdr)?,
+        contributors_to_datasets: encoding().deserialize_from(&mut rdr)?,
+        datasets_to_vehicle_journeys: encoding().deserialize_from(&mut rdr)?,
+        companies_to_vehicle_journeys: encoding().deserialize_from(&mut rdr)?,
+        vehicle_journeys_to_stop_points: encoding().deserialize_from(&mut rdr)?,
+        transfers_to_stop_points: encoding().deserialize_from(&mut rdr)?,
+        grid_calendars_to_lines: encoding().deserialize_from(&mut rdr)?,
+        routes_to_stop_points: encoding().deserialize_from(&mut rdr)?,
+        physical_modes_to_stop_points: encoding().deserialize_from(&mut rdr)?,
+        physical_modes_to_routes: encoding().deserialize_from(&mut rdr)?,
+        datasets_to_stop_points: encoding().deserialize_from(&mut rdr)?,
+        datasets_to_routes: encoding().deserialize_from(&mut rdr)?,
+        datasets_to_physical_modes: encoding().deserialize_from(&mut rdr)?,
+        collections: c,
+    };
+    let o = &objects;
+    let c = &o.collections;
+    let valid = o.networks_to_lines.is_within(c.networks.len(), c.lines.len())
+        && o.commercial_modes_to_lines
+            .is_within(c.commercial_modes.len(), c.lines.len())
+        && o.lines_to_routes.is_within(c.lines.len(), c.routes.len())
+        && o.routes_to_vehicle_journeys
+            .is_within(c.routes.len(), c.vehicle_journeys.len())
+        && o.physical_modes_to_vehicle_journeys
+            .is_within(c.physical_modes.len(), c.vehicle_journeys.len())
+        && o.stop_areas_to_stop_points
+            .is_within(c.stop_areas.len(), c.stop_points.len())
+        && o.contributors_to_datasets
+            .is_within(c.contributors.len(), c.datasets.len())
+        && o.datasets_to_vehicle_journeys
+            .is_within(c.datasets.len(), c.vehicle_journeys.len())
+        && o.companies_to_vehicle_journeys
+            .is_within(c.companies.len(), c.vehicle_journeys.len())
+        && o.vehicle_journeys_to_stop_points
+            .is_within(c.vehicle_journeys.len(), c.stop_points.len())
+        && o.transfers_to_stop_points
+            .is_within(c.transfers.iter().len(), c.stop_points.len())
+        && o.grid_calendars_to_lines
+            .is_within(c.grid_calendars.len(), c.lines.len())
+        && o.routes_to_stop_points
+            .is_within(c.routes.len(), c.stop_points.len())
+        && o.physical_modes_to_stop_points
+            .is_within(c.physical_modes.len(), c.stop_points.len())
+        && o.physical_modes_to_routes
+            .is_within(c.physical_modes.len(), c.routes.len())
+        && o.datasets_to_stop_points
+            .is_within(c.datasets.len(), c.stop_points.len())
+        && o.datasets_to_routes.is_within(c.datasets.len(), c.routes.len())
+        && o.datasets_to_physical_modes
+            .is_within(c.datasets.len(), c.physical_modes.len());
+    if !valid {
+        let message = "invalid index in the relations of the snapshot".to_string();
+        return Err(invalid_snapshot(message));
+    }
+    Ok(objects)
+}
@@ -0,0 +1,181 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! Consistency of the stop times of the vehicle journeys.
+
+use collection::CollectionWithId;
+use error::ErrorKind;
+use objects::*;
+use {Collections, Error, Result};
+
+/// The speed above which the travel between two stops is implausible,
+/// in meters per second (500 km/h).
+pub const MAX_SPEED: f64 = 500. / 3.6;
+
+/// The shortest duration of a travel between two stops when computing
+/// its speed, in seconds, as the times are often rounded to the minute.
+const MIN_TRAVEL_DURATION: u32 = 60;
+
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum StopTimeIssueKind {
+    /// The arrival is before the departure from the previous stop.
+    DecreasingTimes,
+    DepartureBeforeArrival,
+    /// The sequence is the one of the previous stop time.
+    DuplicateSequence,
+    /// The travel from the previous stop is faster than `MAX_SPEED`.
+    ImplausibleSpeed,
+    /// The vehicle journey has only one stop time.
+    SingleStop,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct StopTimeIssue {
+    pub vehicle_journey_id: String,
+    /// The sequence of the faulty stop time, if the issue is not about
+    /// the whole vehicle journey.
+    pub sequence: Option<u32>,
+    pub kind: StopTimeIssueKind,
+}
+
+/// The issues of the stop times of `vj`, that are sorted by sequence.
+/// The speed is not checked from or to a stop without coordinate.
+pub fn check_stop_times(
+    vj: &VehicleJourney,
+    stop_points: &CollectionWithId<StopPoint>,
+) -> Vec<StopTimeIssue> {
+    let issue = |sequence, kind| StopTimeIssue {
+        vehicle_journey_id: vj.id.clone(),
+        sequence,
+        kind,
+    };
+    let mut issues = vec![];
+    if vj.stop_times.len() == 1 {
+        issues.push(issue(None, StopTimeIssueKind::SingleStop));
+    }
+    for (i, st) in vj.stop_times.iter().enumerate() {
+        let sequence = Some(st.sequence);
+        if st.departure_time < st.arrival_time {
+            issues.push(issue(sequence, StopTimeIssueKind::DepartureBeforeArrival));
+        }
+        let previous = match i.checked_sub(1) {
+            Some(i) => &vj.stop_times[i],
+            None => continue,
+        };
+        if st.sequence == previous.sequence {
+            issues.push(issue(sequence, StopTimeIssueKind::DuplicateSequence));
+        }
+        if st.arrival_time < previous.departure_time {
+            issues.push(issue(sequence, StopTimeIssueKind::DecreasingTimes));
+            continue;
+        }
+        let from = &stop_points[previous.stop_point_idx].coord;
+        let to = &stop_points[st.stop_point_idx].coord;
+        if from.is_unknown() || to.is_unknown() {
+            continue;
+        }
+        let duration = (st.arrival_time - previous.departure_time).num_seconds();
+        let distance = from.distance_to(to);
+        if distance / duration.max(i64::from(MIN_TRAVEL_DURATION)) as f64 > MAX_SPEED {
+            issues.push(issue(sequence, StopTimeIssueKind::ImplausibleSpeed));
+        }
+    }
+    issues
+}
+
+/// Gives a time to the stop times of `stop_times` for which `missing`
+/// is true, interpolated from the surrounding stop times with a time
+/// in proportion to the distance travelled, and marks them as
+/// estimated.  `missing` has a flag by stop time, and the first and
+/// the last stop times must have a time.
+pub fn interpolate_times(
+    stop_times: &mut [StopTime],
+    missing: &[bool],
+    stop_points: &CollectionWithId<StopPoint>,
+) -> Result<()> {
+    ensure!(
+        missing.len() == stop_times.len(),
+        "{} missing time flags given for {} stop times",
+        missing.len(),
+        stop_times.len()
+    );
+    if stop_times.is_empty() {
+        return Ok(());
+    }
+    let mut known = (0..stop_times.len()).filter(|&i| !missing[i]);
+    let mut start = match known.next() {
+        Some(0) => 0,
+        _ => return Err(invalid_time("the first stop time has no time".to_string())),
+    };
+    for end in known {
+        if end > start + 1 {
+            let coord = |i: usize| &stop_points[stop_times[i].stop_point_idx].coord;
+            let distances: Vec<f64> = (start..end)
+                .scan(0., |distance, i| {
+                    *distance += coord(i).distance_to(coord(i + 1));
+                    Some(*distance)
+                })
+                .collect();
+            let total = distances[distances.len() - 1];
+            let departure = stop_times[start].departure_time;
+            let duration = (stop_times[end].arrival_time - departure).num_seconds();
+            if duration < 0 {
+                return Err(invalid_time(format!(
+                    "the time of stop {} is before the one of stop {}",
+                    stop_times[end].sequence, stop_times[start].sequence
+                )));
+            }
+            for (n, i) in (start + 1..end).enumerate() {
+                let ratio = if total > 0. {
+                    distances[n] / total
+                } else {
+                    (n + 1) as f64 / (end - start) as f64
+                };
+                let offset = (duration as f64 * ratio).round() as i64;
+                let time = departure + TimeOffset::seconds(offset);
+                let st = &mut stop_times[i];
+                st.arrival_time = time;
+                st.departure_time = time;
+                st.datetime_estimated = true;
+            }
+        }
+        start = end;
+    }
+    if start + 1 != stop_times.len() {
+        return Err(invalid_time("the last stop time has no time".to_string()));
+    }
+    Ok(())
+}
+
+fn invalid_time(message: String) -> Error {
+    ErrorKind::InvalidValue {
+        field: "arrival_time",
+        value: String::new(),
+        message,
+    }.into()
+}
+
+impl Collections {
+    /// The issues of the stop times of all the vehicle journeys (see
+    /// `check_stop_times`).
+    pub fn validate_stop_times(&self) -> Vec<StopTimeIssue> {
+        self.vehicle_journeys
+            .iter()
+            .flat_map(|(_, vj)| check_stop_times(vj, &self.stop_points))
+            .collect()
+    }
+}
@@ -0,0 +1,251 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! Timezones of the vehicle journeys, and UTC instants of their stop
+//! times.
+
+use chrono::offset::LocalResult;
+use chrono::{DateTime, Duration, Offset, TimeZone, Utc};
+use collection::Idx;
+use objects::*;
+use relations::IdxSet;
+use validity_pattern::ValidityPattern;
+use {PtObjects, Result};
+
+/// The UTC instant of the local `time` of the service day `date` in
+/// `timezone`.  A time in the hour skipped when clocks go forward is
+/// shifted by the length of the gap (02:30 is 03:30 when clocks go
+/// from 02:00 to 03:00), and a time in the hour repeated when clocks
+/// go back is its first occurrence.
+pub fn to_utc(timezone: Timezone, date: Date, time: Time) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+    let local = midnight + Duration::from(time);
+    let datetime = match timezone.from_local_datetime(&local) {
+        LocalResult::Single(datetime) | LocalResult::Ambiguous(datetime, _) => datetime,
+        LocalResult::None => {
+            // the offset before the gap, as there is no change the day before
+            let offset = timezone.offset_from_utc_datetime(&(local - Duration::days(1)));
+            let offset = Duration::seconds(i64::from(offset.fix().local_minus_utc()));
+            timezone.from_utc_datetime(&(local - offset))
+        }
+    };
+    datetime.with_timezone(&Utc)
+}
+
+impl PtObjects {
+    /// The timezone of the network of the vehicle journey.
+    pub fn network_timezone(&self, vj_idx: Idx<VehicleJourney>) -> Option<Timezone> {
+        let vj_idxs: IdxSet<_> = Some(vj_idx).into_iter().collect();
+        let networks: IdxSet<Network> = self.get_corresponding(&vj_idxs);
+        networks
+            .iter()
+            .filter_map(|&idx| self.networks[idx].timezone)
+            .next()
+    }
+
+    /// The timezone of the stop point, or else of its stop area.
+    pub fn stop_point_timezone(&self, sp_idx: Idx<StopPoint>) -> Option<Timezone> {
+        let stop_point = &self.stop_points[sp_idx];
+        stop_point.timezone.or_else(|| {
+            self.stop_areas
+                .get(&stop_point.stop_area_id)
+                .and_then(|sa| sa.timezone)
+        })
+    }
+
+    /// The timezone in which the times of the vehicle journey are
+    /// expressed: the one of its first stop, or else of its network.
+    pub fn vehicle_journey_timezone(&self, vj_idx: Idx<VehicleJourney>) -> Option<Timezone> {
+        self.vehicle_journeys[vj_idx]
+            .stop_times
+            .first()
+            .and_then(|st| self.stop_point_timezone(st.stop_point_idx))
+            .or_else(|| self.network_timezone(vj_idx))
+    }
+
+    /// The UTC arrival and departure instants of `stop_time`, a stop
+    /// time of the vehicle journey running on the service day `date`
+    /// (see `to_utc`).
+    pub fn stop_time_to_utc(
+        &self,
+        vj_idx: Idx<VehicleJourney>,
+        date: Date,
+        stop_time: &StopTime,
+    ) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+        let timezone = self.vehicle_journey_timezone(vj_idx).ok_or_else(|| {
+            format_err!(
+                "no timezone for vehicle journey {:?}",
+                self.vehicle_journeys[vj_idx].id
+            )
+        })?;
+        Ok((
+            to_utc(timezone, date, stop_time.arrival_time),
+            to_utc(timezone, date, stop_time.departure_time),
+        ))
+    }
+}
+
+/// The arrival and departure times of a vehicle journey, with the
+/// service days on which it runs with these times.
+struct Circulation {
+    times: Vec<(Time, Time)>,
+    dates: Vec<Date>,
+}
+
+impl PtObjects {
+    /// The circulations of the vehicle journey expressed in
+    /// `reference`, or `None` if they are the ones of the vehicle
+    /// journey.  The times of a stop are in the timezone of the stop,
+    /// and the service days in the one of the network, `reference`
+    /// being used when there is no such timezone.
+    fn circulations_in(
+        &self,
+        vj_idx: Idx<VehicleJourney>,
+        reference: Timezone,
+    ) -> Result<Option<Vec<Circulation>>> {
+        let vj = &self.vehicle_journeys[vj_idx];
+        let dates = match self.calendars.get(&vj.service_id) {
+            Some(calendar) if !vj.stop_times.is_empty() => calendar.dates(),
+            _ => return Ok(None),
+        };
+        let network_timezone = self.network_timezone(vj_idx).unwrap_or(reference);
+        let timezones: Vec<_> = vj.stop_times
+            .iter()
+            .map(|st| self.stop_point_timezone(st.stop_point_idx).unwrap_or(network_timezone))
+            .collect();
+        let mut circulations: Vec<Circulation> = vec![];
+        for &date in &dates {
+            let instants: Vec<_> = vj.stop_times
+                .iter()
+                .zip(&timezones)
+                .map(|(st, &tz)| {
+                    (
+                        to_utc(tz, date, st.arrival_time),
+                        to_utc(tz, date, st.departure_time),
+                    )
+                })
+                .collect();
+            // the times keep the durations between the stops, from the
+            // local time of the first arrival
+            let first = instants[0].0;
+            let local = first.with_timezone(&reference).naive_local();
+            let service_date = local.date();
+            let origin = local - service_date.and_hms_opt(0, 0, 0).expect("valid midnight");
+            let time = |instant: DateTime<Utc>| {
+                Time::from_duration(origin + (instant - first)).ok_or_else(|| {
+                    format_err!("vehicle journey {:?} runs before its first arrival", vj.id)
+                })
+            };
+            let times = instants
+                .iter()
+                .map(|&(arrival, departure)| Ok((time(arrival)?, time(departure)?)))
+                .collect::<Result<Vec<_>>>()?;
+            match circulations.iter_mut().find(|c| c.times == times) {
+                Some(circulation) => circulation.dates.push(service_date),
+                None => circulations.push(Circulation {
+                    times,
+                    dates: vec![service_date],
+                }),
+            }
+        }
+        let unchanged = match circulations.as_slice() {
+            [] => true,
+            [circulation] => {
+                circulation.dates == dates
+                    && vj.stop_times
+                        .iter()
+                        .map(|st| (st.arrival_time, st.departure_time))
+                        .eq(circulation.times.iter().cloned())
+            }
+            _ => false,
+        };
+        Ok(if unchanged { None } else { Some(circulations) })
+    }
+
+    /// Expresses the times and the calendars of all the vehicle
+    /// journeys in the `reference` timezone (see `to_utc` for the
+    /// timezone of the times).  When the offset between the timezones
+    /// changes during the validity period of a vehicle journey, as
+    /// for a DST change, it is split: the new vehicle journeys get the
+    /// id `{id}:{n}`, and the new calendars the id
+    /// `{service_id}:{vehicle journey id}`; the stop times of the new
+    /// vehicle journeys get the id `{stop_time_id}:{n}`.  The new
+    /// calendars are not merged with the existing ones (see
+    /// `Collections::compress_calendars`).
+    pub fn normalize_timezones(self, reference: Timezone) -> Result<PtObjects> {
+        let mut changes = vec![];
+        for (vj_idx, _) in self.vehicle_journeys.iter() {
+            if let Some(circulations) = self.circulations_in(vj_idx, reference)? {
+                changes.push((vj_idx, circulations));
+            }
+        }
+        info!(
+            "Normalizing the timezone of {} vehicle journeys",
+            changes.len()
+        );
+        self.edit(|c| {
+            for (vj_idx, circulations) in changes {
+                let vj = c.vehicle_journeys[vj_idx].clone();
+                let dates = c.calendars
+                    .get(&vj.service_id)
+                    .expect("calendar of a changed vehicle journey")
+                    .dates();
+                for (i, circulation) in circulations.into_iter().enumerate() {
+                    let id = match i {
+                        0 => vj.id.clone(),
+                        _ => format!("{}:{}", vj.id, i),
+                    };
+                    let service_id = if circulation.dates == dates {
+                        vj.service_id.clone()
+                    } else {
+                        let service_id = format!("{}:{}", vj.service_id, id);
+                        let first_date = circulation.dates[0];
+                        let mut vp = ValidityPattern::new(first_date, first_date);
+                        for &date in &circulation.dates {
+                            vp.set(date, true);
+                        }
+                        c.calendars
+                            .push(Calendar::from_validity_pattern(service_id.clone(), &vp))?;
+                        service_id
+                    };
+                    let mut stop_times = vj.stop_times.clone();
+                    for (st, times) in stop_times.iter_mut().zip(&circulation.times) {
+                        st.arrival_time = times.0;
+                        st.departure_time = times.1;
+                        if i > 0 {
+                            if let Some(ref mut extras) = st.extras {
+                                extras.id = extras.id.take().map(|id| format!("{}:{}", id, i));
+                            }
+                        }
+                    }
+                    if i == 0 {
+                        let mut vj = c.vehicle_journeys.index_mut(vj_idx);
+                        vj.service_id = service_id;
+                        vj.stop_times = stop_times;
+                        continue;
+                    }
+                    c.vehicle_journeys.push(VehicleJourney {
+                        id,
+                        service_id,
+                        stop_times,
+                        ..vj.clone()
+                    })?;
+                }
+            }
+            Ok(())
+        }).map_err(|e| e.error)
+    }
+}
@@ -60,6 +60,16 @@ where
     Option::<T>::deserialize(de).map(|opt| opt.unwrap_or_else(Default::default))
 }
 
+/// Serializes a value read with `de_with_empty_default` as the option
+/// it is read from, for the formats distinguishing them.
+pub fn ser_as_some<T, S>(v: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: ::serde::Serialize,
+    S: ::serde::Serializer,
+{
+    serializer.serialize_some(v)
+}
+
 pub fn de_invalid_option<'de, D, T>(de: D) -> Result<Option<T>, D::Error>
 where
     D: ::serde::Deserializer<'de>,
@@ -68,6 +78,25 @@ where
     use serde::Deserialize;
     Option::<T>::deserialize(de).or_else(|e| {
         error!("{}", e);
+        ::read_report::ignore_value(e.to_string());
+        Ok(None)
+    })
+}
+
+/// As `de_invalid_option`, but failing on an invalid value when the
+/// reading is strict (see `read_report::Strictness`).
+pub fn de_lenient_option<'de, D, T>(de: D) -> Result<Option<T>, D::Error>
+where
+    D: ::serde::Deserializer<'de>,
+    Option<T>: ::serde::Deserialize<'de>,
+{
+    use serde::Deserialize;
+    Option::<T>::deserialize(de).or_else(|e| {
+        if ::read_report::is_strict() {
+            return Err(e);
+        }
+        error!("{}", e);
+        ::read_report::ignore_value(e.to_string());
         Ok(None)
     })
 }
@@ -75,6 +104,45 @@ where
 #[macro_export]
 macro_rules! ctx_from_path {
     ($path: expr) => {
-        |_| format!("Error reading {:?}", $path)
+        |e| $crate::error::PathContext::new(&$path, e)
     };
 }
+
+/// Returns the names of the fields expected when deserializing `T`,
+/// with their serde renaming applied.  `T` must be a struct.
+pub fn struct_fields<T>() -> &'static [&'static str]
+where
+    for<'de> T: ::serde::Deserialize<'de>,
+{
+    use serde::de::{self, Visitor};
+
+    struct FieldsDeserializer<'a> {
+        fields: &'a mut &'static [&'static str],
+    }
+    impl<'de, 'a> ::serde::Deserializer<'de> for FieldsDeserializer<'a> {
+        type Error = de::value::Error;
+        fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("only structs have fields"))
+        }
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.fields = fields;
+            Err(de::Error::custom("fields collected"))
+        }
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes
+            byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldsDeserializer {
+        fields: &mut fields,
+    });
+    fields
+}
@@ -0,0 +1,373 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! Validation of the references and of the values of a model.
+
+use collection::{CollectionWithId, Id};
+use objects::*;
+use provenance::Source;
+use read_report::{ReadIssue, ReadReport};
+use stop_times::StopTimeIssueKind;
+use Collections;
+
+#[derive(Serialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The object is suspicious, but usable.
+    Warning,
+    /// The object is invalid.
+    Error,
+}
+
+#[derive(Serialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// The field references an object that does not exist.
+    UnknownReference,
+    /// The value of the field is out of its domain.
+    InvalidValue,
+    /// The stop times of the vehicle journey are not consistent.
+    StopTimes(StopTimeIssueKind),
+    /// The row could not be read, and was skipped.
+    SkippedRow,
+    /// An invalid value was read as empty.
+    IgnoredValue,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub kind: IssueKind,
+    /// The NTFS file of the object.
+    pub file: String,
+    /// Empty for the issues of the reading, as its rows are unknown.
+    pub object_id: String,
+    /// Empty for the issues of the reading.
+    pub field: &'static str,
+    pub message: String,
+    /// The row of the object, if the provenance was tracked when
+    /// reading the model.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+}
+
+/// The issues found by `Collections::validate`.
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct ValidationReport {
+    pub issues: Vec<Issue>,
+}
+
+impl ValidationReport {
+    /// Whether no issue is an error.
+    pub fn is_valid(&self) -> bool {
+        self.issues.iter().all(|i| i.severity < Severity::Error)
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.issues.iter().filter(|i| i.severity == severity).count()
+    }
+
+    /// Adds as errors the rows skipped and the values ignored when
+    /// reading the model in lenient mode (see `read_report::Strictness`).
+    pub fn add_read_report(&mut self, report: ReadReport) {
+        let skipped = report.skipped_rows.into_iter().map(|i| (IssueKind::SkippedRow, i));
+        let ignored = report.ignored_values.into_iter().map(|i| (IssueKind::IgnoredValue, i));
+        for (kind, issue) in skipped.chain(ignored) {
+            let ReadIssue {
+                file,
+                line,
+                message,
+            } = issue;
+            let source = line.map(|line| Source {
+                file: file.clone(),
+                line,
+            });
+            self.issues.push(Issue {
+                severity: Severity::Error,
+                kind,
+                file,
+                object_id: String::new(),
+                field: "",
+                message,
+                source,
+            });
+        }
+    }
+
+    fn push<S: Into<String>>(
+        &mut self,
+        severity: Severity,
+        kind: IssueKind,
+        file: &'static str,
+        object_id: S,
+        field: &'static str,
+        message: String,
+    ) {
+        self.issues.push(Issue {
+            severity,
+            kind,
+            file: file.to_string(),
+            object_id: object_id.into(),
+            field,
+            message,
+            source: None,
+        });
+    }
+
+    fn check_ref<T: Id<T>>(
+        &mut self,
+        file: &'static str,
+        object_id: &str,
+        field: &'static str,
+        id: &str,
+        collection: &CollectionWithId<T>,
+    ) {
+        if collection.get_idx(id).is_none() {
+            self.push(
+                Severity::Error,
+                IssueKind::UnknownReference,
+                file,
+                object_id,
+                field,
+                format!("{}={:?} not found", field, id),
+            );
+        }
+    }
+
+    fn check_opt_ref<T: Id<T>>(
+        &mut self,
+        file: &'static str,
+        object_id: &str,
+        field: &'static str,
+        id: &Option<String>,
+        collection: &CollectionWithId<T>,
+    ) {
+        if let Some(ref id) = *id {
+            self.check_ref(file, object_id, field, id, collection);
+        }
+    }
+
+    fn check_coord(&mut self, file: &'static str, object_id: &str, coord: &Coord) {
+        let field = if !(-90. ..=90.).contains(&coord.lat) {
+            Some("stop_lat")
+        } else if !(-180. ..=180.).contains(&coord.lon) {
+            Some("stop_lon")
+        } else {
+            None
+        };
+        if let Some(field) = field {
+            self.push(
+                Severity::Error,
+                IssueKind::InvalidValue,
+                file,
+                object_id,
+                field,
+                format!("({}, {}) is not a valid coordinate", coord.lon, coord.lat),
+            );
+        } else if coord.is_unknown() {
+            self.push(
+                Severity::Warning,
+                IssueKind::InvalidValue,
+                file,
+                object_id,
+                "stop_lon",
+                "the coordinate is (0, 0)".to_string(),
+            );
+        }
+    }
+
+    fn check_period(
+        &mut self,
+        file: &'static str,
+        object_id: &str,
+        field: &'static str,
+        start_date: Date,
+        end_date: Date,
+    ) {
+        if end_date < start_date {
+            self.push(
+                Severity::Error,
+                IssueKind::InvalidValue,
+                file,
+                object_id,
+                field,
+                format!("{} is before the start date {}", end_date, start_date),
+            );
+        }
+    }
+}
+
+impl Collections {
+    /// Checks the references between the objects, the values of their
+    /// fields and the stop times of the vehicle journeys (see
+    /// `Collections::validate_stop_times`).
+    pub fn validate(&self) -> ValidationReport {
+        let mut r = ValidationReport::default();
+
+        for (_, o) in self.datasets.iter() {
+            let f = "datasets.txt";
+            r.check_ref(f, &o.id, "contributor_id", &o.contributor_id, &self.contributors);
+            r.check_period(f, &o.id, "dataset_end_date", o.start_date, o.end_date);
+        }
+        for (_, o) in self.lines.iter() {
+            let f = "lines.txt";
+            r.check_ref(f, &o.id, "network_id", &o.network_id, &self.networks);
+            let commercial_mode_id = &o.commercial_mode_id;
+            r.check_ref(f, &o.id, "commercial_mode_id", commercial_mode_id, &self.commercial_modes);
+            r.check_opt_ref(f, &o.id, "geometry_id", &o.geometry_id, &self.geometries);
+            if o.color.is_some() && o.color == o.text_color {
+                r.push(
+                    Severity::Warning,
+                    IssueKind::InvalidValue,
+                    f,
+                    o.id.as_str(),
+                    "line_text_color",
+                    "the text color is the line color".to_string(),
+                );
+            }
+        }
+        for (_, o) in self.routes.iter() {
+            let f = "routes.txt";
+            r.check_ref(f, &o.id, "line_id", &o.line_id, &self.lines);
+            r.check_opt_ref(f, &o.id, "geometry_id", &o.geometry_id, &self.geometries);
+            r.check_opt_ref(f, &o.id, "destination_id", &o.destination_id, &self.stop_areas);
+        }
+        for (_, o) in self.vehicle_journeys.iter() {
+            let f = "trips.txt";
+            r.check_ref(f, &o.id, "route_id", &o.route_id, &self.routes);
+            r.check_ref(f, &o.id, "physical_mode_id", &o.physical_mode_id, &self.physical_modes);
+            r.check_ref(f, &o.id, "dataset_id", &o.dataset_id, &self.datasets);
+            r.check_ref(f, &o.id, "service_id", &o.service_id, &self.calendars);
+            r.check_ref(f, &o.id, "company_id", &o.company_id, &self.companies);
+            let trip_property_id = &o.trip_property_id;
+            r.check_opt_ref(f, &o.id, "trip_property_id", trip_property_id, &self.trip_properties);
+            r.check_opt_ref(f, &o.id, "geometry_id", &o.geometry_id, &self.geometries);
+        }
+        for (_, o) in self.stop_areas.iter() {
+            let f = "stops.txt";
+            r.check_coord(f, &o.id, &o.coord);
+            r.check_opt_ref(f, &o.id, "geometry_id", &o.geometry_id, &self.geometries);
+            r.check_opt_ref(f, &o.id, "equipment_id", &o.equipment_id, &self.equipments);
+        }
+        for (_, o) in self.stop_points.iter() {
+            let f = "stops.txt";
+            r.check_coord(f, &o.id, &o.coord);
+            r.check_ref(f, &o.id, "parent_station", &o.stop_area_id, &self.stop_areas);
+            r.check_opt_ref(f, &o.id, "geometry_id", &o.geometry_id, &self.geometries);
+            r.check_opt_ref(f, &o.id, "equipment_id", &o.equipment_id, &self.equipments);
+        }
+        for (_, o) in self.calendars.iter() {
+            r.check_period("calendar.txt", &o.id, "end_date", o.start_date, o.end_date);
+        }
+        for (_, o) in self.transfers.iter() {
+            let f = "transfers.txt";
+            let id = format!("{}-{}", o.from_stop_id, o.to_stop_id);
+            r.check_ref(f, &id, "from_stop_id", &o.from_stop_id, &self.stop_points);
+            r.check_ref(f, &id, "to_stop_id", &o.to_stop_id, &self.stop_points);
+            r.check_opt_ref(f, &id, "equipment_id", &o.equipment_id, &self.equipments);
+        }
+        for (_, o) in self.admin_stations.iter() {
+            if self.stop_areas.get_idx(&o.stop_id).is_none()
+                && self.stop_points.get_idx(&o.stop_id).is_none()
+            {
+                r.push(
+                    Severity::Error,
+                    IssueKind::UnknownReference,
+                    "admin_stations.txt",
+                    o.admin_id.as_str(),
+                    "stop_id",
+                    format!("stop_id={:?} not found", o.stop_id),
+                );
+            }
+        }
+        for (_, o) in self.grid_rel_calendar_line.iter() {
+            let f = "grid_rel_calendar_line.txt";
+            let id = &o.grid_calendar_id;
+            r.check_ref(f, id, "grid_calendar_id", id, &self.grid_calendars);
+            if o.line_idx(&self.lines).is_none() {
+                let field = if o.line_id.is_empty() {
+                    "line_external_code"
+                } else {
+                    "line_id"
+                };
+                let message = format!("{}={:?} not found", field, o.line_ref());
+                r.push(Severity::Error, IssueKind::UnknownReference, f, id, field, message);
+            }
+        }
+        for (_, o) in self.grid_exception_dates.iter() {
+            let f = "grid_exception_dates.txt";
+            let id = &o.grid_calendar_id;
+            r.check_ref(f, id, "grid_calendar_id", id, &self.grid_calendars);
+        }
+        for (_, o) in self.grid_periods.iter() {
+            let f = "grid_periods.txt";
+            let id = &o.grid_calendar_id;
+            r.check_ref(f, id, "grid_calendar_id", id, &self.grid_calendars);
+            r.check_period(f, id, "end_date", o.start_date, o.end_date);
+        }
+
+        // the stop times reference known stop points by construction
+        for issue in self.validate_stop_times() {
+            let (severity, field) = match issue.kind {
+                StopTimeIssueKind::DecreasingTimes => (Severity::Error, "arrival_time"),
+                StopTimeIssueKind::DepartureBeforeArrival => (Severity::Error, "departure_time"),
+                StopTimeIssueKind::DuplicateSequence => (Severity::Error, "stop_sequence"),
+                StopTimeIssueKind::ImplausibleSpeed => (Severity::Warning, "arrival_time"),
+                StopTimeIssueKind::SingleStop => (Severity::Warning, "trip_id"),
+            };
+            let message = match issue.sequence {
+                Some(sequence) => format!("{:?} at stop_sequence={}", issue.kind, sequence),
+                None => format!("{:?}", issue.kind),
+            };
+            let vj_id = issue.vehicle_journey_id;
+            let source = issue.sequence.and_then(|s| self.provenance.stop_time(&vj_id, s));
+            let source = source.cloned();
+            r.push(
+                severity,
+                IssueKind::StopTimes(issue.kind),
+                "stop_times.txt",
+                vj_id,
+                field,
+                message,
+            );
+            r.issues.last_mut().unwrap().source = source;
+        }
+
+        for issue in &mut r.issues {
+            if issue.source.is_none() {
+                issue.source = self.issue_source(&issue.file, &issue.object_id);
+            }
+        }
+        r
+    }
+
+    fn issue_source(&self, file: &str, object_id: &str) -> Option<Source> {
+        let type_names: &[&str] = match file {
+            "datasets.txt" => &["Dataset"],
+            "lines.txt" => &["Line"],
+            "routes.txt" => &["Route"],
+            "trips.txt" => &["VehicleJourney"],
+            "stops.txt" => &["StopPoint", "StopArea"],
+            "calendar.txt" => &["Calendar"],
+            _ => &[],
+        };
+        type_names
+            .iter()
+            .filter_map(|t| self.provenance.get_by_type(t, object_id))
+            .next()
+            .cloned()
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! The days a service runs, as a bitset.
+
+use std::cmp;
+use objects::Date;
+
+/// A set of days, stored as a bitset over a reference period.  The
+/// reference period grows as needed when days are added.
+#[derive(Debug, Clone)]
+pub struct ValidityPattern {
+    start: Date,
+    nb_days: usize,
+    bits: Vec<u64>,
+}
+
+impl PartialEq for ValidityPattern {
+    /// Two validity patterns are equal when they contain the same days,
+    /// whatever their reference periods.
+    fn eq(&self, other: &ValidityPattern) -> bool {
+        self.dates() == other.dates()
+    }
+}
+
+impl ValidityPattern {
+    /// An empty validity pattern over the reference period from
+    /// `start` to `end`, included.
+    pub fn new(start: Date, end: Date) -> ValidityPattern {
+        let nb_days = cmp::max(0, (end - start).num_days() + 1) as usize;
+        ValidityPattern {
+            start,
+            nb_days,
+            bits: vec![0; (nb_days + 63) / 64],
+        }
+    }
+
+    /// The first day of the reference period.
+    pub fn start_date(&self) -> Date {
+        self.start
+    }
+
+    /// The last day of the reference period.
+    pub fn end_date(&self) -> Date {
+        self.start + ::chrono::Duration::days(self.nb_days as i64 - 1)
+    }
+
+    fn offset(&self, date: Date) -> Option<usize> {
+        let offset = (date - self.start).num_days();
+        if 0 <= offset && (offset as usize) < self.nb_days {
+            Some(offset as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_active(&self, date: Date) -> bool {
+        self.offset(date)
+            .map_or(false, |i| self.bits[i / 64] & (1 << (i % 64)) != 0)
+    }
+
+    /// Adds (if `active`) or removes `date`.
+    pub fn set(&mut self, date: Date, active: bool) {
+        if self.offset(date).is_none() {
+            if !active {
+                return;
+            }
+            let (start, end) = if self.nb_days == 0 {
+                (date, date)
+            } else {
+                (
+                    cmp::min(self.start, date),
+                    cmp::max(self.end_date(), date),
+                )
+            };
+            *self = self.realigned(start, end);
+        }
+        let i = self.offset(date).expect("date in the reference period");
+        if active {
+            self.bits[i / 64] |= 1 << (i % 64);
+        } else {
+            self.bits[i / 64] &= !(1 << (i % 64));
+        }
+    }
+
+    /// The days of the pattern, in order.
+    pub fn dates(&self) -> Vec<Date> {
+        (0..self.nb_days)
+            .filter(|&i| self.bits[i / 64] & (1 << (i % 64)) != 0)
+            .map(|i| self.start + ::chrono::Duration::days(i as i64))
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    /// The number of days of the pattern.
+    pub fn len(&self) -> usize {
+        self.bits.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// The same days over the reference period from `start` to `end`,
+    /// the days outside this period being dropped.
+    fn realigned(&self, start: Date, end: Date) -> ValidityPattern {
+        let mut res = ValidityPattern::new(start, end);
+        if res.start == self.start {
+            let nb_words = cmp::min(res.bits.len(), self.bits.len());
+            res.bits[..nb_words].copy_from_slice(&self.bits[..nb_words]);
+            res.clear_after_end();
+        } else {
+            for date in self.dates() {
+                if let Some(i) = res.offset(date) {
+                    res.bits[i / 64] |= 1 << (i % 64);
+                }
+            }
+        }
+        res
+    }
+
+    fn clear_after_end(&mut self) {
+        if self.nb_days % 64 != 0 {
+            let last = self.bits.len() - 1;
+            self.bits[last] &= (1 << (self.nb_days % 64)) - 1;
+        }
+    }
+
+    fn combine<F>(&self, other: &ValidityPattern, start: Date, end: Date, f: F) -> ValidityPattern
+    where
+        F: Fn(u64, u64) -> u64,
+    {
+        let mut res = self.realigned(start, end);
+        let other = other.realigned(start, end);
+        for (word, &other_word) in res.bits.iter_mut().zip(&other.bits) {
+            *word = f(*word, other_word);
+        }
+        res
+    }
+
+    fn has_empty_period(&self) -> bool {
+        self.nb_days == 0
+    }
+
+    /// The days of `self` or `other`.
+    pub fn union(&self, other: &ValidityPattern) -> ValidityPattern {
+        if self.has_empty_period() {
+            return other.clone();
+        }
+        if other.has_empty_period() {
+            return self.clone();
+        }
+        let start = cmp::min(self.start, other.start);
+        let end = cmp::max(self.end_date(), other.end_date());
+        self.combine(other, start, end, |a, b| a | b)
+    }
+
+    /// The days of both `self` and `other`.
+    pub fn intersection(&self, other: &ValidityPattern) -> ValidityPattern {
+        let start = cmp::max(self.start, other.start);
+        let end = cmp::min(self.end_date(), other.end_date());
+        if self.has_empty_period() || other.has_empty_period() || start > end {
+            return ValidityPattern::new(self.start, self.start - ::chrono::Duration::days(1));
+        }
+        self.combine(other, start, end, |a, b| a & b)
+    }
+
+    /// The days of `self` that are not in `other`.
+    pub fn difference(&self, other: &ValidityPattern) -> ValidityPattern {
+        if self.has_empty_period() {
+            return self.clone();
+        }
+        self.combine(other, self.start, self.end_date(), |a, b| a & !b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn date(d: u32) -> Date {
+        NaiveDate::from_ymd_opt(2018, 1, 1).unwrap() + ::chrono::Duration::days(d as i64 - 1)
+    }
+
+    fn pattern(days: &[u32]) -> ValidityPattern {
+        let mut vp = ValidityPattern::new(date(1), date(0));
+        for &d in days {
+            vp.set(date(d), true);
+        }
+        vp
+    }
+
+    fn days(vp: &ValidityPattern) -> Vec<Date> {
+        vp.dates()
+    }
+
+    #[test]
+    fn set_and_is_active() {
+        let mut vp = ValidityPattern::new(date(10), date(100));
+        assert!(vp.is_empty());
+        vp.set(date(10), true);
+        vp.set(date(100), true);
+        vp.set(date(200), true);
+        vp.set(date(3), true);
+        vp.set(date(50), false);
+        vp.set(date(300), false);
+        assert!(vp.is_active(date(10)));
+        assert!(vp.is_active(date(200)));
+        assert!(vp.is_active(date(3)));
+        assert!(!vp.is_active(date(11)));
+        assert!(!vp.is_active(date(1000)));
+        assert_eq!(vp.len(), 4);
+        assert_eq!(days(&vp), vec![date(3), date(10), date(100), date(200)]);
+        vp.set(date(100), false);
+        assert_eq!(days(&vp), vec![date(3), date(10), date(200)]);
+        assert_eq!(vp.start_date(), date(3));
+        assert_eq!(vp.end_date(), date(200));
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = pattern(&[1, 2, 3, 70, 100]);
+        let b = pattern(&[3, 4, 70, 300]);
+        let empty = pattern(&[]);
+        assert_eq!(a.union(&b), pattern(&[1, 2, 3, 4, 70, 100, 300]));
+        assert_eq!(a.intersection(&b), pattern(&[3, 70]));
+        assert_eq!(a.difference(&b), pattern(&[1, 2, 100]));
+        assert_eq!(b.difference(&a), pattern(&[4, 300]));
+        assert_eq!(a.union(&empty), a);
+        assert_eq!(a.intersection(&empty), empty);
+        assert_eq!(a.difference(&empty), a);
+        assert_eq!(pattern(&[1]).intersection(&pattern(&[2])), empty);
+    }
+}
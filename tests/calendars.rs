@@ -0,0 +1,54 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate navitia_model;
+
+use navitia_model::objects::*;
+
+#[test]
+fn compress_calendars() {
+    let objects = navitia_model::ntfs::read("fixtures/ntfs/").unwrap();
+    let objects = objects
+        .edit(|collections| {
+            let vp = collections.calendars.get("Week").unwrap().validity_pattern();
+            let mut week2 = Calendar::from_validity_pattern("Week2".to_string(), &vp);
+            week2.calendar_dates = week2
+                .dates()
+                .into_iter()
+                .map(|date| (date, ExceptionType::Add))
+                .collect();
+            week2.monday = false;
+            week2.tuesday = false;
+            week2.wednesday = false;
+            week2.thursday = false;
+            week2.friday = false;
+            collections.calendars.push(week2)?;
+            assert_eq!(collections.calendars.len(), 2);
+            let rerab1 = collections.vehicle_journeys.get_idx("RERAB1").unwrap();
+            collections.vehicle_journeys.index_mut(rerab1).service_id = "Week2".to_string();
+            collections.compress_calendars()
+        })
+        .unwrap();
+
+    let ids: Vec<_> = objects.calendars.iter().map(|(_, c)| c.id.as_str()).collect();
+    assert_eq!(ids, &["Week"]);
+    let week = objects.calendars.get("Week").unwrap();
+    assert!(week.calendar_dates.is_empty());
+    assert!(week.monday && week.friday && !week.saturday);
+    for (_, vj) in objects.vehicle_journeys.iter() {
+        assert_eq!(vj.service_id, "Week");
+    }
+}
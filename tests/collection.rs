@@ -0,0 +1,117 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate navitia_model;
+
+use navitia_model::collection::{CollectionWithId, Id};
+
+#[derive(Debug, PartialEq)]
+struct Obj(&'static str);
+impl Id<Obj> for Obj {
+    fn id(&self) -> &str {
+        self.0
+    }
+}
+
+fn ids(c: &CollectionWithId<Obj>) -> Vec<&str> {
+    c.iter().map(|(_, obj)| obj.0).collect()
+}
+
+#[test]
+fn push() {
+    let mut c = CollectionWithId::new(vec![Obj("a")]).unwrap();
+    let b = c.push(Obj("b")).unwrap();
+    assert_eq!(c.get_idx("b"), Some(b));
+    assert!(c.push(Obj("a")).is_err());
+    assert_eq!(ids(&c), &["a", "b"]);
+}
+
+#[test]
+fn extend() {
+    let mut c = CollectionWithId::new(vec![Obj("a")]).unwrap();
+    assert!(c.extend(vec![Obj("b"), Obj("b")]).is_err());
+    assert!(c.extend(vec![Obj("b"), Obj("a")]).is_err());
+    assert_eq!(ids(&c), &["a"]);
+    let mapping = c.extend(vec![Obj("b"), Obj("c")]).unwrap();
+    assert_eq!(ids(&c), &["a", "b", "c"]);
+    assert_eq!(c.get("c"), Some(&Obj("c")));
+    let other = CollectionWithId::new(vec![Obj("x"), Obj("y")]).unwrap();
+    assert_eq!(mapping.get(other.get_idx("y").unwrap()), c.get_idx("c"));
+}
+
+#[test]
+fn merge() {
+    let mut c = CollectionWithId::new(vec![Obj("a"), Obj("b")]).unwrap();
+    let other = CollectionWithId::new(vec![Obj("c"), Obj("d")]).unwrap();
+    let d = other.get_idx("d").unwrap();
+    let mapping = c.merge(other).unwrap();
+    assert_eq!(mapping.get(d), c.get_idx("d"));
+    assert_eq!(ids(&c), &["a", "b", "c", "d"]);
+
+    let other = CollectionWithId::new(vec![Obj("a")]).unwrap();
+    assert!(c.merge(other).is_err());
+}
+
+#[test]
+fn retain() {
+    let mut c = CollectionWithId::new(vec![Obj("a"), Obj("b"), Obj("c")]).unwrap();
+    let (a, b, cc) = (
+        c.get_idx("a").unwrap(),
+        c.get_idx("b").unwrap(),
+        c.get_idx("c").unwrap(),
+    );
+    let mapping = c.retain(|obj| obj.0 != "b");
+    assert_eq!(ids(&c), &["a", "c"]);
+    assert_eq!(mapping.get(a), c.get_idx("a"));
+    assert_eq!(mapping.get(b), None);
+    assert_eq!(mapping.get(cc), c.get_idx("c"));
+    assert_eq!(c.get("c"), Some(&Obj("c")));
+
+    let mut idxs = vec![cc, b, a];
+    mapping.apply(&mut idxs);
+    assert_eq!(idxs, vec![c.get_idx("c").unwrap(), c.get_idx("a").unwrap()]);
+}
+
+#[test]
+fn remove() {
+    let mut c = CollectionWithId::new(vec![Obj("a"), Obj("b"), Obj("c")]).unwrap();
+    let (a, cc) = (c.get_idx("a").unwrap(), c.get_idx("c").unwrap());
+    assert!(c.remove("z").is_none());
+    let (obj, mapping) = c.remove("b").unwrap();
+    assert_eq!(obj, Obj("b"));
+    assert_eq!(ids(&c), &["a", "c"]);
+    assert_eq!(mapping.get(a), c.get_idx("a"));
+    assert_eq!(mapping.get(cc), c.get_idx("c"));
+    assert_eq!(c.get("c"), Some(&Obj("c")));
+    assert_eq!(c.get("b"), None);
+}
+
+#[test]
+fn try_for_each_mut() {
+    let mut c = CollectionWithId::new(vec![Obj("a"), Obj("b")]).unwrap();
+    let mut visited = vec![];
+    c.try_for_each_mut(|idx, obj| {
+        visited.push((idx, obj.0));
+        Ok(())
+    }).unwrap();
+    assert_eq!(visited, &[(c.get_idx("a").unwrap(), "a"), (c.get_idx("b").unwrap(), "b")]);
+
+    let res = c.try_for_each_mut(|_, obj| {
+        obj.0 = "c";
+        Ok(())
+    });
+    assert!(res.is_err());
+}
@@ -0,0 +1,38 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+//! Helpers shared by the tests editing a copy of the NTFS fixtures.
+
+// each test crate uses only some of the helpers
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::Path;
+
+/// Copies the files of `fixtures/ntfs/` into `path`.
+pub fn copy_fixtures(path: &Path) {
+    for entry in fs::read_dir("fixtures/ntfs/").unwrap() {
+        let entry = entry.unwrap();
+        fs::copy(entry.path(), path.join(entry.file_name())).unwrap();
+    }
+}
+
+/// Replaces `from`, that must be found, by `to` in `file` of `path`.
+pub fn replace(path: &Path, file: &str, from: &str, to: &str) {
+    let content = fs::read_to_string(path.join(file)).unwrap();
+    assert!(content.contains(from));
+    fs::write(path.join(file), content.replace(from, to)).unwrap();
+}
@@ -0,0 +1,95 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+#[macro_use]
+extern crate failure;
+extern crate navitia_model;
+
+use navitia_model::objects::*;
+use navitia_model::PtObjects;
+
+fn stop_points_of_line(objects: &PtObjects, line_id: &str) -> Vec<String> {
+    let line_idx = objects.lines.get_idx(line_id).unwrap();
+    let mut stop_points: Vec<_> = objects
+        .get_corresponding_from_idx::<_, StopPoint>(line_idx)
+        .iter()
+        .map(|&idx| objects.stop_points[idx].id.clone())
+        .collect();
+    stop_points.sort();
+    stop_points
+}
+
+#[test]
+fn edit_rebuilds_relations() {
+    let objects = navitia_model::ntfs::read("fixtures/minimal_ntfs/").unwrap();
+    assert_eq!(
+        stop_points_of_line(&objects, "M1"),
+        &["CDGM", "CHAM", "GDLM", "NATM"]
+    );
+
+    let objects = objects
+        .edit(|collections| {
+            let gdlm = collections.stop_points.get_idx("GDLM").unwrap();
+            let defr = collections.stop_points.get_idx("DEFR").unwrap();
+            let vj_idxs: Vec<_> = collections.vehicle_journeys.iter().map(|(idx, _)| idx).collect();
+            for vj_idx in vj_idxs {
+                let mut vj = collections.vehicle_journeys.index_mut(vj_idx);
+                for st in vj.stop_times.iter_mut().filter(|st| st.stop_point_idx == gdlm) {
+                    st.stop_point_idx = defr;
+                }
+            }
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        stop_points_of_line(&objects, "M1"),
+        &["CDGM", "CHAM", "DEFR", "NATM"]
+    );
+}
+
+#[test]
+fn edit_checks_consistency() {
+    let objects = navitia_model::ntfs::read("fixtures/minimal_ntfs/").unwrap();
+    let res = objects.edit(|collections| {
+        let m1 = collections.lines.get_idx("M1").unwrap();
+        collections.lines.index_mut(m1).network_id = "unknown".to_string();
+        Ok(())
+    });
+    let mut collections = *res.err().unwrap().collections;
+    assert_eq!(collections.lines.get("M1").unwrap().network_id, "unknown");
+
+    // the collections given back can be fixed
+    let m1 = collections.lines.get_idx("M1").unwrap();
+    collections.lines.index_mut(m1).network_id = "TGN".to_string();
+    let objects = PtObjects::new(collections).unwrap();
+
+    let res = objects.edit(|collections| {
+        collections.lines.retain(|line| line.id != "M1");
+        Err(format_err!("edition failed"))
+    });
+    let err = res.err().unwrap();
+    assert_eq!(err.to_string(), "edition failed");
+    assert!(err.collections.lines.get("M1").is_none());
+}
+
+#[test]
+fn into_collections() {
+    let objects = navitia_model::ntfs::read("fixtures/minimal_ntfs/").unwrap();
+    let nb_lines = objects.lines.len();
+    let collections = objects.into_collections();
+    assert_eq!(collections.lines.len(), nb_lines);
+    assert!(PtObjects::new(collections).is_ok());
+}
@@ -0,0 +1,124 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate navitia_model;
+extern crate tempdir;
+
+mod common;
+
+use common::{copy_fixtures, replace};
+use navitia_model::error::ErrorKind;
+use navitia_model::ntfs::{self, Version};
+use std::fs;
+use std::path::Path;
+use tempdir::TempDir;
+
+fn read_error(path: &Path) -> ErrorKind {
+    match ntfs::read(path) {
+        Ok(_) => panic!("no error reading {:?}", path),
+        Err(err) => err.kind().clone(),
+    }
+}
+
+#[test]
+fn missing_file() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    fs::remove_file(path.join("lines.txt")).unwrap();
+    assert_eq!(
+        read_error(path),
+        ErrorKind::MissingFile {
+            path: path.join("lines.txt"),
+        }
+    );
+}
+
+#[test]
+fn csv_parse() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    replace(path, "stop_times.txt", "M1F1,1,GDLM", "M1F1,first,GDLM");
+    match read_error(path) {
+        ErrorKind::Csv {
+            path: file,
+            line,
+            column,
+            ..
+        } => {
+            assert_eq!(file, path.join("stop_times.txt"));
+            assert_eq!(line, Some(3));
+            assert_eq!(column, Some(2));
+        }
+        kind => panic!("unexpected {:?}", kind),
+    }
+}
+
+#[test]
+fn duplicate_id() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    replace(path, "lines.txt", "B42,Bus 42", "M1,Bus 42");
+    assert_eq!(
+        read_error(path),
+        ErrorKind::DuplicateId {
+            collection: "Line",
+            id: "M1".to_string(),
+        }
+    );
+}
+
+#[test]
+fn unknown_reference() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    replace(path, "routes.txt", "M1B,Charles de Gaulle - Nation,M1", "M1B,Nation,M2");
+    assert_eq!(
+        read_error(path),
+        ErrorKind::UnknownReference {
+            from_type: "Route",
+            from_id: "M1B".to_string(),
+            to_type: "Line",
+            to_id: "M2".to_string(),
+        }
+    );
+}
+
+#[test]
+fn invalid_value() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let objects = ntfs::read("fixtures/ntfs/").unwrap();
+    let err = ntfs::write(tmp_dir.path(), &objects, Version::V0_5).unwrap_err();
+    match *err.kind() {
+        ErrorKind::InvalidValue { field, .. } => assert_eq!(field, "ntfs_version"),
+        ref kind => panic!("unexpected {:?}", kind),
+    }
+    assert_eq!(err.to_string(), "writing NTFS 0.5 is not supported");
+}
+
+#[test]
+fn unsupported_version() {
+    let err = navitia_model::error::Error::from("0.4".parse::<Version>().unwrap_err());
+    match *err.kind() {
+        ErrorKind::InvalidValue { field, ref value, .. } => {
+            assert_eq!((field, value.as_str()), ("ntfs_version", "0.4"))
+        }
+        ref kind => panic!("unexpected {:?}", kind),
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate navitia_model;
+
+use navitia_model::error::ErrorKind;
+use navitia_model::merge::{merge, ConflictPolicy};
+use navitia_model::objects::*;
+use navitia_model::{Collections, PtObjects};
+
+fn minimal() -> Collections {
+    navitia_model::ntfs::read("fixtures/minimal_ntfs/")
+        .unwrap()
+        .into_collections()
+}
+
+#[test]
+fn merge_conflicting_error() {
+    let err = merge(vec![minimal(), minimal()], ConflictPolicy::Error).unwrap_err();
+    match *navitia_model::error::Error::from(err).kind() {
+        ErrorKind::DuplicateId { .. } => (),
+        ref kind => panic!("unexpected {:?}", kind),
+    }
+}
+
+#[test]
+fn merge_keep_first() {
+    let merged = merge(vec![minimal(), minimal()], ConflictPolicy::KeepFirst).unwrap();
+    let objects = PtObjects::new(merged).unwrap();
+    let reference = navitia_model::ntfs::read("fixtures/minimal_ntfs/").unwrap();
+    assert_eq!(objects.lines.len(), reference.lines.len());
+    assert_eq!(objects.stop_points.len(), reference.stop_points.len());
+    assert_eq!(objects.vehicle_journeys.len(), reference.vehicle_journeys.len());
+}
+
+#[test]
+fn merge_prefix() {
+    let models = vec![minimal(), minimal(), minimal()];
+    let merged = merge(models, ConflictPolicy::Prefix).unwrap();
+    let objects = PtObjects::new(merged).unwrap();
+    let reference = navitia_model::ntfs::read("fixtures/minimal_ntfs/").unwrap();
+    assert_eq!(objects.lines.len(), 3 * reference.lines.len());
+    assert_eq!(objects.stop_points.len(), 3 * reference.stop_points.len());
+    assert_eq!(objects.vehicle_journeys.len(), 3 * reference.vehicle_journeys.len());
+    // identical modes are shared
+    assert_eq!(objects.physical_modes.len(), reference.physical_modes.len());
+    assert_eq!(objects.commercial_modes.len(), reference.commercial_modes.len());
+
+    let line = objects.lines.get_idx("2:M1").unwrap();
+    let mut stop_points: Vec<_> = objects
+        .get_corresponding_from_idx::<_, StopPoint>(line)
+        .iter()
+        .map(|&idx| objects.stop_points[idx].id.as_str())
+        .collect();
+    stop_points.sort();
+    assert_eq!(stop_points, &["2:CDGM", "2:CHAM", "2:GDLM", "2:NATM"]);
+
+    let sp = objects.stop_points.get("1:GDLM").unwrap();
+    assert_eq!(sp.stop_area_id, "1:GDL");
+    let physical_modes: Vec<_> = objects
+        .get_corresponding_from_idx::<_, PhysicalMode>(line)
+        .iter()
+        .map(|&idx| objects.physical_modes[idx].id.as_str())
+        .collect();
+    assert_eq!(physical_modes, &["Metro"]);
+}
+
+#[test]
+fn merge_remaps_comment_links() {
+    let ntfs = || {
+        navitia_model::ntfs::read("fixtures/ntfs/")
+            .unwrap()
+            .into_collections()
+    };
+    let merged = merge(vec![ntfs(), ntfs()], ConflictPolicy::Prefix).unwrap();
+    let objects = PtObjects::new(merged).unwrap();
+    assert_eq!(objects.comments.len(), 2);
+    for (line_id, comment_id) in &[("RERA", "RERACOM1"), ("1:RERA", "1:RERACOM1")] {
+        let line = objects.lines.get(line_id).unwrap();
+        let comments: Vec<_> = objects
+            .comments
+            .iter_from(&line.comment_links)
+            .map(|c| c.id.as_str())
+            .collect();
+        assert_eq!(comments, &[*comment_id]);
+    }
+}
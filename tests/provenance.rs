@@ -0,0 +1,175 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate navitia_model;
+extern crate tempdir;
+
+mod common;
+
+use common::{copy_fixtures, replace};
+use navitia_model::gtfs;
+use navitia_model::ntfs::{self, ReadOptions};
+use navitia_model::objects::{Line, StopArea, StopPoint};
+use navitia_model::provenance::Source;
+use navitia_model::PtObjects;
+use std::fs;
+use std::path::Path;
+use tempdir::TempDir;
+
+fn tracking() -> ReadOptions {
+    ReadOptions {
+        track_provenance: true,
+        ..ReadOptions::default()
+    }
+}
+
+fn read_tracked(path: &Path) -> PtObjects {
+    match ntfs::read_with_options(path, &tracking()) {
+        Ok(objects) => objects,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+fn source(file: &str, line: u64) -> Source {
+    Source {
+        file: file.to_string(),
+        line,
+    }
+}
+
+#[test]
+fn not_tracked_by_default() {
+    let objects = ntfs::read("fixtures/ntfs/").unwrap();
+    assert!(objects.provenance.is_empty());
+}
+
+#[test]
+fn sources_of_the_objects() {
+    let objects = read_tracked(Path::new("fixtures/ntfs/"));
+    let provenance = &objects.provenance;
+    let idx = objects.stop_points.get_idx("GDLR").unwrap();
+    assert_eq!(provenance.get(&objects.stop_points, idx), Some(&source("stops.txt", 3)));
+    let idx = objects.stop_areas.get_idx("GDL").unwrap();
+    assert_eq!(provenance.get(&objects.stop_areas, idx), Some(&source("stops.txt", 2)));
+    assert_eq!(provenance.get_by_id::<Line>("B42"), Some(&source("lines.txt", 3)));
+    assert_eq!(provenance.stop_time("M1F1", 1), Some(&source("stop_times.txt", 3)));
+}
+
+#[test]
+fn sources_of_the_codes() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    fs::write(
+        path.join("object_codes.txt"),
+        "object_type,object_id,object_system,object_code\n\
+         line,M1,source,L1\n\
+         stop_point,GDLM,source,SP1\n",
+    ).unwrap();
+    let objects = read_tracked(path);
+    let provenance = &objects.provenance;
+    let expected = Some(source("object_codes.txt", 3));
+    assert_eq!(provenance.code::<StopPoint>("GDLM", "source", "SP1").cloned(), expected);
+    assert_eq!(provenance.code::<StopArea>("GDLM", "source", "SP1"), None);
+}
+
+#[test]
+fn sources_in_errors() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    replace(path, "routes.txt", "Gaulle - Nation,M1", "Gaulle - Nation,M2");
+    let err = match ntfs::read_with_options(path, &tracking()) {
+        Ok(_) => panic!("the unknown line should fail the reading"),
+        Err(err) => err,
+    };
+    assert_eq!(err.source(), Some(&source("routes.txt", 3)));
+
+    replace(path, "routes.txt", "Gaulle - Nation,M2", "Gaulle - Nation,M1");
+    replace(path, "stop_times.txt", "M1F1,1,GDLM", "M1F1,first,GDLM");
+    let err = match ntfs::read_with_options(path, &tracking()) {
+        Ok(_) => panic!("the invalid stop_sequence should fail the reading"),
+        Err(err) => err,
+    };
+    assert_eq!(err.source(), Some(&source("stop_times.txt", 3)));
+    match ntfs::read(path) {
+        Ok(_) => panic!("the invalid stop_sequence should fail the reading"),
+        Err(err) => assert_eq!(err.source(), None),
+    }
+}
+
+#[test]
+fn sources_in_validation_reports() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    replace(path, "stops.txt", "(RER),48.844746,2.372987", "(RER),0,0");
+    let (collections, _) = ntfs::read_collections(path, &tracking()).unwrap();
+    let report = collections.validate();
+    let issue = report.issues.iter().find(|i| i.object_id == "GDLR").unwrap();
+    assert_eq!(issue.source, Some(source("stops.txt", 3)));
+
+    let (collections, _) = ntfs::read_collections(path, &ReadOptions::default()).unwrap();
+    let report = collections.validate();
+    assert!(report.issues.iter().all(|i| i.source.is_none()));
+}
+
+#[test]
+fn sources_of_gtfs_objects() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    let files = [
+        (
+            "agency.txt",
+            "agency_id,agency_name,agency_url,agency_timezone\n\
+             TGN,The Great Network,http://www.foo.com,Europe/Paris\n",
+        ),
+        (
+            "stops.txt",
+            "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station\n\
+             GDL,Gare de Lyon,48.844746,2.372987,1,\n\
+             NATM,Nation,48.84849,2.396497,0,\n",
+        ),
+        (
+            "calendar.txt",
+            "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,\
+             start_date,end_date\n\
+             Week,1,1,1,1,1,0,0,20180101,20180301\n",
+        ),
+        (
+            "routes.txt",
+            "route_id,agency_id,route_short_name,route_long_name,route_type\n\
+             M1,TGN,1,Metro 1,1\n",
+        ),
+        (
+            "trips.txt",
+            "route_id,service_id,trip_id,direction_id,wheelchair_accessible,bikes_allowed\n\
+             M1,Week,M1F1,0,,\n",
+        ),
+    ];
+    for &(file, content) in &files {
+        fs::write(path.join(file), content).unwrap();
+    }
+    let options = gtfs::ReadOptions {
+        track_provenance: true,
+    };
+    let objects = gtfs::read_with_options(path, None, Some("pf".to_string()), &options).unwrap();
+    let provenance = &objects.provenance;
+    assert_eq!(provenance.get_by_id::<StopArea>("pf:GDL"), Some(&source("stops.txt", 2)));
+    let generated = format!("pf:{}", StopArea::generated_id("NATM"));
+    assert_eq!(provenance.get_by_id::<StopArea>(&generated), Some(&source("stops.txt", 3)));
+    assert_eq!(provenance.get_by_id::<Line>("pf:M1"), Some(&source("routes.txt", 2)));
+}
@@ -15,10 +15,17 @@
 // <http://www.gnu.org/licenses/>.
 
 extern crate navitia_model;
+extern crate tempdir;
+
+mod common;
+
+use common::copy_fixtures;
 use navitia_model::objects::*;
 use navitia_model::collection::{CollectionWithId, Id, Idx};
 use navitia_model::relations::IdxSet;
 use navitia_model::{GetCorresponding, PtObjects};
+use std::fs;
+use tempdir::TempDir;
 
 fn get<T, U>(idx: Idx<T>, collection: &CollectionWithId<U>, objects: &PtObjects) -> Vec<String>
 where
@@ -96,4 +103,86 @@ fn ntfs() {
     for comment in pt_objects.comments.iter_from(rera_comment_indexes) {
         assert_eq!(comment.id.to_string(), "RERACOM1");
     }
+
+    // generated stop areas
+    let tour = pt_objects.stop_points.get_idx("TOUR").unwrap();
+    assert_eq!(get(tour, &pt_objects.stop_areas, &pt_objects), &["Navitia:TOUR"]);
+    assert!(pt_objects.stop_areas.get("Navitia:TOUR").unwrap().generated);
+    assert!(!pt_objects.stop_areas.get("GDL").unwrap().generated);
+
+    // grid calendars
+    assert_eq!(2, pt_objects.grid_calendars.len());
+    assert_eq!(1, pt_objects.grid_exception_dates.iter().count());
+    assert_eq!(2, pt_objects.grid_periods.iter().count());
+    let wd = pt_objects.grid_calendars.get_idx("WD").unwrap();
+    assert_eq!(get(wd, &pt_objects.lines, &pt_objects), &["M1", "RERA"]);
+    let m1 = pt_objects.lines.get_idx("M1").unwrap();
+    assert_eq!(
+        get(m1, &pt_objects.grid_calendars, &pt_objects),
+        &["WD", "WE"]
+    );
+}
+
+#[test]
+fn grid_calendars_of_lines_by_code() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    fs::write(
+        path.join("object_codes.txt"),
+        "object_type,object_id,object_system,object_code\nline,B42,source,BUS42\n",
+    ).unwrap();
+    fs::write(
+        path.join("grid_rel_calendar_line.txt"),
+        "grid_calendar_id,line_id,line_external_code\nWD,M1,\nWE,,BUS42\n",
+    ).unwrap();
+    let pt_objects = navitia_model::ntfs::read(path).unwrap();
+    let we = pt_objects.grid_calendars.get_idx("WE").unwrap();
+    assert_eq!(get(we, &pt_objects.lines, &pt_objects), &["B42"]);
+
+    fs::write(
+        path.join("grid_rel_calendar_line.txt"),
+        "grid_calendar_id,line_id,line_external_code\nWE,,UNKNOWN\n",
+    ).unwrap();
+    assert!(navitia_model::ntfs::read(path).is_err());
+
+    fs::copy(
+        "fixtures/ntfs/grid_rel_calendar_line.txt",
+        path.join("grid_rel_calendar_line.txt"),
+    ).unwrap();
+    fs::write(
+        path.join("grid_periods.txt"),
+        "grid_calendar_id,start_date,end_date\nXX,20180101,20181231\n",
+    ).unwrap();
+    assert!(navitia_model::ntfs::read(path).is_err());
+}
+
+#[test]
+fn line_groups() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    fs::write(
+        path.join("line_groups.txt"),
+        "line_group_id,line_group_name,main_line_id\nLG,Metro and bus,M1\n",
+    ).unwrap();
+    fs::write(
+        path.join("line_group_links.txt"),
+        "line_group_id,line_id\nLG,M1\nLG,B42\n",
+    ).unwrap();
+    fs::write(
+        path.join("object_codes.txt"),
+        "object_type,object_id,object_system,object_code\nline_group,LG,source,G1\n",
+    ).unwrap();
+    let pt_objects = navitia_model::ntfs::read(path).unwrap();
+    let line_group = pt_objects.line_groups.get("LG").unwrap();
+    assert_eq!(line_group.main_line_id, "M1");
+    assert_eq!(line_group.codes, &[("source".to_string(), "G1".to_string())]);
+    assert_eq!(pt_objects.line_group_links.iter().count(), 2);
+
+    fs::write(
+        path.join("line_group_links.txt"),
+        "line_group_id,line_id\nLG,M2\n",
+    ).unwrap();
+    assert!(navitia_model::ntfs::read(path).is_err());
 }
@@ -0,0 +1,173 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate navitia_model;
+extern crate tempdir;
+
+mod common;
+
+use common::{copy_fixtures, replace};
+use navitia_model::ntfs::{self, ReadOptions};
+use navitia_model::objects::{Line, StopArea};
+use navitia_model::read_report::Strictness;
+use std::fs;
+use tempdir::TempDir;
+
+fn lenient() -> ReadOptions {
+    ReadOptions {
+        strictness: Strictness::Lenient,
+        ..ReadOptions::default()
+    }
+}
+
+#[test]
+fn nothing_ignored_in_fixtures() {
+    let (_, report) = ntfs::read_with_report("fixtures/ntfs/", &lenient()).unwrap();
+    assert!(report.is_empty());
+}
+
+#[test]
+fn skip_invalid_rows() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    replace(
+        path,
+        "stop_times.txt",
+        "M1F1,1,GDLM,09:10:00",
+        "M1F1,1,GDLM,09:70:00",
+    );
+    replace(path, "stops.txt", "TOUR,Tour Eiffel,48.858370", "TOUR,Tour Eiffel,north");
+    assert!(ntfs::read(path).is_err());
+
+    let (objects, report) = ntfs::read_with_report(path, &lenient()).unwrap();
+    let skipped: Vec<_> = report
+        .skipped_rows
+        .iter()
+        .map(|issue| (issue.file.as_str(), issue.line))
+        .collect();
+    assert_eq!(skipped, &[("stops.txt", Some(18)), ("stop_times.txt", Some(3))]);
+    assert!(report.ignored_values.is_empty());
+    assert!(objects.stop_points.get("TOUR").is_none());
+    assert_eq!(objects.vehicle_journeys.get("M1F1").unwrap().stop_times.len(), 3);
+}
+
+#[test]
+fn skip_unresolved_references() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    replace(path, "routes.txt", "M1B,Charles de Gaulle - Nation,M1", "M1B,Nation,M2");
+    assert!(ntfs::read(path).is_err());
+
+    let (objects, report) = ntfs::read_with_report(path, &lenient()).unwrap();
+    let skipped: Vec<_> = report
+        .skipped_rows
+        .iter()
+        .map(|issue| (issue.file.as_str(), issue.line))
+        .collect();
+    assert_eq!(
+        skipped,
+        &[
+            ("routes.txt", Some(3)),
+            ("trips.txt", Some(3)),
+            ("stop_times.txt", Some(6)),
+            ("stop_times.txt", Some(7)),
+            ("stop_times.txt", Some(8)),
+            ("stop_times.txt", Some(9)),
+        ]
+    );
+    assert_eq!(report.skipped_rows[0].message, "line_id=\"M2\" not found");
+    assert!(objects.routes.get("M1B").is_none());
+    assert!(objects.vehicle_journeys.get("M1B1").is_none());
+}
+
+#[test]
+fn skip_codes_of_unknown_stop_times() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    fs::write(
+        path.join("object_codes.txt"),
+        "object_type,object_id,object_system,object_code\nstop_time,unknown,source,1\n",
+    ).unwrap();
+
+    let (_, report) = ntfs::read_with_report(path, &ReadOptions::default()).unwrap();
+    let skipped: Vec<_> = report
+        .skipped_rows
+        .iter()
+        .map(|issue| (issue.file.as_str(), issue.line))
+        .collect();
+    assert_eq!(skipped, &[("object_codes.txt", None)]);
+}
+
+#[test]
+fn report_ignored_values() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    fs::write(
+        path.join("lines.txt"),
+        "line_id,line_name,network_id,commercial_mode_id,line_color\n\
+         M1,Metro 1,TGN,Metro,yellow\n\
+         B42,Bus 42,TGN,Bus,\n\
+         RERA,RER A,TGN,RER,\n",
+    ).unwrap();
+
+    let (objects, report) = ntfs::read_with_report(path, &ReadOptions::default()).unwrap();
+    assert!(report.skipped_rows.is_empty());
+    assert_eq!(report.ignored_values.len(), 1);
+    assert_eq!(report.ignored_values[0].file, "lines.txt");
+    assert_eq!(report.ignored_values[0].line, Some(2));
+    assert_eq!(objects.lines.get("M1").unwrap().color, None);
+}
+
+#[test]
+fn same_report_whatever_the_threads() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    replace(path, "stops.txt", "TOUR,Tour Eiffel,48.858370", "TOUR,Tour Eiffel,north");
+    replace(path, "routes.txt", "M1B,Charles de Gaulle - Nation,M1", "M1B,Nation,M2");
+
+    let read = |threads| {
+        let options = ReadOptions {
+            threads,
+            ..lenient()
+        };
+        let (objects, report) = ntfs::read_with_report(path, &options).unwrap();
+        let skipped: Vec<_> = report
+            .skipped_rows
+            .iter()
+            .map(|issue| (issue.file.clone(), issue.line))
+            .collect();
+        (objects, skipped)
+    };
+    let (objects, skipped) = read(Some(1));
+    assert_eq!(skipped[0], ("stops.txt".to_string(), Some(18)));
+    assert_eq!(skipped[1], ("routes.txt".to_string(), Some(3)));
+    for &threads in &[None, Some(4)] {
+        let (other_objects, other_skipped) = read(threads);
+        assert_eq!(other_skipped, skipped);
+        assert_eq!(other_objects.stop_points, objects.stop_points);
+        assert_eq!(other_objects.vehicle_journeys, objects.vehicle_journeys);
+        let gdl = objects.stop_areas.get_idx("GDL").unwrap();
+        assert_eq!(
+            other_objects.get_corresponding_from_idx::<StopArea, Line>(gdl),
+            objects.get_corresponding_from_idx::<StopArea, Line>(gdl)
+        );
+    }
+}
@@ -0,0 +1,221 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate chrono;
+extern crate navitia_model;
+
+use chrono::NaiveDate;
+use navitia_model::error::ErrorKind;
+use navitia_model::objects::*;
+use navitia_model::restrict::{BoundingBox, Selection};
+use navitia_model::{Collections, PtObjects};
+
+fn ntfs() -> Collections {
+    navitia_model::ntfs::read("fixtures/ntfs/")
+        .unwrap()
+        .into_collections()
+}
+
+fn date(y: i32, m: u32, d: u32) -> Date {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+}
+
+#[test]
+fn restrict_validity_period() {
+    let mut collections = ntfs();
+    let week = collections.calendars.get_idx("Week").unwrap();
+    collections.calendars.index_mut(week).calendar_dates = vec![
+        (date(2018, 1, 2), ExceptionType::Remove),
+        (date(2018, 3, 5), ExceptionType::Remove),
+    ];
+    let nb_vjs = collections.vehicle_journeys.len();
+
+    let report = collections
+        .restrict_validity_period(date(2018, 3, 1), date(2018, 4, 30))
+        .unwrap();
+    assert!(!report.removed_ids.contains_key("vehicle_journeys"));
+    assert_eq!(collections.vehicle_journeys.len(), nb_vjs);
+
+    let week = collections.calendars.get("Week").unwrap();
+    assert_eq!(week.start_date, date(2018, 3, 1));
+    assert_eq!(week.end_date, date(2018, 4, 30));
+    assert_eq!(
+        week.calendar_dates,
+        vec![(date(2018, 3, 5), ExceptionType::Remove)]
+    );
+    let dataset = collections.datasets.get("TGDS").unwrap();
+    assert_eq!(dataset.start_date, date(2018, 3, 1));
+    assert_eq!(dataset.end_date, date(2018, 4, 30));
+    assert_eq!(collections.feed_infos["feed_start_date"], "20180301");
+    assert_eq!(collections.feed_infos["feed_end_date"], "20180430");
+}
+
+#[test]
+fn restrict_validity_period_to_inactive_days() {
+    let mut collections = ntfs();
+    // a week end, and the calendar only runs on week days
+    let report = collections
+        .restrict_validity_period(date(2018, 3, 3), date(2018, 3, 4))
+        .unwrap();
+    assert_eq!(report.removed_ids["calendars"], &["Week"]);
+    assert_eq!(collections.vehicle_journeys.len(), 0);
+    assert_eq!(collections.lines.len(), 0);
+    assert_eq!(collections.stop_points.len(), 0);
+}
+
+#[test]
+fn restrict_validity_period_outside_calendar() {
+    let mut collections = ntfs();
+    let week = collections.calendars.get_idx("Week").unwrap();
+    collections.calendars.index_mut(week).calendar_dates =
+        vec![(date(2019, 1, 5), ExceptionType::Add)];
+    collections
+        .restrict_validity_period(date(2019, 1, 1), date(2019, 1, 31))
+        .unwrap();
+    let week = collections.calendars.get("Week").unwrap();
+    assert!(!week.monday);
+    assert_eq!(
+        week.calendar_dates,
+        vec![(date(2019, 1, 5), ExceptionType::Add)]
+    );
+    assert!(collections.vehicle_journeys.len() > 0);
+    // the dataset, and the grid periods, end in 2018
+    let dataset = collections.datasets.get("TGDS").unwrap();
+    assert_eq!(dataset.start_date, date(2019, 1, 1));
+    assert_eq!(dataset.end_date, date(2019, 1, 31));
+    assert_eq!(collections.grid_periods.iter().count(), 0);
+}
+
+#[test]
+fn restrict_validity_period_clips_grid_periods() {
+    let mut collections = ntfs();
+    let report = collections
+        .restrict_validity_period(date(2018, 3, 1), date(2018, 4, 30))
+        .unwrap();
+    assert!(!report.removed_counts.contains_key("grid_periods"));
+    for (_, period) in collections.grid_periods.iter() {
+        assert_eq!(period.start_date, date(2018, 3, 1));
+        assert_eq!(period.end_date, date(2018, 4, 30));
+    }
+    assert_eq!(collections.grid_periods.iter().count(), 2);
+}
+
+#[test]
+fn restrict_validity_period_invalid() {
+    let mut collections = ntfs();
+    assert!(
+        collections
+            .restrict_validity_period(date(2018, 3, 4), date(2018, 3, 3))
+            .is_err()
+    );
+}
+
+fn extract(selection: Selection) -> PtObjects {
+    navitia_model::ntfs::read("fixtures/ntfs/")
+        .unwrap()
+        .extract(&selection)
+        .unwrap()
+}
+
+fn ids<T: navitia_model::collection::Id<T>>(
+    collection: &navitia_model::collection::CollectionWithId<T>,
+) -> Vec<&str> {
+    let mut ids: Vec<_> = collection.iter().map(|(_, o)| o.id()).collect();
+    ids.sort();
+    ids
+}
+
+#[test]
+fn extract_lines() {
+    let objects = extract(Selection {
+        lines: vec!["M1".to_string()],
+        ..Selection::default()
+    });
+    assert_eq!(ids(&objects.lines), &["M1"]);
+    assert_eq!(ids(&objects.vehicle_journeys), &["M1B1", "M1F1"]);
+    assert_eq!(ids(&objects.stop_points), &["CDGM", "CHAM", "GDLM", "NATM"]);
+    assert_eq!(ids(&objects.stop_areas), &["CDG", "CHA", "GDL", "NAT"]);
+    assert_eq!(ids(&objects.physical_modes), &["Metro"]);
+    assert_eq!(objects.comments.len(), 0);
+}
+
+#[test]
+fn extract_network() {
+    let objects = extract(Selection {
+        networks: vec!["TGN".to_string()],
+        ..Selection::default()
+    });
+    assert_eq!(objects.vehicle_journeys.len(), 6);
+    assert!(objects.stop_points.get("TOUR").is_none());
+}
+
+#[test]
+fn extract_several_criteria() {
+    let objects = extract(Selection {
+        lines: vec!["RERA".to_string()],
+        physical_modes: vec!["Bus".to_string()],
+        ..Selection::default()
+    });
+    assert_eq!(ids(&objects.vehicle_journeys), &["RERAB1"]);
+    assert_eq!(ids(&objects.comments), &["RERACOM1"]);
+}
+
+#[test]
+fn extract_bounding_box() {
+    let objects = extract(Selection {
+        bounding_box: Some("2.23,48.89,2.24,48.90".parse().unwrap()),
+        ..Selection::default()
+    });
+    assert_eq!(ids(&objects.lines), &["RERA"]);
+    assert_eq!(ids(&objects.stop_points), &["CDGR", "DEFR", "GDLR", "NATR"]);
+}
+
+#[test]
+fn extract_unknown_line() {
+    let objects = navitia_model::ntfs::read("fixtures/ntfs/").unwrap();
+    let selection = Selection {
+        lines: vec!["unknown".to_string()],
+        ..Selection::default()
+    };
+    let err = match objects.extract(&selection) {
+        Ok(_) => panic!("unknown line extracted"),
+        Err(err) => navitia_model::error::Error::from(err),
+    };
+    assert_eq!(
+        *err.kind(),
+        ErrorKind::UnknownReference {
+            from_type: "Selection",
+            from_id: "lines".to_string(),
+            to_type: "Line",
+            to_id: "unknown".to_string(),
+        }
+    );
+}
+
+#[test]
+fn parse_bounding_box() {
+    assert_eq!(
+        "1,2,3,4".parse::<BoundingBox>().unwrap(),
+        BoundingBox {
+            min_lon: 1.,
+            min_lat: 2.,
+            max_lon: 3.,
+            max_lat: 4.,
+        }
+    );
+    assert!("1,2,3".parse::<BoundingBox>().is_err());
+    assert!("1,2,3,a".parse::<BoundingBox>().is_err());
+}
@@ -0,0 +1,69 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate navitia_model;
+
+use navitia_model::objects::*;
+
+#[test]
+fn sanitize_orphan_stop_point() {
+    let objects = navitia_model::ntfs::read("fixtures/ntfs/").unwrap();
+    let nb_stop_points = objects.stop_points.len();
+    let (objects, report) = objects.sanitize().unwrap();
+
+    assert_eq!(report.removed_ids["stop_points"], &["TOUR"]);
+    assert_eq!(report.removed_ids["stop_areas"], &["Navitia:TOUR"]);
+    assert_eq!(objects.stop_points.len(), nb_stop_points - 1);
+    assert!(objects.lines.get("RERA").is_some());
+    assert_eq!(objects.comments.len(), 1);
+
+    let (_, report) = objects.sanitize().unwrap();
+    assert!(report.is_empty());
+}
+
+#[test]
+fn sanitize_after_removing_vehicle_journeys() {
+    let objects = navitia_model::ntfs::read("fixtures/ntfs/").unwrap();
+    let objects = objects
+        .edit(|collections| {
+            collections
+                .vehicle_journeys
+                .retain(|vj| !vj.route_id.starts_with("M1"));
+            Ok(())
+        })
+        .unwrap();
+    let (objects, report) = objects.sanitize().unwrap();
+
+    assert_eq!(report.removed_ids["lines"], &["M1"]);
+    assert_eq!(report.removed_ids["routes"], &["M1F", "M1B"]);
+    assert_eq!(report.removed_ids["physical_modes"], &["Metro"]);
+    assert_eq!(report.removed_ids["grid_calendars"], &["WE"]);
+    assert_eq!(report.removed_counts["grid_rel_calendar_line"], 2);
+    assert_eq!(report.removed_counts["grid_periods"], 1);
+
+    let sa = objects.stop_areas.get_idx("CDG").unwrap();
+    let stop_points: Vec<_> = objects
+        .get_corresponding_from_idx::<_, StopPoint>(sa)
+        .iter()
+        .map(|&idx| objects.stop_points[idx].id.as_str())
+        .collect();
+    assert_eq!(stop_points, &["CDGR"]);
+    for (_, vj) in objects.vehicle_journeys.iter() {
+        for st in &vj.stop_times {
+            assert!(!objects.stop_points[st.stop_point_idx].id.ends_with('M'));
+        }
+    }
+}
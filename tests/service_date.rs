@@ -0,0 +1,125 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate chrono;
+extern crate navitia_model;
+
+use chrono::NaiveDate;
+use navitia_model::objects::*;
+use navitia_model::relations::IdxSet;
+
+fn date(day: u32) -> Date {
+    // 2018-01-01 is a monday
+    NaiveDate::from_ymd_opt(2018, 1, day).unwrap()
+}
+
+#[test]
+fn vehicle_journeys_on_a_day() {
+    let objects = navitia_model::ntfs::read("fixtures/ntfs/").unwrap();
+    let ids: Vec<_> = objects
+        .vehicle_journeys_on(date(5))
+        .iter()
+        .map(|d| d.vehicle_journey.id.as_str())
+        .collect();
+    assert_eq!(
+        ids,
+        &["B42B1", "RERAF1", "M1F1", "RERAB1", "B42F1", "M1B1"]
+    );
+    assert!(objects.vehicle_journeys_on(date(6)).is_empty());
+
+    let lines: IdxSet<_> = Some(objects.lines.get_idx("M1").unwrap()).into_iter().collect();
+    let ids: Vec<_> = objects
+        .vehicle_journeys_on_from(date(5), &lines)
+        .iter()
+        .map(|d| d.vehicle_journey.id.as_str())
+        .collect();
+    assert_eq!(ids, &["M1F1", "M1B1"]);
+}
+
+#[test]
+fn vehicle_journeys_after_midnight() {
+    let objects = navitia_model::ntfs::read("fixtures/ntfs/").unwrap();
+    let objects = objects
+        .edit(|collections| {
+            let idx = collections.vehicle_journeys.get_idx("M1F1").unwrap();
+            let mut vj = collections.vehicle_journeys.index_mut(idx);
+            let times = [(23, 50), (24, 0), (24, 10), (24, 30)];
+            for (st, &(h, m)) in vj.stop_times.iter_mut().zip(times.iter()) {
+                st.arrival_time = Time::new(h, m, 0);
+                st.departure_time = Time::new(h, m, 0);
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    // on friday, M1F1 leaves last
+    let departures = objects.vehicle_journeys_on(date(5));
+    let last = departures.last().unwrap();
+    assert_eq!(last.vehicle_journey.id, "M1F1");
+    assert_eq!(last.service_date, date(5));
+    assert_eq!(last.stop_times.len(), 1);
+
+    // on saturday, only the end of friday's M1F1 runs
+    let departures = objects.vehicle_journeys_on(date(6));
+    assert_eq!(departures.len(), 1);
+    assert_eq!(departures[0].vehicle_journey.id, "M1F1");
+    assert_eq!(departures[0].service_date, date(5));
+    assert_eq!(departures[0].stop_times.len(), 3);
+    assert_eq!(departures[0].departure_time(), Time::new(0, 0, 0));
+
+    // on monday, the end of sunday's M1F1 does not run
+    let departures = objects.vehicle_journeys_on(date(8));
+    assert!(departures.iter().all(|d| d.service_date == date(8)));
+    assert_eq!(departures[0].vehicle_journey.id, "B42B1");
+}
+
+#[test]
+fn vehicle_journeys_after_two_midnights() {
+    let objects = navitia_model::ntfs::read("fixtures/ntfs/").unwrap();
+    let objects = objects
+        .edit(|collections| {
+            let idx = collections.vehicle_journeys.get_idx("M1F1").unwrap();
+            let mut vj = collections.vehicle_journeys.index_mut(idx);
+            let times = [(23, 50), (24, 10), (47, 50), (48, 10)];
+            for (st, &(h, m)) in vj.stop_times.iter_mut().zip(times.iter()) {
+                st.arrival_time = Time::new(h, m, 0);
+                st.departure_time = Time::new(h, m, 0);
+            }
+            Ok(())
+        })
+        .unwrap();
+
+    // on sunday, only the end of friday's M1F1 runs
+    let departures = objects.vehicle_journeys_on(date(7));
+    assert_eq!(departures.len(), 1);
+    assert_eq!(departures[0].vehicle_journey.id, "M1F1");
+    assert_eq!(departures[0].service_date, date(5));
+    assert_eq!(departures[0].stop_times.len(), 1);
+    assert_eq!(departures[0].departure_time(), Time::new(0, 10, 0));
+
+    // on saturday, the end of thursday's M1F1 and the middle of
+    // friday's M1F1 run
+    let departures = objects.vehicle_journeys_on(date(6));
+    assert_eq!(departures.len(), 2);
+    let stop_times_of = |service_date| {
+        departures
+            .iter()
+            .find(|d| d.service_date == service_date)
+            .map(|d| d.stop_times.len())
+    };
+    assert_eq!(stop_times_of(date(4)), Some(1));
+    assert_eq!(stop_times_of(date(5)), Some(2));
+}
@@ -0,0 +1,146 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate navitia_model;
+extern crate tempdir;
+
+mod common;
+
+use common::copy_fixtures;
+use navitia_model::error::ErrorKind;
+use navitia_model::ntfs::{self, ReadOptions};
+use navitia_model::objects::{Line, PhysicalMode, StopArea, StopPoint};
+use navitia_model::snapshot::{self, WriteOptions};
+use navitia_model::PtObjects;
+use std::fs;
+use std::path::Path;
+use tempdir::TempDir;
+
+fn read_fixtures(path: &Path) -> PtObjects {
+    copy_fixtures(path);
+    fs::write(
+        path.join("object_codes.txt"),
+        "object_type,object_id,object_system,object_code\n\
+         line,M1,source,L1\n\
+         stop_point,GDLM,source,SP1\n\
+         contributor,TGC,source,C1\n\
+         dataset,TGDS,source,D1\n\
+         line_group,LG,source,G1\n",
+    ).unwrap();
+    fs::write(
+        path.join("line_groups.txt"),
+        "line_group_id,line_group_name,main_line_id\nLG,Metro and bus,M1\n",
+    ).unwrap();
+    fs::write(
+        path.join("line_group_links.txt"),
+        "line_group_id,line_id\nLG,M1\nLG,B42\n",
+    ).unwrap();
+    fs::write(
+        path.join("calendar_dates.txt"),
+        "service_id,date,exception_type\nWeek,20180102,2\n",
+    ).unwrap();
+    fs::write(path.join("unknown.txt"), "foo\n").unwrap();
+    let options = ReadOptions {
+        keep_extra_data: true,
+        ..ReadOptions::default()
+    };
+    ntfs::read_with_options(path, &options).unwrap()
+}
+
+fn assert_same_objects(objects: &PtObjects, loaded: &PtObjects) {
+    assert_eq!(loaded.contributors, objects.contributors);
+    assert_eq!(loaded.datasets, objects.datasets);
+    assert_eq!(loaded.networks, objects.networks);
+    assert_eq!(loaded.lines, objects.lines);
+    assert_eq!(loaded.routes, objects.routes);
+    assert_eq!(loaded.vehicle_journeys, objects.vehicle_journeys);
+    assert_eq!(loaded.stop_areas, objects.stop_areas);
+    assert_eq!(loaded.stop_points, objects.stop_points);
+    assert_eq!(loaded.calendars, objects.calendars);
+    assert_eq!(loaded.comments, objects.comments);
+    assert_eq!(loaded.transfers, objects.transfers);
+    assert_eq!(loaded.grid_calendars, objects.grid_calendars);
+    assert_eq!(loaded.feed_infos, objects.feed_infos);
+    assert_eq!(loaded.line_groups, objects.line_groups);
+    assert_eq!(loaded.line_group_links, objects.line_group_links);
+    assert_eq!(loaded.extra_data, objects.extra_data);
+    for (idx, _) in objects.lines.iter() {
+        assert_eq!(
+            loaded.get_corresponding_from_idx::<Line, StopPoint>(idx),
+            objects.get_corresponding_from_idx::<Line, StopPoint>(idx)
+        );
+        assert_eq!(
+            loaded.get_corresponding_from_idx::<Line, PhysicalMode>(idx),
+            objects.get_corresponding_from_idx::<Line, PhysicalMode>(idx)
+        );
+    }
+}
+
+#[test]
+fn round_trip() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let objects = read_fixtures(tmp_dir.path());
+    let m1 = objects.lines.get("M1").unwrap();
+    assert_eq!(m1.codes, vec![("source".to_string(), "L1".to_string())]);
+    assert!(!objects.lines.get("RERA").unwrap().comment_links.is_empty());
+    assert!(!objects.extra_data.is_empty());
+    assert!(!objects.calendars.get("Week").unwrap().calendar_dates.is_empty());
+
+    for &relations in &[false, true] {
+        let path = tmp_dir.path().join("model.snapshot");
+        snapshot::write(&objects, &path, &WriteOptions { relations }).unwrap();
+        let loaded = snapshot::read(&path).unwrap();
+        assert_same_objects(&objects, &loaded);
+    }
+}
+
+#[test]
+fn relations_make_a_bigger_snapshot() {
+    let objects = ntfs::read("fixtures/ntfs/").unwrap();
+    let mut without_relations = vec![];
+    snapshot::write_to(&objects, &mut without_relations, &WriteOptions::default()).unwrap();
+    let mut with_relations = vec![];
+    let options = WriteOptions { relations: true };
+    snapshot::write_to(&objects, &mut with_relations, &options).unwrap();
+    assert!(with_relations.len() > without_relations.len());
+
+    let loaded = snapshot::read_from(with_relations.as_slice()).unwrap();
+    let gdl = loaded.stop_areas.get_idx("GDL").unwrap();
+    let stop_points = loaded.get_corresponding_from_idx::<StopArea, StopPoint>(gdl);
+    assert_eq!(stop_points.len(), 3);
+}
+
+#[test]
+fn invalid_snapshots() {
+    let objects = ntfs::read("fixtures/ntfs/").unwrap();
+    let mut bytes = vec![];
+    snapshot::write_to(&objects, &mut bytes, &WriteOptions::default()).unwrap();
+
+    assert!(snapshot::read_from(&bytes[..bytes.len() / 2]).is_err());
+    assert!(snapshot::read_from(&b"stop_id,stop_name\n"[..]).is_err());
+
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path().join("model.snapshot");
+    bytes[8..12].copy_from_slice(&(snapshot::VERSION + 1).to_le_bytes());
+    fs::write(&path, &bytes).unwrap();
+    match snapshot::read(&path) {
+        Ok(_) => panic!("a snapshot of another version should not be read"),
+        Err(err) => match *err.kind() {
+            ErrorKind::InvalidValue { field, .. } => assert_eq!(field, "snapshot_version"),
+            ref kind => panic!("unexpected {:?}", kind),
+        },
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate navitia_model;
+extern crate tempdir;
+
+mod common;
+
+use common::{copy_fixtures, replace};
+use navitia_model::ntfs::{self, ReadOptions};
+use navitia_model::objects::*;
+use navitia_model::stop_times::{interpolate_times, StopTimeIssue, StopTimeIssueKind};
+use tempdir::TempDir;
+
+fn issue(vj_id: &str, sequence: Option<u32>, kind: StopTimeIssueKind) -> StopTimeIssue {
+    StopTimeIssue {
+        vehicle_journey_id: vj_id.to_string(),
+        sequence,
+        kind,
+    }
+}
+
+#[test]
+fn validate_stop_times() {
+    let objects = ntfs::read("fixtures/ntfs/").unwrap();
+    assert_eq!(objects.validate_stop_times(), vec![]);
+
+    let objects = objects
+        .edit(|c| {
+            let idx = c.vehicle_journeys.get_idx("M1F1").unwrap();
+            {
+                let mut vj = c.vehicle_journeys.index_mut(idx);
+                // sequences 0, 1, 1, 3
+                vj.stop_times[2].sequence = 1;
+                vj.stop_times[1].arrival_time = Time::new(9, 5, 0);
+                vj.stop_times[1].departure_time = Time::new(9, 5, 0);
+                vj.stop_times[2].arrival_time = Time::new(9, 4, 0);
+                vj.stop_times[2].departure_time = Time::new(9, 3, 0);
+            }
+            let idx = c.vehicle_journeys.get_idx("B42F1").unwrap();
+            c.vehicle_journeys.index_mut(idx).stop_times.truncate(1);
+            let idx = c.stop_points.get_idx("CDGR").unwrap();
+            c.stop_points.index_mut(idx).coord = Coord {
+                lon: 5.37,
+                lat: 43.30,
+            };
+            // the speed is not checked without coordinate
+            let idx = c.stop_points.get_idx("NATM").unwrap();
+            c.stop_points.index_mut(idx).coord = Coord { lon: 0., lat: 0. };
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        objects.validate_stop_times(),
+        vec![
+            issue("M1F1", Some(1), StopTimeIssueKind::DepartureBeforeArrival),
+            issue("M1F1", Some(1), StopTimeIssueKind::DuplicateSequence),
+            issue("M1F1", Some(1), StopTimeIssueKind::DecreasingTimes),
+            issue("B42F1", None, StopTimeIssueKind::SingleStop),
+            issue("RERAF1", Some(3), StopTimeIssueKind::ImplausibleSpeed),
+            issue("RERAF1", Some(5), StopTimeIssueKind::ImplausibleSpeed),
+            issue("RERAB1", Some(8), StopTimeIssueKind::ImplausibleSpeed),
+            issue("RERAB1", Some(13), StopTimeIssueKind::ImplausibleSpeed),
+        ]
+    );
+}
+
+#[test]
+fn interpolate_missing_times() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    replace(path, "stop_times.txt", "M1F1,1,GDLM,09:10:00,09:10:00", "M1F1,1,GDLM,,");
+    replace(path, "stop_times.txt", "M1F1,2,CHAM,09:20:00,09:20:00", "M1F1,2,CHAM,,");
+
+    assert!(ntfs::read(path).is_err());
+    let options = ReadOptions {
+        interpolate_missing_times: true,
+        ..ReadOptions::default()
+    };
+    let objects = ntfs::read_with_options(path, &options).unwrap();
+    let stop_times = &objects.vehicle_journeys.get("M1F1").unwrap().stop_times;
+    let estimated: Vec<_> = stop_times.iter().map(|st| st.datetime_estimated).collect();
+    assert_eq!(estimated, &[false, true, true, false]);
+    assert_eq!(stop_times[0].departure_time, Time::new(9, 0, 0));
+    assert_eq!(stop_times[3].arrival_time, Time::new(9, 40, 0));
+    for st in &stop_times[1..3] {
+        assert_eq!(st.arrival_time, st.departure_time);
+    }
+    assert!(Time::new(9, 0, 0) < stop_times[1].arrival_time);
+    assert!(stop_times[1].arrival_time < stop_times[2].arrival_time);
+    assert!(stop_times[2].arrival_time < Time::new(9, 40, 0));
+    assert_eq!(objects.validate_stop_times(), vec![]);
+
+    replace(path, "stop_times.txt", "M1F1,3,CDGM,09:40:00,09:40:00", "M1F1,3,CDGM,,");
+    assert!(ntfs::read_with_options(path, &options).is_err());
+}
+
+#[test]
+fn interpolate_times_needs_a_flag_by_stop_time() {
+    let objects = ntfs::read("fixtures/ntfs/").unwrap();
+    let mut stop_times = objects.vehicle_journeys.get("M1F1").unwrap().stop_times.clone();
+    let missing = [false, true, false];
+    assert!(interpolate_times(&mut stop_times, &missing, &objects.stop_points).is_err());
+    let missing = [false, true, true, false];
+    assert!(interpolate_times(&mut stop_times, &missing, &objects.stop_points).is_ok());
+}
@@ -0,0 +1,106 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate navitia_model;
+extern crate tempdir;
+
+mod common;
+
+use common::copy_fixtures;
+use navitia_model::ntfs::{self, ReadOptions};
+use std::fs;
+use tempdir::TempDir;
+
+#[test]
+fn visit_vehicle_journeys() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    let objects = ntfs::read(path).unwrap();
+    let out = tmp_dir.path().join("out");
+    fs::create_dir(&out).unwrap();
+    ntfs::write(&out, &objects, ntfs::Version::default()).unwrap();
+
+    let streamed = tmp_dir.path().join("streamed");
+    fs::create_dir(&streamed).unwrap();
+    let mut writer = ntfs::StopTimesWriter::new(&streamed).unwrap();
+    let mut visited = vec![];
+    let (collections, _) =
+        ntfs::visit_vehicle_journeys(path, &ReadOptions::default(), |c, vj| {
+            let id = &c.vehicle_journeys[vj.idx].id;
+            assert_eq!(vj.stop_times, &objects.vehicle_journeys.get(id).unwrap().stop_times[..]);
+            visited.push(id.clone());
+            Ok(writer.write(c, vj)?)
+        }).unwrap();
+    writer.finish().unwrap();
+    visited.sort();
+    assert_eq!(visited, ["B42B1", "B42F1", "M1B1", "M1F1", "RERAB1", "RERAF1"]);
+    assert!(collections.vehicle_journeys.iter().all(|(_, vj)| vj.stop_times.is_empty()));
+    assert_eq!(
+        fs::read_to_string(streamed.join("stop_times.txt")).unwrap(),
+        fs::read_to_string(out.join("stop_times.txt")).unwrap()
+    );
+
+    let stop_times = fs::read_to_string(path.join("stop_times.txt")).unwrap();
+    let last = "M1F1,3,CDGM,09:40:00,09:40:00\n";
+    let stop_times = stop_times.replace(last, "") + last;
+    fs::write(path.join("stop_times.txt"), stop_times).unwrap();
+    assert!(ntfs::read(path).is_ok());
+    let res = ntfs::visit_vehicle_journeys(path, &ReadOptions::default(), |_, _| Ok(()));
+    assert!(res.is_err());
+}
+
+#[test]
+fn stream_stop_time_extras() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let input = tmp_dir.path().join("input");
+    fs::create_dir(&input).unwrap();
+    let objects = ntfs::read("fixtures/ntfs/").unwrap();
+    let objects = objects
+        .edit(|c| {
+            let comment_idx = c.comments.get_idx("RERACOM1").unwrap();
+            let idx = c.vehicle_journeys.get_idx("M1F1").unwrap();
+            let mut vj = c.vehicle_journeys.index_mut(idx);
+            for st in &mut vj.stop_times {
+                st.extras_mut().id = Some(format!("M1F1:{}", st.sequence));
+            }
+            let st = vj.stop_times[1].extras_mut();
+            st.codes.push(("source".to_string(), "st-1".to_string()));
+            st.object_properties.push(("platform".to_string(), "2".to_string()));
+            st.comment_links.push(comment_idx);
+            Ok(())
+        })
+        .unwrap();
+    ntfs::write(&input, &objects, ntfs::Version::default()).unwrap();
+
+    let output = tmp_dir.path().join("output");
+    fs::create_dir(&output).unwrap();
+    let mut writer = ntfs::StopTimesWriter::new(&output).unwrap();
+    let (collections, _) = ntfs::visit_vehicle_journeys(&input, &ReadOptions::default(), |c, vj| {
+        Ok(writer.write(c, vj)?)
+    }).unwrap();
+    let streamed = navitia_model::PtObjects::new(collections).unwrap();
+    ntfs::write_with_stop_times(&output, &streamed, &Default::default(), writer).unwrap();
+
+    let read = ntfs::read(&output).unwrap();
+    let stop_times = &read.vehicle_journeys.get("M1F1").unwrap().stop_times;
+    assert_eq!(stop_times, &objects.vehicle_journeys.get("M1F1").unwrap().stop_times);
+    assert_eq!(stop_times[1].id(), Some("M1F1:1"));
+    let st = stop_times[1].extras.as_ref().unwrap();
+    assert_eq!(st.codes, &[("source".to_string(), "st-1".to_string())]);
+    assert_eq!(st.object_properties, &[("platform".to_string(), "2".to_string())]);
+    assert_eq!(read.comments[st.comment_links[0]].id, "RERACOM1");
+}
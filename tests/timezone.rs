@@ -0,0 +1,239 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate chrono;
+extern crate navitia_model;
+extern crate tempdir;
+
+use chrono::{NaiveDate, TimeZone, Utc};
+use navitia_model::ntfs;
+use navitia_model::objects::*;
+use navitia_model::read_report::Strictness;
+use navitia_model::timezone::to_utc;
+use std::fs;
+use tempdir::TempDir;
+
+fn paris() -> Timezone {
+    "Europe/Paris".parse().unwrap()
+}
+
+#[test]
+fn utc_on_dst_changes() {
+    let utc = |y, m, d, h, min| Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap();
+
+    let date = NaiveDate::from_ymd_opt(2018, 1, 5).unwrap();
+    assert_eq!(to_utc(paris(), date, Time::new(8, 0, 0)), utc(2018, 1, 5, 7, 0));
+    assert_eq!(to_utc(paris(), date, Time::new(25, 0, 0)), utc(2018, 1, 6, 0, 0));
+
+    // clocks go from 02:00 to 03:00
+    let date = NaiveDate::from_ymd_opt(2018, 3, 25).unwrap();
+    assert_eq!(to_utc(paris(), date, Time::new(1, 30, 0)), utc(2018, 3, 25, 0, 30));
+    assert_eq!(to_utc(paris(), date, Time::new(2, 30, 0)), utc(2018, 3, 25, 1, 30));
+    assert_eq!(to_utc(paris(), date, Time::new(3, 30, 0)), utc(2018, 3, 25, 1, 30));
+
+    // clocks go from 03:00 back to 02:00
+    let date = NaiveDate::from_ymd_opt(2018, 10, 28).unwrap();
+    assert_eq!(to_utc(paris(), date, Time::new(1, 30, 0)), utc(2018, 10, 27, 23, 30));
+    assert_eq!(to_utc(paris(), date, Time::new(2, 30, 0)), utc(2018, 10, 28, 0, 30));
+    assert_eq!(to_utc(paris(), date, Time::new(3, 30, 0)), utc(2018, 10, 28, 2, 30));
+}
+
+#[test]
+fn vehicle_journey_timezone() {
+    let objects = ntfs::read("fixtures/ntfs/").unwrap();
+    let vj_idx = objects.vehicle_journeys.get_idx("M1F1").unwrap();
+    assert_eq!(objects.vehicle_journey_timezone(vj_idx), None);
+    let date = NaiveDate::from_ymd_opt(2018, 1, 5).unwrap();
+    let stop_time = &objects.vehicle_journeys[vj_idx].stop_times[0];
+    assert!(objects.stop_time_to_utc(vj_idx, date, stop_time).is_err());
+
+    let objects = objects
+        .edit(|collections| {
+            let idx = collections.networks.get_idx("TGN").unwrap();
+            collections.networks.index_mut(idx).timezone = Some(paris());
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(objects.vehicle_journey_timezone(vj_idx), Some(paris()));
+    let stop_time = &objects.vehicle_journeys[vj_idx].stop_times[0];
+    assert_eq!(
+        objects.stop_time_to_utc(vj_idx, date, stop_time).unwrap(),
+        (
+            Utc.with_ymd_and_hms(2018, 1, 5, 8, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2018, 1, 5, 8, 0, 0).unwrap()
+        )
+    );
+
+    let objects = objects
+        .edit(|collections| {
+            let idx = collections.stop_areas.get_idx("NAT").unwrap();
+            collections.stop_areas.index_mut(idx).timezone = "Europe/London".parse().ok();
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        objects.vehicle_journey_timezone(vj_idx),
+        "Europe/London".parse().ok()
+    );
+}
+
+#[test]
+fn invalid_timezones() {
+    let objects = ntfs::read("fixtures/ntfs/").unwrap();
+    let objects = objects
+        .edit(|collections| {
+            let idx = collections.networks.get_idx("TGN").unwrap();
+            collections.networks.index_mut(idx).timezone = Some(paris());
+            Ok(())
+        })
+        .unwrap();
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    ntfs::write(path, &objects, ntfs::Version::CURRENT).unwrap();
+    let networks = fs::read_to_string(path.join("networks.txt")).unwrap();
+    assert!(networks.contains("Europe/Paris"));
+    fs::write(
+        path.join("networks.txt"),
+        networks.replace("Europe/Paris", "Mars/Olympus"),
+    ).unwrap();
+
+    assert!(ntfs::read(path).is_err());
+
+    let options = ntfs::ReadOptions {
+        strictness: Strictness::Lenient,
+        ..ntfs::ReadOptions::default()
+    };
+    let (objects, report) = ntfs::read_with_report(path, &options).unwrap();
+    assert_eq!(objects.networks.get("TGN").unwrap().timezone, None);
+    assert!(report.skipped_rows.is_empty());
+    assert_eq!(report.ignored_values.len(), 1);
+    assert_eq!(report.ignored_values[0].file, "networks.txt");
+    assert_eq!(report.ignored_values[0].line, Some(2));
+}
+
+fn with_network_timezone(objects: navitia_model::PtObjects) -> navitia_model::PtObjects {
+    objects
+        .edit(|collections| {
+            let idx = collections.networks.get_idx("TGN").unwrap();
+            collections.networks.index_mut(idx).timezone = Some(paris());
+            Ok(())
+        })
+        .unwrap()
+}
+
+fn times(objects: &navitia_model::PtObjects, vj_id: &str) -> Vec<(Time, Time)> {
+    objects
+        .vehicle_journeys
+        .get(vj_id)
+        .unwrap()
+        .stop_times
+        .iter()
+        .map(|st| (st.arrival_time, st.departure_time))
+        .collect()
+}
+
+#[test]
+fn normalize_in_the_same_timezone() {
+    let objects = with_network_timezone(ntfs::read("fixtures/ntfs/").unwrap());
+    let normalized = with_network_timezone(ntfs::read("fixtures/ntfs/").unwrap())
+        .normalize_timezones(paris())
+        .unwrap();
+    assert_eq!(normalized.vehicle_journeys.len(), objects.vehicle_journeys.len());
+    assert_eq!(times(&normalized, "M1F1"), times(&objects, "M1F1"));
+    assert_eq!(normalized.vehicle_journeys.get("M1F1").unwrap().service_id, "Week");
+}
+
+#[test]
+fn normalize_across_dst_changes() {
+    let objects = with_network_timezone(ntfs::read("fixtures/ntfs/").unwrap());
+    let nb_vjs = objects.vehicle_journeys.len();
+    let objects = objects
+        .normalize_timezones("UTC".parse().unwrap())
+        .unwrap()
+        .edit(|collections| collections.compress_calendars())
+        .unwrap();
+
+    // each vehicle journey is split between winter and summer times
+    assert_eq!(objects.vehicle_journeys.len(), 2 * nb_vjs);
+    assert_eq!(times(&objects, "M1F1")[0].0, Time::new(8, 0, 0));
+    assert_eq!(times(&objects, "M1F1:1")[0].0, Time::new(7, 0, 0));
+
+    let winter = &objects.vehicle_journeys.get("M1F1").unwrap().service_id;
+    let summer = &objects.vehicle_journeys.get("M1F1:1").unwrap().service_id;
+    assert_eq!(objects.vehicle_journeys.get("B42F1").unwrap().service_id, *winter);
+    assert_eq!(objects.vehicle_journeys.get("B42F1:1").unwrap().service_id, *summer);
+    let winter = objects.calendars.get(winter).unwrap();
+    let summer = objects.calendars.get(summer).unwrap();
+    let date = |m, d| NaiveDate::from_ymd_opt(2018, m, d).unwrap();
+    assert!(winter.is_active(date(1, 1)) && winter.is_active(date(12, 31)));
+    assert!(!winter.is_active(date(6, 1)));
+    assert!(summer.is_active(date(6, 1)));
+    assert_eq!((summer.start_date, summer.end_date), (date(3, 26), date(10, 26)));
+}
+
+#[test]
+fn normalize_gives_unique_stop_time_ids() {
+    let objects = with_network_timezone(ntfs::read("fixtures/ntfs/").unwrap());
+    let objects = objects
+        .edit(|collections| {
+            let idx = collections.vehicle_journeys.get_idx("M1F1").unwrap();
+            let mut vj = collections.vehicle_journeys.index_mut(idx);
+            for (i, st) in vj.stop_times.iter_mut().enumerate() {
+                st.extras_mut().id = Some(format!("M1F1:{}", i));
+            }
+            Ok(())
+        })
+        .unwrap();
+    let objects = objects.normalize_timezones("UTC".parse().unwrap()).unwrap();
+
+    let ids = |vj_id: &str| -> Vec<Option<String>> {
+        let vj = objects.vehicle_journeys.get(vj_id).unwrap();
+        vj.stop_times.iter().map(|st| st.id().map(str::to_string)).collect()
+    };
+    assert_eq!(ids("M1F1")[0], Some("M1F1:0".to_string()));
+    assert_eq!(ids("M1F1:1")[0], Some("M1F1:0:1".to_string()));
+    let mut all_ids: Vec<_> = objects
+        .vehicle_journeys
+        .iter()
+        .flat_map(|(_, vj)| vj.stop_times.iter().filter_map(|st| st.id()))
+        .collect();
+    let nb_ids = all_ids.len();
+    all_ids.sort();
+    all_ids.dedup();
+    assert_eq!(all_ids.len(), nb_ids);
+    assert_eq!(nb_ids, 2 * ids("M1F1").len());
+}
+
+#[test]
+fn normalize_across_timezones() {
+    let objects = with_network_timezone(ntfs::read("fixtures/ntfs/").unwrap());
+    let objects = objects
+        .edit(|collections| {
+            collections.vehicle_journeys.retain(|vj| vj.id == "M1B1");
+            let idx = collections.stop_areas.get_idx("NAT").unwrap();
+            collections.stop_areas.index_mut(idx).timezone = "Europe/London".parse().ok();
+            Ok(())
+        })
+        .unwrap();
+    // the local time at Nation is before the one of the previous stop
+    assert_eq!(times(&objects, "M1B1")[3].0, Time::new(11, 10, 0));
+
+    let objects = objects.normalize_timezones(paris()).unwrap();
+    assert_eq!(objects.vehicle_journeys.len(), 1);
+    assert_eq!(times(&objects, "M1B1")[0].0, Time::new(10, 40, 0));
+    assert_eq!(times(&objects, "M1B1")[3].0, Time::new(12, 10, 0));
+    assert_eq!(objects.vehicle_journeys.get("M1B1").unwrap().service_id, "Week");
+}
@@ -0,0 +1,118 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate navitia_model;
+#[macro_use]
+extern crate serde_json;
+extern crate tempdir;
+
+mod common;
+
+use common::{copy_fixtures, replace};
+use navitia_model::ntfs::{self, ReadOptions};
+use navitia_model::read_report::Strictness;
+use navitia_model::validate::{IssueKind, Severity};
+use std::fs;
+use tempdir::TempDir;
+
+#[test]
+fn validate_fixtures() {
+    let (collections, _) =
+        ntfs::read_collections("fixtures/ntfs/", &ReadOptions::default()).unwrap();
+    let report = collections.validate();
+    assert!(report.issues.is_empty());
+    assert!(report.is_valid());
+}
+
+#[test]
+fn validate_broken_references() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    replace(path, "lines.txt", "M1,Metro 1,TGN", "M1,Metro 1,UNKNOWN");
+    replace(path, "trips.txt", "M1F,Week,M1F1", "M1F,Weekend,M1F1");
+    replace(path, "stops.txt", "TOUR,Tour Eiffel,48.858370", "TOUR,Tour Eiffel,148.858370");
+    assert!(ntfs::read(path).is_err());
+
+    let (collections, _) = ntfs::read_collections(path, &ReadOptions::default()).unwrap();
+    let report = collections.validate();
+    assert!(!report.is_valid());
+    assert_eq!(report.count(Severity::Error), 4);
+    let issues: Vec<_> = report
+        .issues
+        .iter()
+        .map(|i| (i.file.as_str(), i.object_id.as_str(), i.field, i.kind))
+        .collect();
+    assert_eq!(
+        issues,
+        &[
+            ("lines.txt", "M1", "network_id", IssueKind::UnknownReference),
+            ("trips.txt", "M1F1", "service_id", IssueKind::UnknownReference),
+            ("stops.txt", "Navitia:TOUR", "stop_lat", IssueKind::InvalidValue),
+            ("stops.txt", "TOUR", "stop_lat", IssueKind::InvalidValue),
+        ]
+    );
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(
+        json["issues"][0],
+        json!({
+            "severity": "error",
+            "kind": "unknown_reference",
+            "file": "lines.txt",
+            "object_id": "M1",
+            "field": "network_id",
+            "message": "network_id=\"UNKNOWN\" not found",
+        })
+    );
+}
+
+#[test]
+fn validate_read_issues() {
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+    copy_fixtures(path);
+    fs::write(
+        path.join("lines.txt"),
+        "line_id,line_name,network_id,commercial_mode_id,line_color\n\
+         M1,Metro 1,TGN,Metro,yellow\n\
+         B42,Bus 42,TGN,Bus,\n\
+         RERA,RER A,TGN,RER,\n",
+    ).unwrap();
+    replace(path, "comment_links.txt", "RERA,line", "RERB,line");
+
+    let options = ReadOptions {
+        strictness: Strictness::Lenient,
+        ..ReadOptions::default()
+    };
+    let (collections, read_report) = ntfs::read_collections(path, &options).unwrap();
+    let mut report = collections.validate();
+    assert!(report.is_valid());
+    report.add_read_report(read_report);
+    assert!(!report.is_valid());
+    let issues: Vec<_> = report
+        .issues
+        .iter()
+        .map(|i| (i.file.as_str(), i.kind, i.source.is_some()))
+        .collect();
+    assert_eq!(
+        issues,
+        &[
+            ("comment_links.txt", IssueKind::SkippedRow, true),
+            ("lines.txt", IssueKind::IgnoredValue, true),
+        ]
+    );
+}
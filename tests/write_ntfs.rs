@@ -0,0 +1,64 @@
+// Copyright 2017-2018 Kisio Digital and/or its affiliates.
+//
+// This program is free software: you can redistribute it and/or
+// modify it under the terms of the GNU General Public License as
+// published by the Free Software Foundation, either version 3 of the
+// License, or (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful, but
+// WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the GNU
+// General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see
+// <http://www.gnu.org/licenses/>.
+
+extern crate navitia_model;
+extern crate tempdir;
+
+use navitia_model::ntfs::{self, GeneratedStopAreas, Version, WriteOptions};
+use tempdir::TempDir;
+
+#[test]
+fn write_older_version() {
+    let objects = ntfs::read("fixtures/ntfs/").unwrap();
+    assert!(objects.grid_calendars.len() > 0);
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+
+    ntfs::write(path, &objects, Version::V0_6).unwrap();
+    assert!(!path.join("grid_calendars.txt").exists());
+
+    let written = ntfs::read(path).unwrap();
+    assert_eq!(written.feed_infos["ntfs_version"], "0.6");
+    assert_eq!(written.grid_calendars.len(), 0);
+    assert_eq!(written.lines.len(), objects.lines.len());
+
+    assert!(ntfs::write(path, &objects, Version::V0_5).is_err());
+}
+
+#[test]
+fn write_generated_stop_areas() {
+    let objects = ntfs::read("fixtures/ntfs/").unwrap();
+    let tmp_dir = TempDir::new("navitia_model_tests").unwrap();
+    let path = tmp_dir.path();
+
+    ntfs::write(path, &objects, Version::CURRENT).unwrap();
+    let written = ntfs::read(path).unwrap();
+    assert!(written.stop_areas.get("Navitia:TOUR").unwrap().generated);
+    assert_eq!(written.stop_areas.len(), objects.stop_areas.len());
+
+    let options = WriteOptions {
+        generated_stop_areas: GeneratedStopAreas::Materialize,
+        ..WriteOptions::default()
+    };
+    ntfs::write_with_options(path, &objects, &options).unwrap();
+    let written = ntfs::read(path).unwrap();
+    assert!(!written.stop_areas.get("Navitia:TOUR").unwrap().generated);
+    assert_eq!(written.stop_areas.len(), objects.stop_areas.len());
+    assert_eq!(
+        written.stop_points.get("TOUR").unwrap().stop_area_id,
+        "Navitia:TOUR"
+    );
+}